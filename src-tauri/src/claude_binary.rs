@@ -33,10 +33,15 @@ pub struct ClaudeInstallation {
 /// Main function to find the Claude binary
 /// Checks database first for stored path and preference, then prioritizes accordingly
 pub fn find_claude_binary(app_handle: &tauri::AppHandle) -> Result<String, String> {
+    find_claude_binary_in(app_handle.path().app_data_dir().ok().as_deref())
+}
+
+/// Find the Claude binary using the settings stored in `app_data_dir`, if given
+pub fn find_claude_binary_in(app_data_dir: Option<&std::path::Path>) -> Result<String, String> {
     info!("Searching for claude binary...");
 
     // First check if we have a stored path and preference in the database
-    if let Ok(app_data_dir) = app_handle.path().app_data_dir() {
+    if let Some(app_data_dir) = app_data_dir {
         let db_path = app_data_dir.join("agents.db");
         if db_path.exists() {
             if let Ok(conn) = rusqlite::Connection::open(&db_path) {
@@ -499,7 +504,7 @@ fn find_standard_installations() -> Vec<ClaudeInstallation> {
 }
 
 /// Get Claude version by running --version command
-fn get_claude_version(path: &str) -> Result<Option<String>, String> {
+pub(crate) fn get_claude_version(path: &str) -> Result<Option<String>, String> {
     match Command::new(path).arg("--version").output() {
         Ok(output) => {
             if output.status.success() {
@@ -578,7 +583,7 @@ fn select_best_installation(installations: Vec<ClaudeInstallation>) -> Option<Cl
 }
 
 /// Compare two version strings
-fn compare_versions(a: &str, b: &str) -> Ordering {
+pub(crate) fn compare_versions(a: &str, b: &str) -> Ordering {
     // Simple semantic version comparison
     let a_parts: Vec<u32> = a
         .split('.')
@@ -0,0 +1,319 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+mod checkpoint;
+mod claude_binary;
+mod commands;
+mod process;
+mod stream_event;
+
+use commands::agents::{AgentDb, AgentRunHost, AgentRunRequest};
+use process::ProcessRegistry;
+use stream_event::{StreamEvent, StreamLine};
+
+#[derive(Parser)]
+#[command(name = "opcode-cli")]
+#[command(about = "Run Opcode agents from scripts and CI")]
+struct Args {
+    /// Opcode data directory holding agents.db (defaults to the app's)
+    #[arg(long, global = true)]
+    data_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the agents stored in the database
+    List,
+    /// Run an agent and stream its output; exits non-zero unless the run completes
+    Run {
+        /// Agent ID or name
+        agent: String,
+
+        /// Project directory to run the agent in
+        #[arg(short, long)]
+        project: PathBuf,
+
+        /// Task for the agent (defaults to the agent's default task; --param renders it from the agent's task template instead)
+        #[arg(short, long)]
+        task: Option<String>,
+
+        /// Model to use instead of the agent's
+        #[arg(short, long)]
+        model: Option<String>,
+
+        /// Task parameter as key=value; values are parsed as JSON when possible
+        #[arg(long = "param", value_name = "KEY=VALUE")]
+        params: Vec<String>,
+
+        /// Run in an isolated git worktree
+        #[arg(long)]
+        isolate: bool,
+
+        /// Stop the run once it has cost this many USD
+        #[arg(long)]
+        max_cost: Option<f64>,
+
+        /// Stop the run once it has used this many tokens
+        #[arg(long)]
+        max_tokens: Option<i64>,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "text")]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    /// Raw stream-json lines as emitted by Claude
+    Json,
+    /// Human-readable summary of the stream
+    Text,
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let args = Args::parse();
+    let code = match run(args).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            2
+        }
+    };
+    std::process::exit(code);
+}
+
+async fn run(args: Args) -> Result<i32, String> {
+    let data_dir = match args.data_dir {
+        Some(dir) => dir,
+        None => dirs::data_dir()
+            .ok_or("Could not determine the data directory")?
+            .join("opcode.asterisk.so"),
+    };
+    let conn = commands::agents::init_database_in(&data_dir)
+        .map_err(|e| format!("Failed to open agents database: {}", e))?;
+    let db = AgentDb(Mutex::new(conn));
+
+    match args.command {
+        Command::List => {
+            for agent in db.agents()? {
+                println!(
+                    "{}\t{}\t{}",
+                    agent.id.unwrap_or_default(),
+                    agent.name,
+                    agent.model
+                );
+            }
+            Ok(0)
+        }
+        Command::Run {
+            agent,
+            project,
+            task,
+            model,
+            params,
+            isolate,
+            max_cost,
+            max_tokens,
+            format,
+        } => {
+            let agent = db
+                .agents()?
+                .into_iter()
+                .find(|a| a.name == agent || a.id.is_some_and(|id| id.to_string() == agent))
+                .ok_or_else(|| format!("No agent with ID or name '{}'", agent))?;
+            let project = project
+                .canonicalize()
+                .map_err(|e| format!("Invalid project path: {}", e))?;
+            let parameters = params
+                .iter()
+                .map(|p| parse_param(p))
+                .collect::<Result<HashMap<_, _>, _>>()?;
+            // With parameters the task is rendered from the agent's task template
+            let task = match task.or(agent.default_task.clone()) {
+                Some(task) => task,
+                None if !parameters.is_empty() => String::new(),
+                None => return Err("No task given and the agent has no default task".to_string()),
+            };
+
+            let request = AgentRunRequest {
+                agent_id: agent.id.ok_or("Agent has no ID")?,
+                project_path: project.to_string_lossy().to_string(),
+                task,
+                model,
+                max_cost_usd: max_cost,
+                max_tokens,
+                isolate,
+                parameters: (!parameters.is_empty()).then_some(parameters),
+                parent_run_id: None,
+            };
+            run_agent(data_dir, &db, request, format).await
+        }
+    }
+}
+
+async fn run_agent(
+    data_dir: PathBuf,
+    db: &AgentDb,
+    request: AgentRunRequest,
+    format: OutputFormat,
+) -> Result<i32, String> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<(String, JsonValue)>();
+    let host = AgentRunHost {
+        data_dir,
+        permissions: None,
+        emitter: Arc::new(move |event, payload| {
+            let _ = tx.send((event.to_string(), payload));
+        }),
+    };
+    let registry = Arc::new(ProcessRegistry::new());
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        registry
+            .set_cancellation_settings(commands::cancellation::load_cancellation_settings(&conn))?;
+    }
+
+    let run_id = commands::agents::start_agent_run(&host, db, registry.clone(), request).await?;
+    // Drop our handle so the channel closes once the run's tasks are done
+    drop(host);
+
+    let output = format!("agent-output:{}", run_id);
+    let error = format!("agent-error:{}", run_id);
+    let complete = format!("agent-complete:{}", run_id);
+    let mut cancelled = false;
+
+    loop {
+        tokio::select! {
+            event = rx.recv() => {
+                let Some((event, payload)) = event else { break };
+                if event == output {
+                    if let Some(line) = payload.as_str() {
+                        print_output(line, format);
+                    }
+                } else if event == error {
+                    if let Some(line) = payload.as_str() {
+                        eprintln!("{}", line);
+                    }
+                } else if event == complete {
+                    break;
+                } else if event.ends_with(&format!(":{}", run_id)) {
+                    eprintln!("{}: {}", event, payload);
+                }
+            }
+            _ = tokio::signal::ctrl_c(), if !cancelled => {
+                cancelled = true;
+                eprintln!("Cancelling run {}...", run_id);
+                registry.kill_process(run_id).await?;
+            }
+        }
+    }
+
+    let run = db.agent_run(run_id)?;
+    if format == OutputFormat::Text {
+        eprintln!("Run {} {}", run_id, run.status);
+    }
+    Ok(if run.status == "completed" { 0 } else { 1 })
+}
+
+/// Parse a `key=value` task parameter, taking the value as JSON when it is valid JSON
+fn parse_param(param: &str) -> Result<(String, JsonValue), String> {
+    let (key, value) = param
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid parameter '{}', expected KEY=VALUE", param))?;
+    let value =
+        serde_json::from_str(value).unwrap_or_else(|_| JsonValue::String(value.to_string()));
+    Ok((key.to_string(), value))
+}
+
+fn print_output(line: &str, format: OutputFormat) {
+    match format {
+        OutputFormat::Json => println!("{}", line),
+        OutputFormat::Text => {
+            for text in render_line(line) {
+                println!("{}", text);
+            }
+        }
+    }
+}
+
+/// Render a stream-json line as human-readable lines
+fn render_line(line: &str) -> Vec<String> {
+    let Some(message) = StreamLine::parse(line) else {
+        return vec![line.to_string()];
+    };
+
+    message
+        .events
+        .iter()
+        .filter_map(|event| match event {
+            StreamEvent::SystemInit {
+                session_id, model, ..
+            } => Some(format!(
+                "Session {} ({})",
+                session_id,
+                model.as_deref().unwrap_or("unknown model")
+            )),
+            StreamEvent::AssistantText { text } => Some(text.clone()),
+            StreamEvent::ToolUse { name, .. } => Some(format!("→ {}", name)),
+            StreamEvent::Result(result) => {
+                let mut summary = format!("Result: {}", result.subtype);
+                if let Some(cost) = result.cost_usd {
+                    summary.push_str(&format!(", ${:.4}", cost));
+                }
+                if let Some(turns) = result.num_turns {
+                    summary.push_str(&format!(", {} turns", turns));
+                }
+                Some(summary)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renders_stream_json() {
+        assert_eq!(
+            render_line(r#"{"type":"system","subtype":"init","session_id":"abc","model":"opus"}"#),
+            vec!["Session abc (opus)"]
+        );
+        assert_eq!(
+            render_line(
+                r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Hi"},{"type":"tool_use","name":"Bash"}]}}"#
+            ),
+            vec!["Hi", "→ Bash"]
+        );
+        assert_eq!(
+            render_line(
+                r#"{"type":"result","subtype":"success","total_cost_usd":0.5,"num_turns":3}"#
+            ),
+            vec!["Result: success, $0.5000, 3 turns"]
+        );
+        assert!(render_line(r#"{"type":"user"}"#).is_empty());
+    }
+
+    #[test]
+    fn test_parses_params() {
+        assert_eq!(
+            parse_param("count=3").unwrap(),
+            ("count".to_string(), serde_json::json!(3))
+        );
+        assert_eq!(
+            parse_param("name=a=b").unwrap(),
+            ("name".to_string(), serde_json::json!("a=b"))
+        );
+        assert!(parse_param("missing").is_err());
+    }
+}
@@ -0,0 +1,465 @@
+#![allow(dead_code)]
+use log::{error, info};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::commands::agent_compare::RunActivity;
+use crate::commands::agents::{start_agent_run, AgentDb, AgentRunHost, AgentRunRequest};
+
+const DEFAULT_CONCURRENCY: usize = 3;
+
+/// A run of one agent over many projects
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentBatch {
+    pub id: i64,
+    pub agent_id: i64,
+    pub task: String,
+    pub model: Option<String>,
+    pub concurrency: i64,
+    pub status: String, // 'running', 'completed', 'failed', 'interrupted'
+    pub created_at: String,
+    pub completed_at: Option<String>,
+}
+
+/// The run of a batch in one project
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentBatchItem {
+    pub project_path: String,
+    pub run_id: Option<i64>,
+    pub status: String, // 'pending', 'running', or the status of the finished run
+    /// Why the run could not be started
+    pub error: Option<String>,
+    pub cost_usd: Option<f64>,
+    pub result_text: Option<String>,
+}
+
+/// A batch with the outcome of each of its runs
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentBatchSummary {
+    #[serde(flatten)]
+    pub batch: AgentBatch,
+    pub items: Vec<AgentBatchItem>,
+    pub completed: usize,
+    pub failed: usize,
+    pub total_cost_usd: f64,
+}
+
+/// Create the tables tracking batch runs
+pub fn init_tables(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS agent_batches (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id INTEGER NOT NULL,
+            task TEXT NOT NULL,
+            model TEXT,
+            concurrency INTEGER NOT NULL,
+            status TEXT NOT NULL DEFAULT 'running',
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            completed_at TEXT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS agent_batch_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            batch_id INTEGER NOT NULL,
+            project_path TEXT NOT NULL,
+            run_id INTEGER,
+            status TEXT NOT NULL DEFAULT 'pending',
+            error TEXT,
+            FOREIGN KEY (batch_id) REFERENCES agent_batches(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn batch_from_row(row: &rusqlite::Row) -> rusqlite::Result<AgentBatch> {
+    Ok(AgentBatch {
+        id: row.get(0)?,
+        agent_id: row.get(1)?,
+        task: row.get(2)?,
+        model: row.get(3)?,
+        concurrency: row.get(4)?,
+        status: row.get(5)?,
+        created_at: row.get(6)?,
+        completed_at: row.get(7)?,
+    })
+}
+
+const BATCH_COLUMNS: &str =
+    "id, agent_id, task, model, concurrency, status, created_at, completed_at";
+
+/// Record the status of a batch item; failures are only logged, so the batch
+/// carries on with its other items
+fn set_item_status(
+    db: &AgentDb,
+    item_id: i64,
+    run_id: Option<i64>,
+    status: &str,
+    error: Option<&str>,
+) {
+    let updated = db.0.lock().map_err(|e| e.to_string()).and_then(|conn| {
+        conn.execute(
+            "UPDATE agent_batch_items SET run_id = COALESCE(?1, run_id), status = ?2, error = ?3
+             WHERE id = ?4",
+            params![run_id, status, error, item_id],
+        )
+        .map_err(|e| e.to_string())
+    });
+    if let Err(e) = updated {
+        error!("Failed to update agent batch item {}: {}", item_id, e);
+    }
+}
+
+/// Record the final status of a batch; items it never finished fail with `error`
+fn finish_batch(db: &AgentDb, batch_id: i64, status: &str, error: Option<&str>) {
+    let updated = db.0.lock().map_err(|e| e.to_string()).and_then(|conn| {
+        conn.execute(
+            "UPDATE agent_batch_items SET status = 'failed', error = ?1
+             WHERE batch_id = ?2 AND status IN ('pending', 'running')",
+            params![error, batch_id],
+        )
+        .map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE agent_batches SET status = ?1, completed_at = CURRENT_TIMESTAMP
+             WHERE id = ?2",
+            params![status, batch_id],
+        )
+        .map_err(|e| e.to_string())
+    });
+    match updated {
+        Ok(_) => info!("Agent batch {} {}", batch_id, status),
+        Err(e) => error!("Failed to finish agent batch {}: {}", batch_id, e),
+    }
+}
+
+/// Mark the batches left running by a previous instance of the app
+/// `interrupted`; returns how many there were
+///
+/// Their unfinished items take the status of their run if it has finished,
+/// e.g. after startup recovery marked it `interrupted`, and are otherwise
+/// `interrupted` as well: no batch is left to start or follow them.
+pub fn reconcile_interrupted_batches(conn: &Connection) -> Result<usize, String> {
+    conn.execute(
+        "UPDATE agent_batch_items SET status = COALESCE(
+             (SELECT status FROM agent_runs
+              WHERE agent_runs.id = agent_batch_items.run_id
+              AND agent_runs.status NOT IN ('pending', 'running')),
+             'interrupted')
+         WHERE status IN ('pending', 'running')
+         AND batch_id IN (SELECT id FROM agent_batches WHERE status = 'running')",
+        [],
+    )
+    .map_err(|e| format!("Failed to reconcile agent batch items: {}", e))?;
+    conn.execute(
+        "UPDATE agent_batches SET status = 'interrupted', completed_at = CURRENT_TIMESTAMP
+         WHERE status = 'running'",
+        [],
+    )
+    .map_err(|e| format!("Failed to reconcile agent batches: {}", e))
+}
+
+/// Run the items of a batch, at most `concurrency` at a time
+async fn run_batch(
+    app: AppHandle,
+    batch_id: i64,
+    request: AgentRunRequest,
+    items: Vec<(i64, String)>,
+    concurrency: usize,
+) -> Result<(), String> {
+    let db = app.state::<AgentDb>();
+    let registry = app
+        .state::<crate::process::ProcessRegistryState>()
+        .0
+        .clone();
+
+    // Forward all events to the app and collect the completions of runs
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<i64>();
+    let app_host = AgentRunHost::from_app(&app)?;
+    let host = AgentRunHost {
+        data_dir: app_host.data_dir.clone(),
+        permissions: app_host.permissions.clone(),
+        emitter: Arc::new(move |event, payload| {
+            if let Some(run_id) = event
+                .strip_prefix("agent-complete:")
+                .and_then(|id| id.parse().ok())
+            {
+                let _ = tx.send(run_id);
+            }
+            (app_host.emitter)(event, payload);
+        }),
+    };
+
+    let mut pending = items.into_iter();
+    let mut running: HashMap<i64, i64> = HashMap::new();
+    loop {
+        while running.len() < concurrency {
+            let Some((item_id, project_path)) = pending.next() else {
+                break;
+            };
+            let request = AgentRunRequest {
+                project_path: project_path.clone(),
+                ..request.clone()
+            };
+            match start_agent_run(&host, &db, registry.clone(), request).await {
+                Ok(run_id) => {
+                    set_item_status(&db, item_id, Some(run_id), "running", None);
+                    running.insert(run_id, item_id);
+                }
+                Err(e) => {
+                    error!(
+                        "Batch {} failed to start in {}: {}",
+                        batch_id, project_path, e
+                    );
+                    set_item_status(&db, item_id, None, "failed", Some(&e));
+                    let _ = app.emit(&format!("agent-batch-progress:{}", batch_id), item_id);
+                }
+            }
+        }
+
+        if running.is_empty() {
+            break;
+        }
+        let Some(run_id) = rx.recv().await else {
+            break;
+        };
+        if let Some(item_id) = running.remove(&run_id) {
+            match db.agent_run(run_id) {
+                Ok(run) => set_item_status(&db, item_id, None, &run.status, None),
+                Err(e) => {
+                    error!("Batch {} failed to load run {}: {}", batch_id, run_id, e);
+                    set_item_status(&db, item_id, None, "failed", Some(&e));
+                }
+            }
+            let _ = app.emit(&format!("agent-batch-progress:{}", batch_id), item_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Run an agent over many projects and return the batch ID
+///
+/// Without `project_paths` the agent runs in every project from `list_projects`.
+/// At most `concurrency` runs (default 3) execute at the same time; progress is
+/// reported through `agent-batch-progress:{id}` and `agent-batch-complete:{id}`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_agent_batch(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+    project_paths: Option<Vec<String>>,
+    task: String,
+    model: Option<String>,
+    max_cost_usd: Option<f64>,
+    max_tokens: Option<i64>,
+    isolate: Option<bool>,
+    parameters: Option<HashMap<String, JsonValue>>,
+    concurrency: Option<usize>,
+) -> Result<i64, String> {
+    let mut project_paths = match project_paths {
+        Some(paths) => paths,
+        None => crate::commands::claude::list_projects(db.clone(), None, None, None)
+            .await?
+            .into_iter()
+            .map(|project| project.path)
+            .collect(),
+    };
+    let mut seen = std::collections::HashSet::new();
+    project_paths.retain(|path| seen.insert(path.clone()));
+    if project_paths.is_empty() {
+        return Err("No projects to run the agent in".to_string());
+    }
+    let concurrency = concurrency.unwrap_or(DEFAULT_CONCURRENCY).max(1);
+
+    let (batch_id, items) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO agent_batches (agent_id, task, model, concurrency) VALUES (?1, ?2, ?3, ?4)",
+            params![agent_id, task, model, concurrency as i64],
+        )
+        .map_err(|e| e.to_string())?;
+        let batch_id = conn.last_insert_rowid();
+
+        let mut items = Vec::new();
+        for project_path in project_paths {
+            conn.execute(
+                "INSERT INTO agent_batch_items (batch_id, project_path) VALUES (?1, ?2)",
+                params![batch_id, project_path],
+            )
+            .map_err(|e| e.to_string())?;
+            items.push((conn.last_insert_rowid(), project_path));
+        }
+        (batch_id, items)
+    };
+    info!(
+        "Starting agent batch {} over {} projects",
+        batch_id,
+        items.len()
+    );
+
+    let request = AgentRunRequest {
+        agent_id,
+        project_path: String::new(),
+        task,
+        model,
+        max_cost_usd,
+        max_tokens,
+        isolate: isolate.unwrap_or(false),
+        parameters,
+        parent_run_id: None,
+    };
+    tokio::spawn(async move {
+        let result = run_batch(app.clone(), batch_id, request, items, concurrency).await;
+        if let Err(e) = &result {
+            error!("Agent batch {} failed: {}", batch_id, e);
+        }
+        let status = if result.is_ok() {
+            "completed"
+        } else {
+            "failed"
+        };
+        finish_batch(
+            &app.state::<AgentDb>(),
+            batch_id,
+            status,
+            result.err().as_deref(),
+        );
+        let _ = app.emit(&format!("agent-batch-complete:{}", batch_id), batch_id);
+    });
+
+    Ok(batch_id)
+}
+
+/// Get a batch with the status, cost and result of each of its runs
+#[tauri::command]
+pub async fn get_agent_batch(
+    db: State<'_, AgentDb>,
+    batch_id: i64,
+) -> Result<AgentBatchSummary, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let batch = conn
+        .query_row(
+            &format!("SELECT {} FROM agent_batches WHERE id = ?1", BATCH_COLUMNS),
+            params![batch_id],
+            batch_from_row,
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT i.project_path, i.run_id, i.status, i.error, r.result_text
+             FROM agent_batch_items i LEFT JOIN agent_runs r ON r.id = i.run_id
+             WHERE i.batch_id = ?1 ORDER BY i.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut items = stmt
+        .query_map(params![batch_id], |row| {
+            Ok(AgentBatchItem {
+                project_path: row.get(0)?,
+                run_id: row.get(1)?,
+                status: row.get(2)?,
+                error: row.get(3)?,
+                cost_usd: None,
+                result_text: row.get(4)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    // Cost and result of finished runs come from their stored output
+    for item in &mut items {
+        let Some(run_id) = item.run_id else { continue };
+        if let Some(output) = crate::commands::agent_output::load(&conn, run_id)? {
+            let activity = RunActivity::from_jsonl(&output);
+            item.cost_usd = activity.cost_usd;
+            item.result_text = item.result_text.take().or(activity.result_text);
+        }
+    }
+
+    Ok(AgentBatchSummary {
+        completed: items.iter().filter(|i| i.status == "completed").count(),
+        failed: items
+            .iter()
+            .filter(|i| !matches!(i.status.as_str(), "pending" | "running" | "completed"))
+            .count(),
+        total_cost_usd: items.iter().filter_map(|i| i.cost_usd).sum(),
+        batch,
+        items,
+    })
+}
+
+/// List batches, newest first
+#[tauri::command]
+pub async fn list_agent_batches(db: State<'_, AgentDb>) -> Result<Vec<AgentBatch>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM agent_batches ORDER BY id DESC",
+            BATCH_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let batches = stmt
+        .query_map([], batch_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconcile_interrupted_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = crate::commands::agents::init_database_in(dir.path()).unwrap();
+        conn.execute(
+            "INSERT INTO agents (id, name, icon, system_prompt) VALUES (1, 'agent', 'bot', '')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO agent_runs (id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status)
+             VALUES (5, 1, 'agent', 'bot', 'task', 'sonnet', '/a', '', 'completed')",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO agent_batches (id, agent_id, task, concurrency) VALUES (1, 1, 'task', 1)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO agent_batch_items (batch_id, project_path, run_id, status)
+             VALUES (1, '/a', 5, 'running'), (1, '/b', NULL, 'pending')",
+            [],
+        )
+        .unwrap();
+
+        assert_eq!(reconcile_interrupted_batches(&conn).unwrap(), 1);
+
+        let status: String = conn
+            .query_row("SELECT status FROM agent_batches WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(status, "interrupted");
+        let items: Vec<String> = conn
+            .prepare("SELECT status FROM agent_batch_items ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(items, vec!["completed", "interrupted"]);
+    }
+}
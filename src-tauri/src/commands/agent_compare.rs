@@ -0,0 +1,283 @@
+#![allow(dead_code)]
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use tauri::State;
+
+use crate::commands::agents::{read_session_jsonl, AgentDb, AgentRun, AgentRunMetrics};
+use crate::stream_event::{parse_jsonl, StreamEvent};
+
+/// Tool inputs naming the file a tool call works on
+const FILE_INPUT_KEYS: &[&str] = &["file_path", "notebook_path"];
+
+/// One side of a run comparison
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RunComparisonSide {
+    pub run: AgentRun,
+    pub metrics: AgentRunMetrics,
+    /// Number of tool calls per tool name
+    pub tool_calls: BTreeMap<String, i64>,
+    /// Files the run's tool calls read or wrote, sorted
+    pub files_touched: Vec<String>,
+    pub result_text: Option<String>,
+    /// Changes of the run against its base commit, for runs isolated in a worktree
+    pub diff: Option<String>,
+    /// Changes made by the Edit, MultiEdit and Write tool calls of runs without a
+    /// worktree, laid out like a diff; see `RunActivity::edit_changes`
+    pub edit_changes: Option<String>,
+}
+
+/// Two agent runs with aligned metrics
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentRunComparison {
+    pub left: RunComparisonSide,
+    pub right: RunComparisonSide,
+    /// Files touched by only one of the runs, sorted
+    pub files_only_left: Vec<String>,
+    pub files_only_right: Vec<String>,
+    /// Diff from the left run's changes to the right run's, when both were isolated
+    /// in worktrees of the same project
+    pub diff_between: Option<String>,
+}
+
+/// A change a tool call made to a file
+#[derive(Debug, PartialEq)]
+pub(crate) struct FileChange {
+    pub tool: String,
+    /// Text replaced by the change; `None` when the file was written as a whole
+    pub old: Option<String>,
+    pub new: String,
+}
+
+/// Tool usage and outcome extracted from a run's stream-json or session JSONL
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct RunActivity {
+    pub tool_calls: BTreeMap<String, i64>,
+    pub files_touched: BTreeSet<String>,
+    /// Changes per file, in the order they were made
+    pub changes: BTreeMap<String, Vec<FileChange>>,
+    pub result_text: Option<String>,
+    pub cost_usd: Option<f64>,
+    pub duration_ms: Option<i64>,
+}
+
+impl RunActivity {
+    pub fn from_jsonl(jsonl: &str) -> Self {
+        let mut activity = Self::default();
+
+        for line in parse_jsonl(jsonl) {
+            for event in line.events {
+                match event {
+                    StreamEvent::ToolUse { name, input, .. } => {
+                        for key in FILE_INPUT_KEYS {
+                            if let Some(path) = input[key].as_str() {
+                                activity.files_touched.insert(path.to_string());
+                            }
+                        }
+                        activity.record_changes(&name, &input);
+                        let name = if name.is_empty() {
+                            "unknown".to_string()
+                        } else {
+                            name
+                        };
+                        *activity.tool_calls.entry(name).or_default() += 1;
+                    }
+                    StreamEvent::Result(result) => {
+                        activity.result_text = result.result;
+                        activity.cost_usd = result.cost_usd;
+                        activity.duration_ms = result.duration_ms;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        activity
+    }
+
+    /// Record the file changes of an Edit, MultiEdit or Write tool call
+    fn record_changes(&mut self, tool: &str, input: &serde_json::Value) {
+        let Some(path) = input["file_path"].as_str() else {
+            return;
+        };
+        let edit = |edit: &serde_json::Value| FileChange {
+            tool: tool.to_string(),
+            old: Some(edit["old_string"].as_str().unwrap_or_default().to_string()),
+            new: edit["new_string"].as_str().unwrap_or_default().to_string(),
+        };
+        let changes: Vec<FileChange> = match tool {
+            "Edit" => vec![edit(input)],
+            "MultiEdit" => input["edits"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(edit)
+                .collect(),
+            "Write" => vec![FileChange {
+                tool: tool.to_string(),
+                old: None,
+                new: input["content"].as_str().unwrap_or_default().to_string(),
+            }],
+            _ => return,
+        };
+        self.changes
+            .entry(path.to_string())
+            .or_default()
+            .extend(changes);
+    }
+
+    /// The recorded file changes laid out like a diff, one hunk per change
+    ///
+    /// This is not a unified diff that `git apply` accepts: tool calls don't tell
+    /// where in the file a change was made, so hunks are headed by the tool
+    /// instead of line ranges.
+    pub fn edit_changes(&self) -> Option<String> {
+        if self.changes.is_empty() {
+            return None;
+        }
+
+        let mut diff = String::new();
+        for (path, changes) in &self.changes {
+            diff.push_str(&format!("--- {}\n+++ {}\n", path, path));
+            for change in changes {
+                diff.push_str(&format!("@@ {} @@\n", change.tool));
+                for line in change.old.as_deref().unwrap_or_default().lines() {
+                    diff.push_str(&format!("-{}\n", line));
+                }
+                for line in change.new.lines() {
+                    diff.push_str(&format!("+{}\n", line));
+                }
+            }
+        }
+        Some(diff)
+    }
+}
+
+/// The JSONL of a run: its session file, or the output stored when it finished
+async fn run_jsonl(db: &AgentDb, run: &AgentRun) -> Result<String, String> {
+    if !run.session_id.is_empty() {
+        if let Ok(jsonl) = read_session_jsonl(&run.session_id, run.working_dir()).await {
+            return Ok(jsonl);
+        }
+    }
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(crate::commands::agent_output::load(&conn, run.id.unwrap_or_default())?.unwrap_or_default())
+}
+
+async fn comparison_side(db: &AgentDb, run_id: i64) -> Result<RunComparisonSide, String> {
+    let run = db.agent_run(run_id)?;
+    let jsonl = run_jsonl(db, &run).await?;
+
+    let mut metrics = AgentRunMetrics::from_jsonl(&jsonl);
+    let activity = RunActivity::from_jsonl(&jsonl);
+    metrics.cost_usd = metrics.cost_usd.or(activity.cost_usd);
+    metrics.duration_ms = metrics.duration_ms.or(activity.duration_ms);
+
+    let (diff, edit_changes) = match run.worktree() {
+        Some(worktree) => (
+            crate::commands::worktree::diff(&run.project_path, &worktree)
+                .map_err(|e| log::warn!("Failed to diff agent run {}: {}", run_id, e))
+                .ok(),
+            None,
+        ),
+        None => (None, activity.edit_changes()),
+    };
+
+    Ok(RunComparisonSide {
+        result_text: run.result_text.clone().or(activity.result_text),
+        tool_calls: activity.tool_calls,
+        files_touched: activity.files_touched.into_iter().collect(),
+        metrics,
+        diff,
+        edit_changes,
+        run,
+    })
+}
+
+/// Compare two agent runs, e.g. of the same agent with different models or prompts
+#[tauri::command]
+pub async fn compare_agent_runs(
+    db: State<'_, AgentDb>,
+    left_run_id: i64,
+    right_run_id: i64,
+) -> Result<AgentRunComparison, String> {
+    let left = comparison_side(&db, left_run_id).await?;
+    let right = comparison_side(&db, right_run_id).await?;
+
+    let only_in = |a: &RunComparisonSide, b: &RunComparisonSide| {
+        a.files_touched
+            .iter()
+            .filter(|file| !b.files_touched.contains(file))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+
+    let diff_between = match (left.run.worktree(), right.run.worktree()) {
+        (Some(from), Some(to)) if left.run.project_path == right.run.project_path => {
+            crate::commands::worktree::diff_between(&left.run.project_path, &from, &to)
+                .map_err(|e| log::warn!("Failed to diff agent runs: {}", e))
+                .ok()
+        }
+        _ => None,
+    };
+
+    Ok(AgentRunComparison {
+        files_only_left: only_in(&left, &right),
+        files_only_right: only_in(&right, &left),
+        left,
+        right,
+        diff_between,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_activity_from_jsonl() {
+        let jsonl = [
+            r#"{"type":"system","subtype":"init"}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Looking"},{"type":"tool_use","name":"Read","input":{"file_path":"/p/a.rs"}}]}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Edit","input":{"file_path":"/p/a.rs"}},{"type":"tool_use","name":"Bash","input":{"command":"ls"}}]}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Edit","input":{"file_path":"/p/b.rs"}}]}}"#,
+            r#"{"type":"result","subtype":"success","result":"Done","total_cost_usd":0.25,"duration_ms":1200}"#,
+        ]
+        .join("\n");
+
+        let activity = RunActivity::from_jsonl(&jsonl);
+        assert_eq!(
+            activity.tool_calls,
+            BTreeMap::from([
+                ("Bash".to_string(), 1),
+                ("Edit".to_string(), 2),
+                ("Read".to_string(), 1)
+            ])
+        );
+        assert_eq!(
+            activity.files_touched.into_iter().collect::<Vec<_>>(),
+            vec!["/p/a.rs", "/p/b.rs"]
+        );
+        assert_eq!(activity.result_text.as_deref(), Some("Done"));
+        assert_eq!(activity.cost_usd, Some(0.25));
+        assert_eq!(activity.duration_ms, Some(1200));
+    }
+
+    #[test]
+    fn test_edit_changes() {
+        let jsonl = [
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Read","input":{"file_path":"/p/a.rs"}}]}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Edit","input":{"file_path":"/p/a.rs","old_string":"let a = 1;","new_string":"let a = 2;"}}]}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"MultiEdit","input":{"file_path":"/p/a.rs","edits":[{"old_string":"b","new_string":"c\nd"}]}}]}}"#,
+            r#"{"type":"assistant","message":{"content":[{"type":"tool_use","name":"Write","input":{"file_path":"/p/new.rs","content":"fn main() {}\n"}}]}}"#,
+        ]
+        .join("\n");
+
+        let activity = RunActivity::from_jsonl(&jsonl);
+        assert_eq!(
+            activity.edit_changes().unwrap(),
+            "--- /p/a.rs\n+++ /p/a.rs\n@@ Edit @@\n-let a = 1;\n+let a = 2;\n@@ MultiEdit @@\n-b\n+c\n+d\n\
+             --- /p/new.rs\n+++ /p/new.rs\n@@ Write @@\n+fn main() {}\n"
+        );
+        assert_eq!(RunActivity::from_jsonl("").edit_changes(), None);
+    }
+}
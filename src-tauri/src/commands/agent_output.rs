@@ -0,0 +1,203 @@
+#![allow(dead_code)]
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use zstd::stream::{decode_all, encode_all};
+
+use crate::commands::agents::AgentDb;
+
+const RETENTION_SETTINGS_KEY: &str = "agent_output_retention";
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// How long the stored stdout of agent runs is kept
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AgentOutputRetention {
+    /// Delete the output of runs older than this many days
+    pub max_age_days: Option<u32>,
+    /// Delete the output of the oldest runs once all stored output exceeds this many
+    /// (compressed) megabytes
+    pub max_total_mb: Option<u32>,
+}
+
+impl Default for AgentOutputRetention {
+    fn default() -> Self {
+        Self {
+            max_age_days: Some(30),
+            max_total_mb: Some(256),
+        }
+    }
+}
+
+impl AgentOutputRetention {
+    /// Load the retention policy from app_settings
+    pub fn load(conn: &Connection) -> Self {
+        conn.query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![RETENTION_SETTINGS_KEY],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+    }
+}
+
+/// Create the table holding the compressed stdout of agent runs
+pub fn init_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS agent_run_outputs (
+            run_id INTEGER PRIMARY KEY,
+            output BLOB NOT NULL,
+            size INTEGER NOT NULL,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (run_id) REFERENCES agent_runs(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Store the stdout of a run, replacing any output stored for it before
+pub fn store(conn: &Connection, run_id: i64, output: &str) -> Result<(), String> {
+    let compressed = encode_all(output.as_bytes(), COMPRESSION_LEVEL)
+        .map_err(|e| format!("Failed to compress agent output: {}", e))?;
+    conn.execute(
+        "INSERT OR REPLACE INTO agent_run_outputs (run_id, output, size) VALUES (?1, ?2, ?3)",
+        params![run_id, compressed, output.len() as i64],
+    )
+    .map_err(|e| format!("Failed to store agent output: {}", e))?;
+    Ok(())
+}
+
+/// The stored stdout of a run, if any
+pub fn load(conn: &Connection, run_id: i64) -> Result<Option<String>, String> {
+    let compressed = match conn.query_row(
+        "SELECT output FROM agent_run_outputs WHERE run_id = ?1",
+        params![run_id],
+        |row| row.get::<_, Vec<u8>>(0),
+    ) {
+        Ok(compressed) => compressed,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+        Err(e) => return Err(e.to_string()),
+    };
+    let output = decode_all(&compressed[..])
+        .map_err(|e| format!("Failed to decompress agent output: {}", e))?;
+    Ok(Some(String::from_utf8_lossy(&output).into_owned()))
+}
+
+/// Delete stored output according to the retention policy; returns the number of runs
+/// whose output was deleted
+pub fn prune(conn: &Connection, retention: &AgentOutputRetention) -> Result<usize, String> {
+    let mut deleted = 0;
+
+    if let Some(days) = retention.max_age_days {
+        deleted += conn
+            .execute(
+                "DELETE FROM agent_run_outputs WHERE created_at < datetime('now', ?1)",
+                params![format!("-{} days", days)],
+            )
+            .map_err(|e| e.to_string())?;
+    }
+
+    if let Some(max_mb) = retention.max_total_mb {
+        let limit = max_mb as i64 * 1024 * 1024;
+        let mut stmt = conn
+            .prepare(
+                "SELECT run_id, length(output) FROM agent_run_outputs
+                 ORDER BY created_at DESC, run_id DESC",
+            )
+            .map_err(|e| e.to_string())?;
+        let sizes = stmt
+            .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        let mut total = 0;
+        for (run_id, size) in sizes {
+            total += size;
+            if total > limit {
+                deleted += conn
+                    .execute(
+                        "DELETE FROM agent_run_outputs WHERE run_id = ?1",
+                        params![run_id],
+                    )
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// Get the retention policy for stored agent run output
+#[tauri::command]
+pub async fn get_agent_output_retention(
+    db: State<'_, AgentDb>,
+) -> Result<AgentOutputRetention, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(AgentOutputRetention::load(&conn))
+}
+
+/// Save the retention policy for stored agent run output and apply it
+#[tauri::command]
+pub async fn save_agent_output_retention(
+    db: State<'_, AgentDb>,
+    retention: AgentOutputRetention,
+) -> Result<usize, String> {
+    let json = serde_json::to_string(&retention).map_err(|e| e.to_string())?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+        params![RETENTION_SETTINGS_KEY, json],
+    )
+    .map_err(|e| format!("Failed to save agent output retention: {}", e))?;
+
+    prune(&conn, &retention)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE agent_runs (id INTEGER PRIMARY KEY);
+             CREATE TABLE app_settings (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+             INSERT INTO agent_runs (id) VALUES (1), (2);",
+        )
+        .unwrap();
+        init_table(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_stores_and_prunes_output() {
+        let conn = test_db();
+        store(&conn, 1, "{\"type\":\"system\"}\n").unwrap();
+        store(&conn, 2, "new").unwrap();
+        assert_eq!(
+            load(&conn, 1).unwrap().as_deref(),
+            Some("{\"type\":\"system\"}\n")
+        );
+        assert_eq!(load(&conn, 4).unwrap(), None);
+
+        conn.execute(
+            "UPDATE agent_run_outputs SET created_at = datetime('now', '-40 days') WHERE run_id = 1",
+            [],
+        )
+        .unwrap();
+        assert_eq!(prune(&conn, &AgentOutputRetention::default()).unwrap(), 1);
+        assert_eq!(load(&conn, 1).unwrap(), None);
+        assert_eq!(load(&conn, 2).unwrap().as_deref(), Some("new"));
+
+        let retention = AgentOutputRetention {
+            max_age_days: None,
+            max_total_mb: Some(0),
+        };
+        assert_eq!(prune(&conn, &retention).unwrap(), 1);
+        assert_eq!(load(&conn, 2).unwrap(), None);
+    }
+}
@@ -0,0 +1,448 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+use tauri::AppHandle;
+
+use crate::commands::slash_commands;
+
+/// Current version of the agent package format
+///
+/// Version 1 only carried the agent's name, icon, prompt, default task, model
+/// and hooks. Version 2 carries every agent field plus bundled resources.
+pub const AGENT_PACKAGE_VERSION: u32 = 2;
+
+fn default_true() -> bool {
+    true
+}
+
+/// The agent definition within a package
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PackagedAgent {
+    pub name: String,
+    pub icon: String,
+    pub system_prompt: String,
+    #[serde(default)]
+    pub default_task: Option<String>,
+    pub model: String,
+    #[serde(default = "default_true")]
+    pub enable_file_read: bool,
+    #[serde(default = "default_true")]
+    pub enable_file_write: bool,
+    #[serde(default)]
+    pub enable_network: bool,
+    #[serde(default)]
+    pub hooks: Option<String>,
+    #[serde(default)]
+    pub first_output_timeout_secs: Option<i64>,
+    #[serde(default)]
+    pub max_runtime_secs: Option<i64>,
+    #[serde(default)]
+    pub idle_timeout_secs: Option<i64>,
+    #[serde(default)]
+    pub max_cost_usd: Option<f64>,
+    #[serde(default)]
+    pub max_tokens: Option<i64>,
+    #[serde(default)]
+    pub parameters: Option<String>,
+    #[serde(default)]
+    pub task_template: Option<String>,
+}
+
+/// Result of merging an upstream update into a possibly modified local agent
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentMerge {
+    pub merged: PackagedAgent,
+    /// Fields changed upstream that were taken over
+    pub updated: Vec<String>,
+    /// Fields changed both upstream and locally, where the local value was kept
+    pub preserved: Vec<String>,
+}
+
+impl PackagedAgent {
+    /// Three-way merge of an upstream update
+    ///
+    /// `base` is the agent as it was imported. Fields changed upstream since
+    /// then are taken over unless they were also changed locally, so local
+    /// overrides survive updates.
+    pub fn merge_update(base: &Self, local: &Self, upstream: &Self) -> Result<AgentMerge, String> {
+        let base = to_map(base)?;
+        let mut merged = to_map(local)?;
+        let mut updated = Vec::new();
+        let mut preserved = Vec::new();
+
+        for (field, upstream_value) in to_map(upstream)? {
+            let base_value = base.get(&field);
+            if base_value == Some(&upstream_value) {
+                continue;
+            }
+            if merged.get(&field) == base_value {
+                merged.insert(field.clone(), upstream_value);
+                updated.push(field);
+            } else if merged.get(&field) != Some(&upstream_value) {
+                preserved.push(field);
+            }
+        }
+
+        Ok(AgentMerge {
+            merged: serde_json::from_value(JsonValue::Object(merged))
+                .map_err(|e| format!("Failed to merge agent update: {}", e))?,
+            updated,
+            preserved,
+        })
+    }
+}
+
+fn to_map(agent: &PackagedAgent) -> Result<serde_json::Map<String, JsonValue>, String> {
+    match serde_json::to_value(agent).map_err(|e| e.to_string())? {
+        JsonValue::Object(map) => Ok(map),
+        _ => Err("Agent did not serialize to an object".to_string()),
+    }
+}
+
+/// A slash command bundled with an agent, installed as a user command
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PackagedSlashCommand {
+    pub name: String,
+    #[serde(default)]
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub allowed_tools: Vec<String>,
+    pub content: String,
+}
+
+/// A CLAUDE.md fragment bundled with an agent, added to its system prompt
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ClaudeMdFragment {
+    #[serde(default)]
+    pub title: Option<String>,
+    pub content: String,
+}
+
+/// A versioned, self-contained agent package
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AgentPackage {
+    pub version: u32,
+    pub exported_at: String,
+    pub agent: PackagedAgent,
+    #[serde(default)]
+    pub slash_commands: Vec<PackagedSlashCommand>,
+    /// MCP server definitions by name, in the JSON format of `claude mcp add-json`
+    #[serde(default)]
+    pub mcp_servers: BTreeMap<String, JsonValue>,
+    #[serde(default)]
+    pub claude_md: Vec<ClaudeMdFragment>,
+}
+
+impl AgentPackage {
+    /// Parse a package, upgrading older versions to the current format
+    pub fn parse(json_data: &str) -> Result<Self, String> {
+        let value: JsonValue =
+            serde_json::from_str(json_data).map_err(|e| format!("Invalid JSON format: {}", e))?;
+
+        match value.get("version").and_then(|v| v.as_u64()) {
+            // Fields missing from version 1 get the defaults its importer used
+            Some(1) | Some(2) => {
+                let mut package: AgentPackage = serde_json::from_value(value)
+                    .map_err(|e| format!("Invalid agent package: {}", e))?;
+                package.version = AGENT_PACKAGE_VERSION;
+                Ok(package)
+            }
+            Some(version) => Err(format!(
+                "Unsupported export version: {}. This version of the app supports versions 1 and {}.",
+                version, AGENT_PACKAGE_VERSION
+            )),
+            None => Err("Invalid agent package: missing version".to_string()),
+        }
+    }
+}
+
+/// A bundled resource that was not installed because it clashes with an existing one
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PackageConflict {
+    /// "slash_command" or "mcp_server"
+    pub kind: String,
+    pub name: String,
+    pub reason: String,
+}
+
+/// Whether a configured MCP server runs what `config` describes
+fn server_matches(server: &crate::commands::mcp::MCPServer, config: &JsonValue) -> bool {
+    let args: Vec<String> = config["args"]
+        .as_array()
+        .map(|args| {
+            args.iter()
+                .filter_map(|a| a.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default();
+    server.command.as_deref() == config["command"].as_str()
+        && server.args == args
+        && server.url.as_deref() == config["url"].as_str()
+}
+
+/// Install the slash commands and MCP servers bundled in a package at user scope
+///
+/// Existing commands and servers are only overwritten when `previous`, the
+/// package they were installed from, has them unchanged: that is how updates
+/// replace resources installed from an older version. Otherwise identical
+/// commands are left alone and anything else is reported as a conflict.
+/// Returns the names of the installed resources and the conflicts.
+pub async fn install_resources(
+    app: &AppHandle,
+    package: &AgentPackage,
+    previous: Option<&AgentPackage>,
+) -> (Vec<String>, Vec<PackageConflict>) {
+    let mut installed = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for command in &package.slash_commands {
+        let display_name = match &command.namespace {
+            Some(ns) => format!("/{}:{}", ns, command.name),
+            None => format!("/{}", command.name),
+        };
+        let conflict = |reason: String| PackageConflict {
+            kind: "slash_command".to_string(),
+            name: display_name.clone(),
+            reason,
+        };
+
+        let base_dir = match slash_commands::user_commands_dir() {
+            Ok(dir) => dir,
+            Err(e) => {
+                conflicts.push(conflict(e));
+                continue;
+            }
+        };
+        let file_path = slash_commands::command_file_path(
+            &base_dir,
+            command.namespace.as_deref(),
+            &command.name,
+        );
+        if file_path.exists() {
+            let file = |command: &PackagedSlashCommand| {
+                slash_commands::build_command_file(
+                    &command.content,
+                    command.description.as_deref(),
+                    &command.allowed_tools,
+                )
+            };
+            let existing = std::fs::read_to_string(&file_path).ok();
+            if existing.as_deref() == Some(file(command).as_str()) {
+                continue;
+            }
+            let installed_before = previous
+                .into_iter()
+                .flat_map(|p| &p.slash_commands)
+                .find(|c| c.name == command.name && c.namespace == command.namespace)
+                .is_some_and(|c| existing.as_deref() == Some(file(c).as_str()));
+            if !installed_before {
+                conflicts.push(conflict(
+                    "A different command with this name already exists".to_string(),
+                ));
+                continue;
+            }
+        }
+
+        match slash_commands::slash_command_save(
+            "user".to_string(),
+            command.name.clone(),
+            command.namespace.clone(),
+            command.content.clone(),
+            command.description.clone(),
+            command.allowed_tools.clone(),
+            None,
+        )
+        .await
+        {
+            Ok(_) => installed.push(display_name),
+            Err(e) => conflicts.push(conflict(e)),
+        }
+    }
+
+    for (name, config) in &package.mcp_servers {
+        let conflict = |reason: String| PackageConflict {
+            kind: "mcp_server".to_string(),
+            name: name.clone(),
+            reason,
+        };
+
+        if let Ok(server) = crate::commands::mcp::mcp_get(app.clone(), name.clone()).await {
+            let before = previous.and_then(|p| p.mcp_servers.get(name));
+            if before == Some(config) {
+                continue;
+            }
+            let installed_before = server.scope == "user"
+                && before.is_some_and(|before| server_matches(&server, before));
+            if !installed_before {
+                conflicts.push(conflict(
+                    "An MCP server with this name is already configured".to_string(),
+                ));
+                continue;
+            }
+            if let Err(e) = crate::commands::mcp::mcp_remove(app.clone(), name.clone()).await {
+                conflicts.push(conflict(e));
+                continue;
+            }
+        }
+
+        match crate::commands::mcp::mcp_add_json(
+            app.clone(),
+            name.clone(),
+            config.to_string(),
+            "user".to_string(),
+        )
+        .await
+        {
+            Ok(result) if result.success => installed.push(name.clone()),
+            Ok(result) => conflicts.push(conflict(result.message)),
+            Err(e) => conflicts.push(conflict(e)),
+        }
+    }
+
+    if conflicts.is_empty() {
+        info!("Installed {} bundled agent resources", installed.len());
+    } else {
+        warn!(
+            "Installed {} bundled agent resources, {} conflicts",
+            installed.len(),
+            conflicts.len()
+        );
+    }
+
+    (installed, conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_matches() {
+        let server = crate::commands::mcp::MCPServer {
+            name: "docs".to_string(),
+            transport: "stdio".to_string(),
+            command: Some("npx".to_string()),
+            args: vec!["docs-server".to_string()],
+            env: Default::default(),
+            url: None,
+            scope: "user".to_string(),
+            is_active: true,
+            status: crate::commands::mcp::ServerStatus {
+                running: false,
+                error: None,
+                last_checked: None,
+            },
+        };
+        assert!(server_matches(
+            &server,
+            &serde_json::json!({ "command": "npx", "args": ["docs-server"] })
+        ));
+        assert!(!server_matches(
+            &server,
+            &serde_json::json!({ "command": "npx", "args": ["other-server"] })
+        ));
+    }
+
+    #[test]
+    fn test_parse_upgrades_v1() {
+        let v1 = r#"{
+            "version": 1,
+            "exported_at": "2025-01-01T00:00:00Z",
+            "agent": {
+                "name": "Reviewer",
+                "icon": "bot",
+                "system_prompt": "Review code",
+                "default_task": null,
+                "model": "sonnet",
+                "hooks": null
+            }
+        }"#;
+
+        let package = AgentPackage::parse(v1).unwrap();
+        assert_eq!(package.version, AGENT_PACKAGE_VERSION);
+        assert!(package.agent.enable_file_read);
+        assert!(package.agent.enable_file_write);
+        assert!(!package.agent.enable_network);
+        assert!(package.slash_commands.is_empty());
+        assert!(package.mcp_servers.is_empty());
+    }
+
+    #[test]
+    fn test_parse_round_trips_v2() {
+        let package = AgentPackage {
+            version: AGENT_PACKAGE_VERSION,
+            exported_at: "2025-01-01T00:00:00Z".to_string(),
+            agent: PackagedAgent {
+                name: "Reviewer".to_string(),
+                icon: "bot".to_string(),
+                system_prompt: "Review code".to_string(),
+                default_task: Some("Review the diff".to_string()),
+                model: "opus".to_string(),
+                enable_file_read: true,
+                enable_file_write: false,
+                enable_network: true,
+                hooks: None,
+                first_output_timeout_secs: Some(120),
+                max_runtime_secs: None,
+                idle_timeout_secs: None,
+                max_cost_usd: Some(2.5),
+                max_tokens: None,
+                parameters: Some(r#"[{"name":"target","type":"path"}]"#.to_string()),
+                task_template: Some("Review {{target}}".to_string()),
+            },
+            slash_commands: vec![PackagedSlashCommand {
+                name: "review".to_string(),
+                namespace: Some("team".to_string()),
+                description: Some("Run a review".to_string()),
+                allowed_tools: vec!["Read".to_string()],
+                content: "Review $ARGUMENTS".to_string(),
+            }],
+            mcp_servers: BTreeMap::from([(
+                "github".to_string(),
+                serde_json::json!({ "command": "github-mcp", "args": [] }),
+            )]),
+            claude_md: vec![ClaudeMdFragment {
+                title: Some("Style".to_string()),
+                content: "Prefer small diffs".to_string(),
+            }],
+        };
+
+        let json = serde_json::to_string(&package).unwrap();
+        assert_eq!(AgentPackage::parse(&json).unwrap(), package);
+    }
+
+    #[test]
+    fn test_merge_update_preserves_local_overrides() {
+        let base = AgentPackage::parse(
+            r#"{"version": 2, "exported_at": "", "agent": {"name": "Reviewer", "icon": "bot", "system_prompt": "Review code", "model": "sonnet"}}"#,
+        )
+        .unwrap()
+        .agent;
+        let local = PackagedAgent {
+            name: "Reviewer (Imported)".to_string(),
+            model: "opus".to_string(),
+            ..base.clone()
+        };
+        let upstream = PackagedAgent {
+            system_prompt: "Review code carefully".to_string(),
+            model: "haiku".to_string(),
+            ..base.clone()
+        };
+
+        let merge = PackagedAgent::merge_update(&base, &local, &upstream).unwrap();
+        assert_eq!(merge.merged.system_prompt, "Review code carefully");
+        assert_eq!(merge.merged.model, "opus");
+        assert_eq!(merge.merged.name, "Reviewer (Imported)");
+        assert_eq!(merge.updated, ["system_prompt"]);
+        assert_eq!(merge.preserved, ["model"]);
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_version() {
+        let err = AgentPackage::parse(r#"{"version": 3}"#).unwrap_err();
+        assert!(err.contains("Unsupported export version: 3"));
+    }
+}
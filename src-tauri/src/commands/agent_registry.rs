@@ -0,0 +1,758 @@
+#![allow(dead_code)]
+use log::{info, warn};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+
+use crate::commands::agent_trust::{self, AgentImportPreview, AgentTrustSettings};
+use crate::commands::agents::AgentDb;
+
+const SOURCES_SETTINGS_KEY: &str = "agent_sources";
+
+fn default_true() -> bool {
+    true
+}
+
+/// Where the agents of a source are listed
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AgentSourceLocation {
+    /// A directory of `*.opcode.json` files in a GitHub repository
+    Github {
+        /// Repository as `owner/name`
+        repo: String,
+        #[serde(default)]
+        path: String,
+        #[serde(default)]
+        branch: Option<String>,
+        /// API base URL, for GitHub Enterprise (defaults to api.github.com)
+        #[serde(default)]
+        api_url: Option<String>,
+    },
+    /// An index JSON listing agents, either an array of entries or `{"agents": [...]}`
+    Http { url: String },
+    /// A local directory of `*.opcode.json` files
+    Local { path: String },
+}
+
+/// A configured agent registry
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AgentSource {
+    pub id: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub location: AgentSourceLocation,
+    /// Sent as a bearer token with requests to the source's own hosts
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+impl AgentSource {
+    /// The opcode community agents, the only source before registries were configurable
+    fn opcode() -> Self {
+        Self {
+            id: "opcode".to_string(),
+            name: "opcode".to_string(),
+            location: AgentSourceLocation::Github {
+                repo: "getAsterisk/opcode".to_string(),
+                path: "cc_agents".to_string(),
+                branch: None,
+                api_url: None,
+            },
+            token: None,
+            enabled: true,
+        }
+    }
+
+    /// The token to send with a request to `url`
+    ///
+    /// Agents listed by a source may be hosted anywhere, so the token is only
+    /// sent to the source itself: the index URL's origin, or GitHub's API and
+    /// raw file origins.
+    fn token_for(&self, url: &str) -> Option<&str> {
+        let token = self.token.as_deref().filter(|t| !t.trim().is_empty())?;
+        let origin = reqwest::Url::parse(url).ok()?.origin();
+        let own = |url: &str| reqwest::Url::parse(url).is_ok_and(|u| u.origin() == origin);
+        let trusted = match &self.location {
+            AgentSourceLocation::Github { api_url, .. } => match api_url {
+                Some(api_url) => own(api_url),
+                None => own("https://api.github.com") || own("https://raw.githubusercontent.com"),
+            },
+            AgentSourceLocation::Http { url } => own(url),
+            AgentSourceLocation::Local { .. } => false,
+        };
+        trusted.then_some(token)
+    }
+}
+
+/// Stands in for stored tokens in sources sent to the frontend
+pub const REDACTED_TOKEN: &str = "********";
+
+/// Represents an agent file listed by a source
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GitHubAgentFile {
+    pub name: String,
+    pub path: String,
+    pub download_url: String,
+    pub size: i64,
+    pub sha: String,
+    /// Source the agent was listed by
+    #[serde(default)]
+    pub source_id: String,
+    #[serde(default)]
+    pub source_name: String,
+}
+
+/// A source that could not be listed
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentSourceError {
+    pub source_id: String,
+    pub source_name: String,
+    pub message: String,
+}
+
+/// Agents listed across all enabled sources
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AgentRegistryListing {
+    pub agents: Vec<GitHubAgentFile>,
+    pub errors: Vec<AgentSourceError>,
+    /// Sources that could not be reached and were listed from the cache
+    pub cached_sources: Vec<String>,
+}
+
+/// Represents the GitHub API response for directory contents
+#[derive(Debug, Deserialize)]
+struct GitHubApiResponse {
+    name: String,
+    path: String,
+    sha: String,
+    size: i64,
+    download_url: Option<String>,
+    #[serde(rename = "type")]
+    file_type: String,
+}
+
+/// An entry of an HTTP index
+#[derive(Debug, Deserialize)]
+struct IndexEntry {
+    name: String,
+    #[serde(alias = "url")]
+    download_url: String,
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    size: Option<i64>,
+    #[serde(default)]
+    sha: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum IndexDocument {
+    List(Vec<IndexEntry>),
+    Object { agents: Vec<IndexEntry> },
+}
+
+/// A response body kept for offline use, with the ETag it was served with
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    etag: Option<String>,
+    body: String,
+}
+
+/// A fetched response body
+struct Fetched {
+    body: String,
+    /// The source could not be reached and the body is from the cache
+    stale: bool,
+}
+
+/// Load the configured sources, defaulting to the opcode community agents
+pub fn load_sources(conn: &Connection) -> Vec<AgentSource> {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![SOURCES_SETTINGS_KEY],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+    .and_then(|json| match serde_json::from_str(&json) {
+        Ok(sources) => Some(sources),
+        Err(e) => {
+            warn!("Ignoring invalid agent sources setting: {}", e);
+            None
+        }
+    })
+    .unwrap_or_else(|| vec![AgentSource::opcode()])
+}
+
+fn validate_sources(sources: &[AgentSource]) -> Result<(), String> {
+    for (i, source) in sources.iter().enumerate() {
+        if source.id.trim().is_empty() || source.name.trim().is_empty() {
+            return Err("Agent sources need an id and a name".to_string());
+        }
+        if sources[..i].iter().any(|s| s.id == source.id) {
+            return Err(format!("Duplicate agent source id: '{}'", source.id));
+        }
+        match &source.location {
+            AgentSourceLocation::Github { repo, .. } => {
+                let parts: Vec<&str> = repo.split('/').collect();
+                if parts.len() != 2 || parts.iter().any(|p| p.is_empty()) {
+                    return Err(format!(
+                        "Invalid GitHub repository '{}', expected owner/name",
+                        repo
+                    ));
+                }
+            }
+            AgentSourceLocation::Http { url } => {
+                reqwest::Url::parse(url)
+                    .map_err(|e| format!("Invalid index URL '{}': {}", url, e))?;
+            }
+            AgentSourceLocation::Local { path } => {
+                if path.trim().is_empty() {
+                    return Err(format!("Agent source '{}' needs a directory", source.name));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cache_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join("agent_registry_cache"))
+        .map_err(|e| format!("Failed to get app data dir: {}", e))
+}
+
+fn cache_file(cache_dir: &Path, url: &str) -> PathBuf {
+    cache_dir.join(format!("{:x}.json", Sha256::digest(url.as_bytes())))
+}
+
+/// GET a URL, revalidating a cached copy with its ETag
+///
+/// When the source can't be reached (network errors and server errors) the
+/// cached copy is returned, so registries can still be browsed offline.
+async fn fetch_cached(
+    client: &reqwest::Client,
+    cache_dir: &Path,
+    url: &str,
+    accept: &str,
+    token: Option<&str>,
+) -> Result<Fetched, String> {
+    let cache_path = cache_file(cache_dir, url);
+    let cached: Option<CachedResponse> = std::fs::read_to_string(&cache_path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok());
+
+    let mut request = client
+        .get(url)
+        .header("Accept", accept)
+        .header("User-Agent", "opcode-App");
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    if let Some(etag) = cached.as_ref().and_then(|c| c.etag.as_deref()) {
+        request = request.header("If-None-Match", etag);
+    }
+
+    let stale = |error: String| match &cached {
+        Some(cached) => {
+            warn!("{}, using cached copy of {}", error, url);
+            Ok(Fetched {
+                body: cached.body.clone(),
+                stale: true,
+            })
+        }
+        None => Err(error),
+    };
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => return stale(format!("Failed to fetch {}: {}", url, e)),
+    };
+
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_MODIFIED {
+        if let Some(cached) = cached {
+            return Ok(Fetched {
+                body: cached.body,
+                stale: false,
+            });
+        }
+    }
+    if status.is_server_error() {
+        return stale(format!("HTTP {} from {}", status, url));
+    }
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(format!("HTTP {} from {}: {}", status, url, error_text));
+    }
+
+    let etag = response
+        .headers()
+        .get("ETag")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let body = match response.text().await {
+        Ok(body) => body,
+        Err(e) => return stale(format!("Failed to read response from {}: {}", url, e)),
+    };
+
+    let entry = CachedResponse {
+        etag,
+        body: body.clone(),
+    };
+    if let Err(e) = std::fs::create_dir_all(cache_dir).and_then(|_| {
+        std::fs::write(
+            &cache_path,
+            serde_json::to_string(&entry).unwrap_or_default(),
+        )
+    }) {
+        warn!("Failed to cache {}: {}", url, e);
+    }
+
+    Ok(Fetched { body, stale: false })
+}
+
+fn parse_github_listing(source: &AgentSource, body: &str) -> Result<Vec<GitHubAgentFile>, String> {
+    let api_files: Vec<GitHubApiResponse> = serde_json::from_str(body)
+        .map_err(|e| format!("Failed to parse GitHub response: {}", e))?;
+
+    // Filter only .opcode.json agent files
+    Ok(api_files
+        .into_iter()
+        .filter(|f| f.name.ends_with(".opcode.json") && f.file_type == "file")
+        .filter_map(|f| {
+            f.download_url.map(|download_url| GitHubAgentFile {
+                name: f.name,
+                path: f.path,
+                download_url,
+                size: f.size,
+                sha: f.sha,
+                source_id: source.id.clone(),
+                source_name: source.name.clone(),
+            })
+        })
+        .collect())
+}
+
+fn parse_index(
+    source: &AgentSource,
+    index_url: &str,
+    body: &str,
+) -> Result<Vec<GitHubAgentFile>, String> {
+    let entries = match serde_json::from_str(body)
+        .map_err(|e| format!("Failed to parse agent index: {}", e))?
+    {
+        IndexDocument::List(entries) | IndexDocument::Object { agents: entries } => entries,
+    };
+    let base = reqwest::Url::parse(index_url).map_err(|e| e.to_string())?;
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            // Entries may point at agent files relative to the index
+            let download_url = base
+                .join(&entry.download_url)
+                .map_err(|e| format!("Invalid URL for agent '{}': {}", entry.name, e))?
+                .to_string();
+            Ok(GitHubAgentFile {
+                path: entry.path.unwrap_or_else(|| entry.name.clone()),
+                sha: entry
+                    .sha
+                    .unwrap_or_else(|| format!("{:x}", Sha256::digest(download_url.as_bytes()))),
+                name: entry.name,
+                download_url,
+                size: entry.size.unwrap_or(0),
+                source_id: source.id.clone(),
+                source_name: source.name.clone(),
+            })
+        })
+        .collect()
+}
+
+fn list_local(source: &AgentSource, dir: &str) -> Result<Vec<GitHubAgentFile>, String> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("Failed to read directory {}: {}", dir, e))?;
+
+    let mut agents = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.ends_with(".opcode.json") || !path.is_file() {
+            continue;
+        }
+        let content =
+            std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+        agents.push(GitHubAgentFile {
+            download_url: path.to_string_lossy().to_string(),
+            path: name.clone(),
+            name,
+            size: content.len() as i64,
+            sha: format!("{:x}", Sha256::digest(&content)),
+            source_id: source.id.clone(),
+            source_name: source.name.clone(),
+        });
+    }
+    agents.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(agents)
+}
+
+/// List the agents of one source, returning whether the listing came from the cache
+async fn list_source(
+    client: &reqwest::Client,
+    cache_dir: &Path,
+    source: &AgentSource,
+) -> Result<(Vec<GitHubAgentFile>, bool), String> {
+    match &source.location {
+        AgentSourceLocation::Github {
+            repo,
+            path,
+            branch,
+            api_url,
+        } => {
+            let mut url = format!(
+                "{}/repos/{}/contents/{}",
+                api_url
+                    .as_deref()
+                    .unwrap_or("https://api.github.com")
+                    .trim_end_matches('/'),
+                repo,
+                path.trim_matches('/')
+            );
+            if let Some(branch) = branch.as_deref().filter(|b| !b.is_empty()) {
+                url = format!("{}?ref={}", url, branch);
+            }
+            let fetched = fetch_cached(
+                client,
+                cache_dir,
+                &url,
+                "application/vnd.github+json",
+                source.token_for(&url),
+            )
+            .await?;
+            Ok((parse_github_listing(source, &fetched.body)?, fetched.stale))
+        }
+        AgentSourceLocation::Http { url } => {
+            let fetched = fetch_cached(
+                client,
+                cache_dir,
+                url,
+                "application/json",
+                source.token_for(url),
+            )
+            .await?;
+            Ok((parse_index(source, url, &fetched.body)?, fetched.stale))
+        }
+        AgentSourceLocation::Local { path } => Ok((list_local(source, path)?, false)),
+    }
+}
+
+/// Get the configured agent sources, with their tokens redacted
+#[tauri::command]
+pub async fn get_agent_sources(db: State<'_, AgentDb>) -> Result<Vec<AgentSource>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(load_sources(&conn)
+        .into_iter()
+        .map(|mut source| {
+            if source.token.is_some() {
+                source.token = Some(REDACTED_TOKEN.to_string());
+            }
+            source
+        })
+        .collect())
+}
+
+/// Save the configured agent sources
+///
+/// A token of `REDACTED_TOKEN` keeps the token stored for the source.
+#[tauri::command]
+pub async fn save_agent_sources(
+    db: State<'_, AgentDb>,
+    mut sources: Vec<AgentSource>,
+) -> Result<(), String> {
+    validate_sources(&sources)?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let stored = load_sources(&conn);
+    for source in &mut sources {
+        if source.token.as_deref() == Some(REDACTED_TOKEN) {
+            source.token = stored
+                .iter()
+                .find(|s| s.id == source.id)
+                .and_then(|s| s.token.clone());
+        }
+    }
+    let json = serde_json::to_string(&sources).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+        params![SOURCES_SETTINGS_KEY, json],
+    )
+    .map_err(|e| format!("Failed to save agent sources: {}", e))?;
+
+    Ok(())
+}
+
+/// List the agents of all enabled sources
+pub async fn list_agents(
+    app: &AppHandle,
+    sources: &[AgentSource],
+) -> Result<AgentRegistryListing, String> {
+    let cache_dir = cache_dir(app)?;
+    let client = reqwest::Client::new();
+
+    let mut listing = AgentRegistryListing::default();
+    for source in sources.iter().filter(|s| s.enabled) {
+        info!("Fetching agents from source '{}'...", source.name);
+        match list_source(&client, &cache_dir, source).await {
+            Ok((agents, stale)) => {
+                if stale {
+                    listing.cached_sources.push(source.id.clone());
+                }
+                listing.agents.extend(agents);
+            }
+            Err(message) => {
+                warn!("Failed to list agent source '{}': {}", source.name, message);
+                listing.errors.push(AgentSourceError {
+                    source_id: source.id.clone(),
+                    source_name: source.name.clone(),
+                    message,
+                });
+            }
+        }
+    }
+
+    info!(
+        "Found {} agents across {} sources",
+        listing.agents.len(),
+        sources.len()
+    );
+    Ok(listing)
+}
+
+/// Fetch the list of agents from all enabled sources
+#[tauri::command]
+pub async fn fetch_github_agents(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+) -> Result<AgentRegistryListing, String> {
+    let sources = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        load_sources(&conn)
+    };
+    list_agents(&app, &sources).await
+}
+
+/// Fetch, verify and preview a specific agent from a source
+///
+/// `source_id` selects the source whose token is sent to the source's own hosts;
+/// without it the URL is fetched anonymously. The detached signature and checksum are looked up next
+/// to the agent file (`<url>.sig` and `<url>.sha256`).
+#[tauri::command]
+pub async fn fetch_github_agent_content(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    download_url: String,
+    source_id: Option<String>,
+) -> Result<AgentImportPreview, String> {
+    fetch_agent_preview(&app, &db, &download_url, source_id, None).await
+}
+
+/// Like `fetch_github_agent_content`, accepting a file with the pinned `expected_sha256`
+pub async fn fetch_agent_preview(
+    app: &AppHandle,
+    db: &State<'_, AgentDb>,
+    download_url: &str,
+    source_id: Option<String>,
+    expected_sha256: Option<&str>,
+) -> Result<AgentImportPreview, String> {
+    info!("Fetching agent content from: {}", download_url);
+
+    let (source, settings) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let source = match source_id {
+            Some(id) => Some(
+                load_sources(&conn)
+                    .into_iter()
+                    .find(|s| s.id == id)
+                    .ok_or_else(|| format!("Unknown agent source: {}", id))?,
+            ),
+            None => None,
+        };
+        (source, AgentTrustSettings::load(&conn))
+    };
+
+    if let Some(AgentSourceLocation::Local { path }) = source.as_ref().map(|s| &s.location) {
+        // Only read files within the source's directory
+        let dir = Path::new(path)
+            .canonicalize()
+            .map_err(|e| format!("Failed to read directory {}: {}", path, e))?;
+        let file = Path::new(download_url)
+            .canonicalize()
+            .map_err(|e| format!("Failed to read agent: {}", e))?;
+        if !file.starts_with(&dir) {
+            return Err("Agent file is outside of its source directory".to_string());
+        }
+        return agent_trust::preview_file(&file.to_string_lossy(), &settings, expected_sha256);
+    }
+
+    let client = reqwest::Client::new();
+    let cache_dir = cache_dir(app)?;
+    let token_for = |url: &str| source.as_ref().and_then(|s| s.token_for(url));
+    let json_text = fetch_cached(
+        &client,
+        &cache_dir,
+        download_url,
+        "application/json",
+        token_for(download_url),
+    )
+    .await
+    .map_err(|e| format!("Failed to download agent: {}", e))?
+    .body;
+
+    // Missing signatures and checksums are expected, so fetch errors just mean there is
+    // none. Copies from the cache may belong to an older version of the file.
+    let mut sidecars = Vec::new();
+    for extension in [
+        agent_trust::SIGNATURE_EXTENSION,
+        agent_trust::CHECKSUM_EXTENSION,
+    ] {
+        let url = format!("{}.{}", download_url, extension);
+        sidecars.push(
+            fetch_cached(&client, &cache_dir, &url, "text/plain", token_for(&url))
+                .await
+                .ok()
+                .filter(|fetched| !fetched.stale)
+                .map(|fetched| fetched.body),
+        );
+    }
+
+    let verification = agent_trust::verify(
+        json_text.as_bytes(),
+        sidecars[0].as_deref(),
+        sidecars[1].as_deref(),
+        &settings,
+        expected_sha256,
+    )?;
+    Ok(AgentImportPreview::new(
+        agent_trust::parse_agent_file(json_text.as_bytes())?,
+        verification,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn source(location: AgentSourceLocation) -> AgentSource {
+        AgentSource {
+            id: "team".to_string(),
+            name: "Team".to_string(),
+            location,
+            token: Some("secret".to_string()),
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_source_serialization() {
+        let json = r#"{"id": "team", "name": "Team", "type": "github", "repo": "acme/agents", "token": "secret"}"#;
+        let parsed: AgentSource = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            parsed,
+            source(AgentSourceLocation::Github {
+                repo: "acme/agents".to_string(),
+                path: String::new(),
+                branch: None,
+                api_url: None,
+            })
+        );
+
+        assert!(validate_sources(&[parsed.clone(), parsed]).is_err());
+        let bad_repo = source(AgentSourceLocation::Github {
+            repo: "acme".to_string(),
+            path: String::new(),
+            branch: None,
+            api_url: None,
+        });
+        assert!(validate_sources(&[bad_repo]).is_err());
+    }
+
+    #[test]
+    fn test_token_only_sent_to_source_hosts() {
+        let github = source(AgentSourceLocation::Github {
+            repo: "acme/agents".to_string(),
+            path: String::new(),
+            branch: None,
+            api_url: None,
+        });
+        assert_eq!(
+            github.token_for("https://raw.githubusercontent.com/acme/agents/main/a.json"),
+            Some("secret")
+        );
+        assert_eq!(github.token_for("https://cdn.example.com/a.json"), None);
+
+        let http = source(AgentSourceLocation::Http {
+            url: "https://agents.example.com/index.json".to_string(),
+        });
+        assert_eq!(
+            http.token_for("https://agents.example.com/agents/a.json"),
+            Some("secret")
+        );
+        assert_eq!(http.token_for("https://cdn.example.com/a.json"), None);
+        assert_eq!(http.token_for("http://agents.example.com/a.json"), None);
+    }
+
+    #[test]
+    fn test_parse_index_resolves_relative_urls() {
+        let source = source(AgentSourceLocation::Http {
+            url: "https://agents.example.com/index.json".to_string(),
+        });
+        let body = r#"{"agents": [
+            {"name": "reviewer.opcode.json", "url": "agents/reviewer.opcode.json", "size": 42},
+            {"name": "fixer.opcode.json", "download_url": "https://cdn.example.com/fixer.json"}
+        ]}"#;
+
+        let agents = parse_index(&source, "https://agents.example.com/index.json", body).unwrap();
+        assert_eq!(
+            agents[0].download_url,
+            "https://agents.example.com/agents/reviewer.opcode.json"
+        );
+        assert_eq!(agents[0].size, 42);
+        assert_eq!(agents[1].download_url, "https://cdn.example.com/fixer.json");
+        assert_eq!(agents[1].source_id, "team");
+
+        // A bare array works too
+        let agents = parse_index(
+            &source,
+            "https://agents.example.com/index.json",
+            r#"[{"name": "a", "url": "a.json"}]"#,
+        )
+        .unwrap();
+        assert_eq!(agents.len(), 1);
+    }
+
+    #[test]
+    fn test_list_local() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("b.opcode.json"), "{}").unwrap();
+        std::fs::write(dir.path().join("a.opcode.json"), "{}").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+        let path = dir.path().to_string_lossy().to_string();
+
+        let agents = list_local(
+            &source(AgentSourceLocation::Local { path: path.clone() }),
+            &path,
+        )
+        .unwrap();
+        let names: Vec<_> = agents.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, ["a.opcode.json", "b.opcode.json"]);
+        assert_eq!(agents[0].size, 2);
+    }
+}
@@ -0,0 +1,414 @@
+#![allow(dead_code)]
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ed25519_dalek::{Signature, VerifyingKey};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sha2::{Digest, Sha256};
+use tauri::State;
+
+use crate::commands::agent_package::AgentPackage;
+use crate::commands::agents::AgentDb;
+
+const TRUST_SETTINGS_KEY: &str = "agent_trust_settings";
+
+/// Extension of the detached signature next to an agent file (base64 ed25519 signature)
+pub const SIGNATURE_EXTENSION: &str = "sig";
+/// Extension of the checksum next to an agent file (hex SHA-256, as written by `sha256sum`)
+pub const CHECKSUM_EXTENSION: &str = "sha256";
+
+/// A publisher whose signed agents are trusted
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TrustedPublisher {
+    pub name: String,
+    /// Base64-encoded ed25519 public key
+    pub public_key: String,
+}
+
+impl TrustedPublisher {
+    fn verifying_key(&self) -> Result<VerifyingKey, String> {
+        let bytes = BASE64
+            .decode(self.public_key.trim())
+            .map_err(|e| format!("Invalid public key for '{}': {}", self.name, e))?;
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| format!("Public key for '{}' must be 32 bytes", self.name))?;
+        VerifyingKey::from_bytes(&bytes)
+            .map_err(|e| format!("Invalid public key for '{}': {}", self.name, e))
+    }
+}
+
+/// An agent file trusted by its SHA-256, whoever serves it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PinnedChecksum {
+    pub name: String,
+    /// Hex SHA-256 of the agent file
+    pub sha256: String,
+}
+
+/// Trust store and policy for imported agent files
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct AgentTrustSettings {
+    /// Reject agent files that are neither signed by a trusted publisher nor pinned
+    #[serde(default)]
+    pub require_verification: bool,
+    #[serde(default)]
+    pub trusted_publishers: Vec<TrustedPublisher>,
+    #[serde(default)]
+    pub pinned_checksums: Vec<PinnedChecksum>,
+}
+
+impl AgentTrustSettings {
+    /// Load the trust settings from app_settings
+    pub fn load(conn: &Connection) -> Self {
+        conn.query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![TRUST_SETTINGS_KEY],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+    }
+}
+
+/// How an agent file was verified
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationStatus {
+    /// Signed by a trusted publisher
+    Signed,
+    /// Matches a pinned SHA-256 checksum
+    Checksum,
+    Unverified,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AgentVerification {
+    pub status: VerificationStatus,
+    /// Trusted publisher that signed the file
+    pub publisher: Option<String>,
+    /// SHA-256 of the file, which the import can be pinned to
+    pub sha256: String,
+}
+
+/// Verify an agent file against its detached signature and checksum
+///
+/// A signature or checksum that doesn't match is always an error. A checksum
+/// served next to the file only guards against corruption: whoever serves the
+/// file serves its checksum too. Only a trusted signature, or a checksum pinned
+/// in the trust store or passed as `expected_sha256`, verifies the file, and
+/// files that are not verified are rejected when the settings require it.
+pub fn verify(
+    content: &[u8],
+    signature: Option<&str>,
+    checksum: Option<&str>,
+    settings: &AgentTrustSettings,
+    expected_sha256: Option<&str>,
+) -> Result<AgentVerification, String> {
+    let sha256 = format!("{:x}", Sha256::digest(content));
+    let pinned = expected_sha256
+        .into_iter()
+        .chain(settings.pinned_checksums.iter().map(|p| p.sha256.as_str()))
+        .any(|pinned| pinned.trim().eq_ignore_ascii_case(&sha256));
+
+    if let Some(checksum) = checksum {
+        let expected = checksum
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        if expected != sha256 {
+            return Err(format!(
+                "Checksum mismatch: expected {}, got {}",
+                expected, sha256
+            ));
+        }
+    }
+
+    let publisher = match signature {
+        Some(signature) => {
+            let bytes = BASE64
+                .decode(signature.trim())
+                .map_err(|e| format!("Invalid signature encoding: {}", e))?;
+            let signature =
+                Signature::from_slice(&bytes).map_err(|e| format!("Invalid signature: {}", e))?;
+            let publisher = settings
+                .trusted_publishers
+                .iter()
+                .find(|p| {
+                    p.verifying_key()
+                        .map(|key| key.verify_strict(content, &signature).is_ok())
+                        .unwrap_or(false)
+                })
+                .ok_or("Agent signature does not match any trusted publisher")?;
+            Some(publisher.name.clone())
+        }
+        None => None,
+    };
+
+    let status = if publisher.is_some() {
+        VerificationStatus::Signed
+    } else if pinned {
+        VerificationStatus::Checksum
+    } else if settings.require_verification {
+        return Err(
+            "Agent file is neither signed by a trusted publisher nor pinned, and verification is required"
+                .to_string(),
+        );
+    } else {
+        VerificationStatus::Unverified
+    };
+
+    Ok(AgentVerification {
+        status,
+        publisher,
+        sha256,
+    })
+}
+
+/// Fail if the previewed file is not the one being imported
+pub fn check_expected_sha256(
+    verification: &AgentVerification,
+    expected: Option<&str>,
+) -> Result<(), String> {
+    match expected {
+        Some(expected) if !expected.eq_ignore_ascii_case(&verification.sha256) => {
+            Err("Agent file changed since it was previewed".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A shell command an imported agent would run
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PreviewCommand {
+    /// "hook" or "mcp_server"
+    pub kind: String,
+    /// Hook event and matcher, or MCP server name
+    pub context: String,
+    pub command: String,
+}
+
+/// What importing an agent package would install, for review before accepting
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentImportPreview {
+    pub package: AgentPackage,
+    pub verification: AgentVerification,
+    /// Commands run by the agent's hooks and bundled MCP servers
+    pub commands: Vec<PreviewCommand>,
+}
+
+impl AgentImportPreview {
+    pub fn new(package: AgentPackage, verification: AgentVerification) -> Self {
+        let mut commands = hook_commands(package.agent.hooks.as_deref());
+        for (name, config) in &package.mcp_servers {
+            if let Some(command) = config.get("command").and_then(|c| c.as_str()) {
+                let args = config
+                    .get("args")
+                    .and_then(|a| a.as_array())
+                    .map(|args| {
+                        args.iter()
+                            .filter_map(|a| a.as_str())
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    })
+                    .unwrap_or_default();
+                commands.push(PreviewCommand {
+                    kind: "mcp_server".to_string(),
+                    context: name.clone(),
+                    command: format!("{} {}", command, args).trim().to_string(),
+                });
+            }
+        }
+
+        Self {
+            package,
+            verification,
+            commands,
+        }
+    }
+}
+
+/// List the commands in a hooks configuration (`{"Event": [{"matcher", "hooks": [...]}]}`)
+pub fn hook_commands(hooks_json: Option<&str>) -> Vec<PreviewCommand> {
+    let hooks: JsonValue = match hooks_json.map(serde_json::from_str) {
+        Some(Ok(hooks)) => hooks,
+        _ => return Vec::new(),
+    };
+
+    let mut commands = Vec::new();
+    for (event, matchers) in hooks.as_object().into_iter().flatten() {
+        for matcher in matchers.as_array().into_iter().flatten() {
+            let context = match matcher.get("matcher").and_then(|m| m.as_str()) {
+                Some(m) if !m.is_empty() => format!("{} ({})", event, m),
+                _ => event.clone(),
+            };
+            for hook in matcher
+                .get("hooks")
+                .and_then(|h| h.as_array())
+                .into_iter()
+                .flatten()
+            {
+                if let Some(command) = hook.get("command").and_then(|c| c.as_str()) {
+                    commands.push(PreviewCommand {
+                        kind: "hook".to_string(),
+                        context: context.clone(),
+                        command: command.to_string(),
+                    });
+                }
+            }
+        }
+    }
+    commands
+}
+
+/// Parse agent file content, tolerating a BOM and surrounding whitespace
+pub fn parse_agent_file(content: &[u8]) -> Result<AgentPackage, String> {
+    let text = String::from_utf8_lossy(content);
+    AgentPackage::parse(text.trim_start_matches('\u{feff}').trim())
+}
+
+/// Verify and preview a local agent file, using the signature and checksum next to it
+pub fn preview_file(
+    file_path: &str,
+    settings: &AgentTrustSettings,
+    expected_sha256: Option<&str>,
+) -> Result<AgentImportPreview, String> {
+    let content = std::fs::read(file_path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let sidecar =
+        |extension: &str| std::fs::read_to_string(format!("{}.{}", file_path, extension)).ok();
+
+    let verification = verify(
+        &content,
+        sidecar(SIGNATURE_EXTENSION).as_deref(),
+        sidecar(CHECKSUM_EXTENSION).as_deref(),
+        settings,
+        expected_sha256,
+    )?;
+    Ok(AgentImportPreview::new(
+        parse_agent_file(&content)?,
+        verification,
+    ))
+}
+
+/// Get the agent trust store and verification policy
+#[tauri::command]
+pub async fn get_agent_trust_settings(
+    db: State<'_, AgentDb>,
+) -> Result<AgentTrustSettings, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(AgentTrustSettings::load(&conn))
+}
+
+/// Save the agent trust store and verification policy
+#[tauri::command]
+pub async fn save_agent_trust_settings(
+    db: State<'_, AgentDb>,
+    settings: AgentTrustSettings,
+) -> Result<(), String> {
+    for publisher in &settings.trusted_publishers {
+        publisher.verifying_key()?;
+    }
+    let json = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+        params![TRUST_SETTINGS_KEY, json],
+    )
+    .map_err(|e| format!("Failed to save agent trust settings: {}", e))?;
+
+    Ok(())
+}
+
+/// Verify and preview an agent file before importing it
+#[tauri::command]
+pub async fn preview_agent_import_from_file(
+    db: State<'_, AgentDb>,
+    file_path: String,
+) -> Result<AgentImportPreview, String> {
+    let settings = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        AgentTrustSettings::load(&conn)
+    };
+    preview_file(&file_path, &settings, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    const AGENT: &[u8] = br#"{"version": 2, "exported_at": "", "agent": {"name": "Reviewer", "icon": "bot", "system_prompt": "Review code", "model": "sonnet", "hooks": "{\"PreToolUse\": [{\"matcher\": \"Bash\", \"hooks\": [{\"type\": \"command\", \"command\": \"./check.sh\"}]}]}"}}"#;
+
+    fn publisher(key: &SigningKey) -> TrustedPublisher {
+        TrustedPublisher {
+            name: "Acme".to_string(),
+            public_key: BASE64.encode(key.verifying_key().to_bytes()),
+        }
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let signature = BASE64.encode(key.sign(AGENT).to_bytes());
+        let settings = AgentTrustSettings {
+            require_verification: true,
+            trusted_publishers: vec![publisher(&key)],
+            pinned_checksums: Vec::new(),
+        };
+
+        let verification = verify(AGENT, Some(&signature), None, &settings, None).unwrap();
+        assert_eq!(verification.status, VerificationStatus::Signed);
+        assert_eq!(verification.publisher.as_deref(), Some("Acme"));
+
+        // Tampered content or an unknown publisher is rejected
+        assert!(verify(b"{}", Some(&signature), None, &settings, None).is_err());
+        let other = AgentTrustSettings {
+            trusted_publishers: vec![publisher(&SigningKey::from_bytes(&[8; 32]))],
+            ..settings.clone()
+        };
+        assert!(verify(AGENT, Some(&signature), None, &other, None).is_err());
+    }
+
+    #[test]
+    fn test_verify_checksum_and_policy() {
+        let sha256 = format!("{:x}", Sha256::digest(AGENT));
+        let mut settings = AgentTrustSettings::default();
+
+        // A checksum served with the file proves nothing about its origin
+        let checksum = format!("{}  reviewer.opcode.json\n", sha256);
+        let verification = verify(AGENT, None, Some(&checksum), &settings, None).unwrap();
+        assert_eq!(verification.status, VerificationStatus::Unverified);
+        assert!(verify(AGENT, None, Some("deadbeef"), &settings, None).is_err());
+
+        settings.require_verification = true;
+        assert!(verify(AGENT, None, None, &settings, None).is_err());
+        assert!(verify(AGENT, None, Some(&checksum), &settings, None).is_err());
+
+        // Pinned checksums verify the file
+        let verification = verify(AGENT, None, None, &settings, Some(&sha256)).unwrap();
+        assert_eq!(verification.status, VerificationStatus::Checksum);
+        settings.pinned_checksums.push(PinnedChecksum {
+            name: "Reviewer".to_string(),
+            sha256: sha256.to_uppercase(),
+        });
+        let verification = verify(AGENT, None, None, &settings, None).unwrap();
+        assert_eq!(verification.status, VerificationStatus::Checksum);
+    }
+
+    #[test]
+    fn test_preview_lists_hook_commands() {
+        let verification = verify(AGENT, None, None, &AgentTrustSettings::default(), None).unwrap();
+        let preview = AgentImportPreview::new(parse_agent_file(AGENT).unwrap(), verification);
+        assert_eq!(
+            preview.commands,
+            [PreviewCommand {
+                kind: "hook".to_string(),
+                context: "PreToolUse (Bash)".to_string(),
+                command: "./check.sh".to_string(),
+            }]
+        );
+    }
+}
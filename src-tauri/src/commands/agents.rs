@@ -3,7 +3,6 @@ use anyhow::Result;
 use chrono;
 use dirs;
 use log::{debug, error, info, warn};
-use reqwest;
 use rusqlite::{params, Connection, Result as SqliteResult};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
@@ -15,6 +14,14 @@ use tauri::{AppHandle, Emitter, Manager, State};
 use tokio::io::{AsyncBufReadExt, BufReader as TokioBufReader};
 use tokio::process::Command;
 
+use crate::commands::agent_package::{
+    self, AgentPackage, ClaudeMdFragment, PackageConflict, PackagedAgent, PackagedSlashCommand,
+};
+use crate::commands::agent_registry;
+use crate::commands::agent_trust::{self, AgentTrustSettings};
+use crate::commands::task_template;
+use crate::stream_event::{parse_jsonl, RunResult, StreamLine};
+
 /// Finds the full path to the claude binary
 /// This is necessary because macOS apps have a limited PATH environment
 fn find_claude_binary(app_handle: &AppHandle) -> Result<String, String> {
@@ -34,10 +41,478 @@ pub struct Agent {
     pub enable_file_write: bool,
     pub enable_network: bool,
     pub hooks: Option<String>, // JSON string of hooks configuration
+    // Per-agent overrides of the global run limits (see AgentRunLimits)
+    pub first_output_timeout_secs: Option<i64>,
+    pub max_runtime_secs: Option<i64>,
+    pub idle_timeout_secs: Option<i64>,
+    // Default budget for runs of this agent (see AgentBudget)
+    pub max_cost_usd: Option<f64>,
+    pub max_tokens: Option<i64>,
+    pub parameters: Option<String>, // JSON array of task parameter definitions
+    pub task_template: Option<String>, // Task with `{{parameter}}` placeholders
+    // Where an agent imported from an agent source came from (see check_agent_updates)
+    pub source_id: Option<String>,
+    pub source_url: Option<String>,
+    pub source_sha: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
 
+/// Columns selected when loading an `Agent`, in the order expected by `agent_from_row`
+const AGENT_COLUMNS: &str = "id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at, first_output_timeout_secs, max_runtime_secs, idle_timeout_secs, max_cost_usd, max_tokens, parameters, task_template, source_id, source_url, source_sha";
+
+/// Map a row selected with `AGENT_COLUMNS` to an `Agent`
+fn agent_from_row(row: &rusqlite::Row) -> rusqlite::Result<Agent> {
+    Ok(Agent {
+        id: Some(row.get(0)?),
+        name: row.get(1)?,
+        icon: row.get(2)?,
+        system_prompt: row.get(3)?,
+        default_task: row.get(4)?,
+        model: row
+            .get::<_, String>(5)
+            .unwrap_or_else(|_| "sonnet".to_string()),
+        enable_file_read: row.get::<_, bool>(6).unwrap_or(true),
+        enable_file_write: row.get::<_, bool>(7).unwrap_or(true),
+        enable_network: row.get::<_, bool>(8).unwrap_or(false),
+        hooks: row.get(9)?,
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+        first_output_timeout_secs: row.get(12)?,
+        max_runtime_secs: row.get(13)?,
+        idle_timeout_secs: row.get(14)?,
+        max_cost_usd: row.get(15)?,
+        max_tokens: row.get(16)?,
+        parameters: row.get(17)?,
+        task_template: row.get(18)?,
+        source_id: row.get(19)?,
+        source_url: row.get(20)?,
+        source_sha: row.get(21)?,
+    })
+}
+
+/// The agent as it is stored in agent packages
+fn packaged_agent(agent: &Agent) -> PackagedAgent {
+    PackagedAgent {
+        name: agent.name.clone(),
+        icon: agent.icon.clone(),
+        system_prompt: agent.system_prompt.clone(),
+        default_task: agent.default_task.clone(),
+        model: agent.model.clone(),
+        enable_file_read: agent.enable_file_read,
+        enable_file_write: agent.enable_file_write,
+        enable_network: agent.enable_network,
+        hooks: agent.hooks.clone(),
+        first_output_timeout_secs: agent.first_output_timeout_secs,
+        max_runtime_secs: agent.max_runtime_secs,
+        idle_timeout_secs: agent.idle_timeout_secs,
+        max_cost_usd: agent.max_cost_usd,
+        max_tokens: agent.max_tokens,
+        parameters: agent.parameters.clone(),
+        task_template: agent.task_template.clone(),
+    }
+}
+
+/// A revision of an agent's behaviour: its prompt, model, hooks and permissions
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentVersion {
+    pub id: Option<i64>,
+    pub agent_id: i64,
+    pub version: i64,
+    pub system_prompt: String,
+    pub default_task: Option<String>,
+    pub model: String,
+    pub enable_file_read: bool,
+    pub enable_file_write: bool,
+    pub enable_network: bool,
+    pub hooks: Option<String>,
+    pub parameters: Option<String>,
+    pub task_template: Option<String>,
+    pub created_at: String,
+}
+
+/// A field that differs between two agent versions
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AgentVersionChange {
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+/// Columns selected when loading an `AgentVersion`, in the order expected by `agent_version_from_row`
+const AGENT_VERSION_COLUMNS: &str = "id, agent_id, version, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, parameters, task_template";
+
+/// Map a row selected with `AGENT_VERSION_COLUMNS` to an `AgentVersion`
+fn agent_version_from_row(row: &rusqlite::Row) -> rusqlite::Result<AgentVersion> {
+    Ok(AgentVersion {
+        id: Some(row.get(0)?),
+        agent_id: row.get(1)?,
+        version: row.get(2)?,
+        system_prompt: row.get(3)?,
+        default_task: row.get(4)?,
+        model: row.get(5)?,
+        enable_file_read: row.get(6)?,
+        enable_file_write: row.get(7)?,
+        enable_network: row.get(8)?,
+        hooks: row.get(9)?,
+        created_at: row.get(10)?,
+        parameters: row.get(11)?,
+        task_template: row.get(12)?,
+    })
+}
+
+impl AgentVersion {
+    /// Versioned fields as (name, value) pairs, used for comparing versions
+    fn fields(&self) -> [(&'static str, Option<String>); 9] {
+        [
+            ("system_prompt", Some(self.system_prompt.clone())),
+            ("default_task", self.default_task.clone()),
+            ("model", Some(self.model.clone())),
+            ("enable_file_read", Some(self.enable_file_read.to_string())),
+            (
+                "enable_file_write",
+                Some(self.enable_file_write.to_string()),
+            ),
+            ("enable_network", Some(self.enable_network.to_string())),
+            ("hooks", self.hooks.clone()),
+            ("parameters", self.parameters.clone()),
+            ("task_template", self.task_template.clone()),
+        ]
+    }
+
+    /// Fields that changed going from `previous` to this version
+    pub fn changes_from(&self, previous: &AgentVersion) -> Vec<AgentVersionChange> {
+        previous
+            .fields()
+            .into_iter()
+            .zip(self.fields())
+            .filter(|((_, old), (_, new))| old != new)
+            .map(|((field, old_value), (_, new_value))| AgentVersionChange {
+                field: field.to_string(),
+                old_value,
+                new_value,
+            })
+            .collect()
+    }
+
+    fn matches(&self, agent: &Agent) -> bool {
+        self.system_prompt == agent.system_prompt
+            && self.default_task == agent.default_task
+            && self.model == agent.model
+            && self.enable_file_read == agent.enable_file_read
+            && self.enable_file_write == agent.enable_file_write
+            && self.enable_network == agent.enable_network
+            && self.hooks == agent.hooks
+            && self.parameters == agent.parameters
+            && self.task_template == agent.task_template
+    }
+}
+
+/// Record the agent's current state as a new version if it differs from the latest one
+///
+/// Returns the version number describing the agent as it is now. Agents created
+/// before versioning existed get their first version the first time this runs.
+fn record_agent_version(conn: &Connection, agent: &Agent) -> Result<i64, String> {
+    let agent_id = agent.id.ok_or("Agent has no ID")?;
+
+    let latest = match conn.query_row(
+        &format!(
+            "SELECT {} FROM agent_versions WHERE agent_id = ?1 ORDER BY version DESC LIMIT 1",
+            AGENT_VERSION_COLUMNS
+        ),
+        params![agent_id],
+        agent_version_from_row,
+    ) {
+        Ok(version) => Some(version),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    if let Some(latest) = &latest {
+        if latest.matches(agent) {
+            return Ok(latest.version);
+        }
+    }
+
+    let version = latest.map(|v| v.version + 1).unwrap_or(1);
+    conn.execute(
+        "INSERT INTO agent_versions (agent_id, version, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, parameters, task_template) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            agent_id,
+            version,
+            agent.system_prompt,
+            agent.default_task,
+            agent.model,
+            agent.enable_file_read,
+            agent.enable_file_write,
+            agent.enable_network,
+            agent.hooks,
+            agent.parameters,
+            agent.task_template
+        ],
+    )
+    .map_err(|e| format!("Failed to record agent version: {}", e))?;
+
+    Ok(version)
+}
+
+/// Load a single version of an agent
+fn get_agent_version(
+    conn: &Connection,
+    agent_id: i64,
+    version: i64,
+) -> Result<AgentVersion, String> {
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM agent_versions WHERE agent_id = ?1 AND version = ?2",
+            AGENT_VERSION_COLUMNS
+        ),
+        params![agent_id, version],
+        agent_version_from_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => {
+            format!("Version {} of agent {} not found", version, agent_id)
+        }
+        e => e.to_string(),
+    })
+}
+
+/// Time limits enforced on agent runs, in seconds. A value of 0 disables the limit.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct AgentRunLimits {
+    /// Maximum time to wait for the first line of output
+    pub first_output_timeout_secs: u64,
+    /// Maximum wall-clock duration of a run
+    pub max_runtime_secs: u64,
+    /// Maximum time without any output once the run has started producing output
+    pub idle_timeout_secs: u64,
+}
+
+impl Default for AgentRunLimits {
+    fn default() -> Self {
+        Self {
+            first_output_timeout_secs: 30,
+            max_runtime_secs: 0,
+            idle_timeout_secs: 0,
+        }
+    }
+}
+
+impl AgentRunLimits {
+    const SETTINGS_KEYS: [&'static str; 3] = [
+        "agent_first_output_timeout_secs",
+        "agent_max_runtime_secs",
+        "agent_idle_timeout_secs",
+    ];
+
+    /// Load the global limits from app_settings, falling back to the defaults
+    pub fn load_global(conn: &Connection) -> Self {
+        let mut limits = Self::default();
+
+        for key in Self::SETTINGS_KEYS {
+            if let Ok(value) = conn.query_row(
+                "SELECT value FROM app_settings WHERE key = ?1",
+                params![key],
+                |row| row.get::<_, String>(0),
+            ) {
+                if let Ok(secs) = value.parse::<u64>() {
+                    match key {
+                        "agent_first_output_timeout_secs" => {
+                            limits.first_output_timeout_secs = secs
+                        }
+                        "agent_max_runtime_secs" => limits.max_runtime_secs = secs,
+                        "agent_idle_timeout_secs" => limits.idle_timeout_secs = secs,
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        limits
+    }
+
+    /// Apply the per-agent overrides on top of these limits
+    pub fn with_agent_overrides(mut self, agent: &Agent) -> Self {
+        if let Some(secs) = agent.first_output_timeout_secs {
+            self.first_output_timeout_secs = secs.max(0) as u64;
+        }
+        if let Some(secs) = agent.max_runtime_secs {
+            self.max_runtime_secs = secs.max(0) as u64;
+        }
+        if let Some(secs) = agent.idle_timeout_secs {
+            self.idle_timeout_secs = secs.max(0) as u64;
+        }
+        self
+    }
+
+    /// Check the limits against the elapsed run time and the time since the last
+    /// output, returning the reason if one of them has been exceeded
+    pub fn exceeded(
+        &self,
+        elapsed: std::time::Duration,
+        since_last_output: Option<std::time::Duration>,
+    ) -> Option<&'static str> {
+        let limit = |secs: u64| std::time::Duration::from_secs(secs);
+
+        match since_last_output {
+            None if self.first_output_timeout_secs > 0
+                && elapsed >= limit(self.first_output_timeout_secs) =>
+            {
+                return Some("first_output_timeout");
+            }
+            Some(idle) if self.idle_timeout_secs > 0 && idle >= limit(self.idle_timeout_secs) => {
+                return Some("idle_timeout");
+            }
+            _ => {}
+        }
+
+        if self.max_runtime_secs > 0 && elapsed >= limit(self.max_runtime_secs) {
+            return Some("max_runtime_exceeded");
+        }
+
+        None
+    }
+}
+
+/// Cost and token budget for an agent run. `None` leaves that dimension unlimited.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+pub struct AgentBudget {
+    pub max_cost_usd: Option<f64>,
+    pub max_tokens: Option<i64>,
+}
+
+impl AgentBudget {
+    /// Fractions of the budget at which a warning is emitted
+    const WARNING_THRESHOLDS: [f64; 3] = [0.5, 0.75, 0.9];
+
+    /// Resolve the budget of a run: run-level values take precedence over the agent's.
+    /// Non-positive values disable that dimension.
+    pub fn resolve(agent: &Agent, max_cost_usd: Option<f64>, max_tokens: Option<i64>) -> Self {
+        Self {
+            max_cost_usd: max_cost_usd.or(agent.max_cost_usd).filter(|c| *c > 0.0),
+            max_tokens: max_tokens.or(agent.max_tokens).filter(|t| *t > 0),
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_cost_usd.is_none() && self.max_tokens.is_none()
+    }
+
+    /// Fraction of the budget used, taking whichever limit is closest to being hit
+    fn used_fraction(&self, total_tokens: i64, cost_usd: f64) -> f64 {
+        let tokens = self
+            .max_tokens
+            .map(|max| total_tokens as f64 / max as f64)
+            .unwrap_or(0.0);
+        let cost = self.max_cost_usd.map(|max| cost_usd / max).unwrap_or(0.0);
+        tokens.max(cost)
+    }
+}
+
+/// Live budget usage of a running agent, sent with budget events
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AgentBudgetUsage {
+    pub total_tokens: i64,
+    pub cost_usd: f64,
+    pub max_tokens: Option<i64>,
+    pub max_cost_usd: Option<f64>,
+    pub fraction_used: f64,
+}
+
+/// Result of recording a stream-json message against a budget
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BudgetCheck {
+    /// A warning threshold (fraction of the budget) was crossed
+    Warning(f64),
+    /// The budget is used up
+    Exceeded,
+}
+
+/// Tracks token usage and estimated cost of a run from its stream-json output
+///
+/// Mirrors `AgentRunMetrics::from_jsonl` (input + output tokens) but works line
+/// by line. Assistant messages are split into one line per content block that
+/// all carry the same `usage`, so usage is counted once per message id. The
+/// final `result` message reports the authoritative total cost.
+#[derive(Debug)]
+pub struct BudgetTracker {
+    budget: AgentBudget,
+    total_tokens: i64,
+    cost_usd: f64,
+    seen_message_ids: std::collections::HashSet<String>,
+    warnings_emitted: usize,
+    exceeded: bool,
+}
+
+impl BudgetTracker {
+    pub fn new(budget: AgentBudget) -> Self {
+        Self {
+            budget,
+            total_tokens: 0,
+            cost_usd: 0.0,
+            seen_message_ids: std::collections::HashSet::new(),
+            warnings_emitted: 0,
+            exceeded: false,
+        }
+    }
+
+    /// Record a stream-json message, returning a check when a threshold is crossed
+    pub fn record(&mut self, message: &StreamLine) -> Option<BudgetCheck> {
+        if let Some(result) = message.result() {
+            if let Some(total_cost) = result.cost_usd {
+                self.cost_usd = self.cost_usd.max(total_cost);
+            }
+        } else {
+            let (message_id, model, usage) = message.message_usage()?;
+
+            if let Some(id) = message_id {
+                if !self.seen_message_ids.insert(id.to_string()) {
+                    return None;
+                }
+            }
+
+            self.total_tokens += usage.input_output() as i64;
+            self.cost_usd +=
+                crate::commands::usage::estimate_usage_cost(model.unwrap_or(""), usage);
+        }
+
+        self.check()
+    }
+
+    fn check(&mut self) -> Option<BudgetCheck> {
+        if self.exceeded || self.budget.is_unlimited() {
+            return None;
+        }
+
+        let fraction = self.budget.used_fraction(self.total_tokens, self.cost_usd);
+        if fraction >= 1.0 {
+            self.exceeded = true;
+            return Some(BudgetCheck::Exceeded);
+        }
+
+        let mut crossed = None;
+        while let Some(threshold) = AgentBudget::WARNING_THRESHOLDS
+            .get(self.warnings_emitted)
+            .filter(|threshold| fraction >= **threshold)
+        {
+            crossed = Some(BudgetCheck::Warning(*threshold));
+            self.warnings_emitted += 1;
+        }
+        crossed
+    }
+
+    pub fn usage(&self) -> AgentBudgetUsage {
+        AgentBudgetUsage {
+            total_tokens: self.total_tokens,
+            cost_usd: self.cost_usd,
+            max_tokens: self.budget.max_tokens,
+            max_cost_usd: self.budget.max_cost_usd,
+            fraction_used: self.budget.used_fraction(self.total_tokens, self.cost_usd),
+        }
+    }
+}
+
 /// Represents an agent execution run
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AgentRun {
@@ -49,11 +524,158 @@ pub struct AgentRun {
     pub model: String,
     pub project_path: String,
     pub session_id: String, // UUID session ID from Claude Code
-    pub status: String,     // 'pending', 'running', 'completed', 'failed', 'cancelled'
+    pub status: String, // 'pending', 'running', 'completed', 'failed', 'cancelled', 'interrupted', 'budget_exceeded'
     pub pid: Option<u32>,
     pub process_started_at: Option<String>,
     pub created_at: String,
     pub completed_at: Option<String>,
+    pub exit_code: Option<i32>,
+    pub error_subtype: Option<String>, // e.g. 'error_max_turns', 'authentication_failed'
+    pub stderr_tail: Option<String>,   // Last lines written to stderr by the process
+    pub result_text: Option<String>,   // Final `result` text from the stream-json output
+    pub max_cost_usd: Option<f64>,     // Budget the run was started with
+    pub max_tokens: Option<i64>,
+    pub worktree_path: Option<String>, // Set for runs isolated in a git worktree
+    pub worktree_branch: Option<String>,
+    pub base_commit: Option<String>,
+    pub agent_version: Option<i64>, // Version of the agent the run was started with
+    pub parameters: Option<String>, // JSON object of the values the task was rendered with
+    pub parent_run_id: Option<i64>, // Run this one continues, for follow-ups
+    #[serde(skip)]
+    pub hooks_settings_created: bool, // Whether opcode wrote .claude/settings.json for the agent's hooks
+}
+
+impl AgentRun {
+    /// Directory the run executes in: its worktree when isolated, otherwise the project
+    pub fn working_dir(&self) -> &str {
+        self.worktree_path.as_deref().unwrap_or(&self.project_path)
+    }
+
+    /// The worktree the run was isolated in, if any
+    pub fn worktree(&self) -> Option<crate::commands::worktree::AgentWorktree> {
+        Some(crate::commands::worktree::AgentWorktree {
+            path: self.worktree_path.clone()?,
+            branch: self.worktree_branch.clone()?,
+            base_commit: self.base_commit.clone()?,
+        })
+    }
+}
+
+/// Columns selected when loading an `AgentRun`, in the order expected by `agent_run_from_row`
+pub(crate) const AGENT_RUN_COLUMNS: &str = "id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at, exit_code, error_subtype, stderr_tail, result_text, max_cost_usd, max_tokens, worktree_path, worktree_branch, base_commit, agent_version, parameters, parent_run_id, hooks_settings_created";
+
+/// Number of stderr lines kept for the `stderr_tail` column
+const STDERR_TAIL_LINES: usize = 50;
+
+/// Map a row selected with `AGENT_RUN_COLUMNS` to an `AgentRun`
+pub(crate) fn agent_run_from_row(row: &rusqlite::Row) -> rusqlite::Result<AgentRun> {
+    Ok(AgentRun {
+        id: Some(row.get(0)?),
+        agent_id: row.get(1)?,
+        agent_name: row.get(2)?,
+        agent_icon: row.get(3)?,
+        task: row.get(4)?,
+        model: row.get(5)?,
+        project_path: row.get(6)?,
+        session_id: row.get(7)?,
+        status: row
+            .get::<_, String>(8)
+            .unwrap_or_else(|_| "pending".to_string()),
+        pid: row
+            .get::<_, Option<i64>>(9)
+            .ok()
+            .flatten()
+            .map(|p| p as u32),
+        process_started_at: row.get(10)?,
+        created_at: row.get(11)?,
+        completed_at: row.get(12)?,
+        exit_code: row.get(13)?,
+        error_subtype: row.get(14)?,
+        stderr_tail: row.get(15)?,
+        result_text: row.get(16)?,
+        max_cost_usd: row.get(17)?,
+        max_tokens: row.get(18)?,
+        worktree_path: row.get(19)?,
+        worktree_branch: row.get(20)?,
+        base_commit: row.get(21)?,
+        agent_version: row.get(22)?,
+        parameters: row.get(23)?,
+        parent_run_id: row.get(24)?,
+        hooks_settings_created: row.get(25)?,
+    })
+}
+
+/// Final outcome of an agent run, derived from the process exit code and the
+/// stream-json `result` message
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentRunOutcome {
+    pub status: String, // 'completed', 'failed', 'budget_exceeded' or, for adopted runs, 'interrupted'
+    pub error_subtype: Option<String>,
+    pub result_text: Option<String>,
+}
+
+impl AgentRunOutcome {
+    /// Determine the outcome of a finished run
+    ///
+    /// The `result` message is authoritative when present: Claude Code reports
+    /// errors such as `error_max_turns` through its `subtype`, and flags other
+    /// failures (e.g. an invalid API key) with `is_error` while keeping the
+    /// `success` subtype. Without a result message we fall back to the exit code.
+    pub fn determine(exit_code: Option<i32>, result_message: Option<&RunResult>) -> Self {
+        let result_text = result_message.and_then(|r| r.result.clone());
+
+        if let Some(result) = result_message {
+            if result.subtype.starts_with("error") {
+                return Self::failed(result.subtype.clone(), result_text);
+            }
+            if result.is_error {
+                let text = result_text.as_deref().unwrap_or("").to_lowercase();
+                let error_subtype = if text.contains("api key")
+                    || text.contains("/login")
+                    || text.contains("authentication")
+                    || text.contains("oauth")
+                {
+                    "authentication_failed"
+                } else {
+                    "error_during_execution"
+                };
+                return Self::failed(error_subtype.to_string(), result_text);
+            }
+        }
+
+        match exit_code {
+            Some(0) => Self {
+                status: "completed".to_string(),
+                error_subtype: None,
+                result_text,
+            },
+            Some(_) => Self::failed("nonzero_exit".to_string(), result_text),
+            None => Self::failed("terminated_by_signal".to_string(), result_text),
+        }
+    }
+
+    /// Outcome of a run stopped by opcode, e.g. for exceeding a time limit or
+    /// its budget; runs over budget get their own status
+    fn stopped(reason: String, result_text: Option<String>) -> Self {
+        let status = if reason == "budget_exceeded" {
+            "budget_exceeded"
+        } else {
+            "failed"
+        };
+        Self {
+            status: status.to_string(),
+            error_subtype: Some(reason),
+            result_text,
+        }
+    }
+
+    fn failed(error_subtype: String, result_text: Option<String>) -> Self {
+        Self {
+            status: "failed".to_string(),
+            error_subtype: Some(error_subtype),
+            result_text,
+        }
+    }
 }
 
 /// Represents runtime metrics calculated from JSONL
@@ -74,28 +696,217 @@ pub struct AgentRunWithMetrics {
     pub output: Option<String>, // Real-time JSONL content
 }
 
-/// Agent export format
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AgentExport {
-    pub version: u32,
-    pub exported_at: String,
-    pub agent: AgentData,
+/// Result of importing an agent package
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentImportResult {
+    #[serde(flatten)]
+    pub agent: Agent,
+    /// Bundled slash commands and MCP servers that were installed
+    pub installed: Vec<String>,
+    /// Bundled resources that were skipped because they clash with existing ones
+    pub conflicts: Vec<PackageConflict>,
 }
 
-/// Agent data within export
-#[derive(Debug, Serialize, Deserialize)]
-pub struct AgentData {
-    pub name: String,
-    pub icon: String,
-    pub system_prompt: String,
-    pub default_task: Option<String>,
-    pub model: String,
-    pub hooks: Option<String>,
+/// Resources bundled with an agent through its package
+#[derive(Debug, Default)]
+struct AgentResources {
+    slash_commands: Vec<PackagedSlashCommand>,
+    mcp_servers: std::collections::BTreeMap<String, JsonValue>,
+    claude_md: Vec<ClaudeMdFragment>,
+}
+
+/// Load the resources stored with an agent
+fn load_agent_resources(conn: &Connection, agent_id: i64) -> Result<AgentResources, String> {
+    let mut stmt = conn
+        .prepare("SELECT kind, name, content FROM agent_resources WHERE agent_id = ?1 ORDER BY kind, position")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![agent_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut resources = AgentResources::default();
+    for (kind, name, content) in rows {
+        let invalid = |e: serde_json::Error| format!("Invalid stored {} '{}': {}", kind, name, e);
+        match kind.as_str() {
+            "slash_command" => resources
+                .slash_commands
+                .push(serde_json::from_str(&content).map_err(invalid)?),
+            "mcp_server" => {
+                let config = serde_json::from_str(&content).map_err(invalid)?;
+                resources.mcp_servers.insert(name, config);
+            }
+            "claude_md" => resources
+                .claude_md
+                .push(serde_json::from_str(&content).map_err(invalid)?),
+            _ => warn!("Ignoring unknown agent resource kind: {}", kind),
+        }
+    }
+
+    Ok(resources)
+}
+
+impl AgentResources {
+    /// Fields of the resources that differ in `package`, as version changes
+    fn changes(&self, package: &AgentPackage) -> Vec<AgentVersionChange> {
+        fn change<T: Serialize + PartialEq>(
+            field: &str,
+            old: &T,
+            new: &T,
+        ) -> Option<AgentVersionChange> {
+            (old != new).then(|| AgentVersionChange {
+                field: field.to_string(),
+                old_value: serde_json::to_string(old).ok(),
+                new_value: serde_json::to_string(new).ok(),
+            })
+        }
+
+        [
+            change(
+                "slash_commands",
+                &self.slash_commands,
+                &package.slash_commands,
+            ),
+            change("mcp_servers", &self.mcp_servers, &package.mcp_servers),
+            change("claude_md", &self.claude_md, &package.claude_md),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    /// A package of `agent` bundling these resources
+    fn into_package(self, agent: PackagedAgent) -> AgentPackage {
+        AgentPackage {
+            version: agent_package::AGENT_PACKAGE_VERSION,
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            agent,
+            slash_commands: self.slash_commands,
+            mcp_servers: self.mcp_servers,
+            claude_md: self.claude_md,
+        }
+    }
+}
+
+/// Store the resources bundled in a package with an agent, replacing earlier ones
+fn save_agent_resources(
+    conn: &Connection,
+    agent_id: i64,
+    package: &AgentPackage,
+) -> Result<(), String> {
+    let mut entries = Vec::new();
+    for (position, command) in package.slash_commands.iter().enumerate() {
+        let name = match &command.namespace {
+            Some(ns) => format!("{}:{}", ns, command.name),
+            None => command.name.clone(),
+        };
+        entries.push((
+            "slash_command",
+            name,
+            position,
+            serde_json::to_string(command),
+        ));
+    }
+    for (position, (name, config)) in package.mcp_servers.iter().enumerate() {
+        entries.push((
+            "mcp_server",
+            name.clone(),
+            position,
+            serde_json::to_string(config),
+        ));
+    }
+    for (position, fragment) in package.claude_md.iter().enumerate() {
+        entries.push((
+            "claude_md",
+            position.to_string(),
+            position,
+            serde_json::to_string(fragment),
+        ));
+    }
+
+    conn.execute(
+        "DELETE FROM agent_resources WHERE agent_id = ?1",
+        params![agent_id],
+    )
+    .map_err(|e| format!("Failed to store agent resources: {}", e))?;
+    for (kind, name, position, content) in entries {
+        conn.execute(
+            "INSERT OR REPLACE INTO agent_resources (agent_id, kind, name, position, content) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![agent_id, kind, name, position as i64, content.map_err(|e| e.to_string())?],
+        )
+        .map_err(|e| format!("Failed to store agent resources: {}", e))?;
+    }
+
+    Ok(())
 }
 
 /// Database connection state
 pub struct AgentDb(pub Mutex<Connection>);
 
+/// Receives the events of agent runs, e.g. `agent-output:{run_id}`
+pub type AgentEventEmitter = std::sync::Arc<dyn Fn(&str, JsonValue) + Send + Sync>;
+
+/// What agent runs need from the program hosting them
+///
+/// The app forwards run events to the webview; the headless CLI prints them.
+#[derive(Clone)]
+pub struct AgentRunHost {
+    /// Directory containing agents.db and the worktrees of isolated runs
+    pub data_dir: std::path::PathBuf,
+    pub emitter: AgentEventEmitter,
+    /// Answers permission prompts; without it runs skip permission checks
+    pub permissions: Option<std::sync::Arc<crate::commands::permissions::PermissionBroker>>,
+}
+
+impl AgentRunHost {
+    /// Host for runs started from the app, emitting events to the webview
+    pub fn from_app(app: &AppHandle) -> Result<Self, String> {
+        let data_dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("Failed to get app data dir: {}", e))?;
+        let permissions = app
+            .try_state::<crate::commands::permissions::PermissionBrokerState>()
+            .map(|state| state.0.clone());
+        let app = app.clone();
+        Ok(Self {
+            data_dir,
+            permissions,
+            emitter: std::sync::Arc::new(move |event, payload| {
+                let _ = app.emit(event, payload);
+            }),
+        })
+    }
+
+    pub(crate) fn emit<T: Serialize>(&self, event: &str, payload: T) {
+        if let Ok(payload) = serde_json::to_value(payload) {
+            (self.emitter)(event, payload);
+        }
+    }
+}
+
+/// An agent run to start; see `execute_agent` for the meaning of the fields
+#[derive(Debug, Clone, Default)]
+pub struct AgentRunRequest {
+    pub agent_id: i64,
+    pub project_path: String,
+    pub task: String,
+    pub model: Option<String>,
+    pub max_cost_usd: Option<f64>,
+    pub max_tokens: Option<i64>,
+    pub isolate: bool,
+    pub parameters: Option<std::collections::HashMap<String, JsonValue>>,
+    /// Run whose session this run resumes with `task` as the follow-up prompt
+    pub parent_run_id: Option<i64>,
+}
+
 /// Real-time JSONL reading and processing functions
 impl AgentRunMetrics {
     /// Calculate metrics from JSONL content
@@ -106,42 +917,27 @@ impl AgentRunMetrics {
         let mut start_time: Option<chrono::DateTime<chrono::Utc>> = None;
         let mut end_time: Option<chrono::DateTime<chrono::Utc>> = None;
 
-        for line in jsonl_content.lines() {
-            if let Ok(json) = serde_json::from_str::<JsonValue>(line) {
-                message_count += 1;
+        for line in parse_jsonl(jsonl_content) {
+            message_count += 1;
 
-                // Track timestamps
-                if let Some(timestamp_str) = json.get("timestamp").and_then(|t| t.as_str()) {
-                    if let Ok(timestamp) = chrono::DateTime::parse_from_rfc3339(timestamp_str) {
-                        let utc_time = timestamp.with_timezone(&chrono::Utc);
-                        if start_time.is_none() || utc_time < start_time.unwrap() {
-                            start_time = Some(utc_time);
-                        }
-                        if end_time.is_none() || utc_time > end_time.unwrap() {
-                            end_time = Some(utc_time);
-                        }
-                    }
+            // Track timestamps
+            if let Some(utc_time) = line.timestamp_utc() {
+                if start_time.is_none() || utc_time < start_time.unwrap() {
+                    start_time = Some(utc_time);
                 }
-
-                // Extract token usage - check both top-level and nested message.usage
-                let usage = json
-                    .get("usage")
-                    .or_else(|| json.get("message").and_then(|m| m.get("usage")));
-
-                if let Some(usage) = usage {
-                    if let Some(input_tokens) = usage.get("input_tokens").and_then(|t| t.as_i64()) {
-                        total_tokens += input_tokens;
-                    }
-                    if let Some(output_tokens) = usage.get("output_tokens").and_then(|t| t.as_i64())
-                    {
-                        total_tokens += output_tokens;
-                    }
+                if end_time.is_none() || utc_time > end_time.unwrap() {
+                    end_time = Some(utc_time);
                 }
+            }
 
-                // Extract cost information
-                if let Some(cost) = json.get("cost").and_then(|c| c.as_f64()) {
-                    cost_usd += cost;
-                }
+            // Token usage of assistant messages, or of the whole run in result messages
+            if let Some(usage) = line.usage() {
+                total_tokens += usage.input_output() as i64;
+            }
+
+            // Extract cost information
+            if let Some(cost) = line.cost_usd {
+                cost_usd += cost;
             }
         }
 
@@ -167,6 +963,15 @@ impl AgentRunMetrics {
     }
 }
 
+/// Encode a project path to match Claude Code's directory naming, which replaces every
+/// character other than ASCII letters and digits with `-`
+pub fn encode_project_path(project_path: &str) -> String {
+    project_path
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect()
+}
+
 /// Read JSONL content from a session file
 pub async fn read_session_jsonl(session_id: &str, project_path: &str) -> Result<String, String> {
     let claude_dir = dirs::home_dir()
@@ -174,9 +979,7 @@ pub async fn read_session_jsonl(session_id: &str, project_path: &str) -> Result<
         .join(".claude")
         .join("projects");
 
-    // Encode project path to match Claude Code's directory naming
-    let encoded_project = project_path.replace('/', "-");
-    let project_dir = claude_dir.join(&encoded_project);
+    let project_dir = claude_dir.join(encode_project_path(project_path));
     let session_file = project_dir.join(format!("{}.jsonl", session_id));
 
     if !session_file.exists() {
@@ -194,7 +997,7 @@ pub async fn read_session_jsonl(session_id: &str, project_path: &str) -> Result<
 
 /// Get agent run with real-time metrics
 pub async fn get_agent_run_with_metrics(run: AgentRun) -> AgentRunWithMetrics {
-    match read_session_jsonl(&run.session_id, &run.project_path).await {
+    match read_session_jsonl(&run.session_id, run.working_dir()).await {
         Ok(jsonl_content) => {
             let metrics = AgentRunMetrics::from_jsonl(&jsonl_content);
             AgentRunWithMetrics {
@@ -220,7 +1023,12 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         .path()
         .app_data_dir()
         .expect("Failed to get app data dir");
-    std::fs::create_dir_all(&app_dir).expect("Failed to create app data dir");
+    init_database_in(&app_dir)
+}
+
+/// Open agents.db in `app_dir`, creating and migrating its schema as needed
+pub fn init_database_in(app_dir: &std::path::Path) -> SqliteResult<Connection> {
+    std::fs::create_dir_all(app_dir).expect("Failed to create app data dir");
 
     let db_path = app_dir.join("agents.db");
     let conn = Connection::open(db_path)?;
@@ -238,6 +1046,13 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
             enable_file_write BOOLEAN NOT NULL DEFAULT 1,
             enable_network BOOLEAN NOT NULL DEFAULT 0,
             hooks TEXT,
+            first_output_timeout_secs INTEGER,
+            max_runtime_secs INTEGER,
+            idle_timeout_secs INTEGER,
+            max_cost_usd REAL,
+            max_tokens INTEGER,
+            parameters TEXT,
+            task_template TEXT,
             created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
             updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
         )",
@@ -263,6 +1078,23 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         "ALTER TABLE agents ADD COLUMN enable_network BOOLEAN DEFAULT 0",
         [],
     );
+    let _ = conn.execute(
+        "ALTER TABLE agents ADD COLUMN first_output_timeout_secs INTEGER",
+        [],
+    );
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN max_runtime_secs INTEGER", []);
+    let _ = conn.execute(
+        "ALTER TABLE agents ADD COLUMN idle_timeout_secs INTEGER",
+        [],
+    );
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN max_cost_usd REAL", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN max_tokens INTEGER", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN parameters TEXT", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN task_template TEXT", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN source_id TEXT", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN source_url TEXT", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN source_sha TEXT", []);
+    let _ = conn.execute("ALTER TABLE agents ADD COLUMN source_snapshot TEXT", []);
 
     // Create agent_runs table
     conn.execute(
@@ -280,6 +1112,17 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
             process_started_at TEXT,
             created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
             completed_at TEXT,
+            exit_code INTEGER,
+            error_subtype TEXT,
+            stderr_tail TEXT,
+            result_text TEXT,
+            max_cost_usd REAL,
+            max_tokens INTEGER,
+            worktree_path TEXT,
+            worktree_branch TEXT,
+            base_commit TEXT,
+            agent_version INTEGER,
+            parameters TEXT,
             FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE CASCADE
         )",
         [],
@@ -296,6 +1139,80 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         "ALTER TABLE agent_runs ADD COLUMN process_started_at TEXT",
         [],
     );
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN exit_code INTEGER", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN error_subtype TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN stderr_tail TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN result_text TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN max_cost_usd REAL", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN max_tokens INTEGER", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN worktree_path TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN worktree_branch TEXT", []);
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN base_commit TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE agent_runs ADD COLUMN agent_version INTEGER",
+        [],
+    );
+    let _ = conn.execute("ALTER TABLE agent_runs ADD COLUMN parameters TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE agent_runs ADD COLUMN parent_run_id INTEGER",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE agent_runs ADD COLUMN hooks_settings_created INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+
+    // Create agent_versions table
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS agent_versions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id INTEGER NOT NULL,
+            version INTEGER NOT NULL,
+            system_prompt TEXT NOT NULL,
+            default_task TEXT,
+            model TEXT NOT NULL,
+            enable_file_read BOOLEAN NOT NULL,
+            enable_file_write BOOLEAN NOT NULL,
+            enable_network BOOLEAN NOT NULL,
+            hooks TEXT,
+            parameters TEXT,
+            task_template TEXT,
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (agent_id, version),
+            FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    let _ = conn.execute("ALTER TABLE agent_versions ADD COLUMN parameters TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE agent_versions ADD COLUMN task_template TEXT",
+        [],
+    );
+
+    // Create agent_resources table for the slash commands, MCP servers and
+    // CLAUDE.md fragments bundled with imported agent packages
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS agent_resources (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            name TEXT NOT NULL,
+            position INTEGER NOT NULL DEFAULT 0,
+            content TEXT NOT NULL,
+            UNIQUE (agent_id, kind, name),
+            FOREIGN KEY (agent_id) REFERENCES agents(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Create agent_run_outputs table for the stdout of finished runs
+    crate::commands::agent_output::init_table(&conn)?;
+
+    // Create agent_batches and agent_batch_items tables for runs over many projects
+    crate::commands::agent_batch::init_tables(&conn)?;
+
+    // Create session_catalog table indexing the session files of all projects
+    crate::commands::session_catalog::init_table(&conn)?;
 
     // Drop old columns that are no longer needed (data is now read from JSONL files)
     // Note: SQLite doesn't support DROP COLUMN, so we'll ignore errors for existing columns
@@ -343,42 +1260,52 @@ pub fn init_database(app: &AppHandle) -> SqliteResult<Connection> {
         [],
     )?;
 
+    // Apply the retention policy to the stored output of agent runs
+    let retention = crate::commands::agent_output::AgentOutputRetention::load(&conn);
+    if let Err(e) = crate::commands::agent_output::prune(&conn, &retention) {
+        warn!("Failed to prune stored agent output: {}", e);
+    }
+
     Ok(conn)
 }
 
 /// List all agents
 #[tauri::command]
 pub async fn list_agents(db: State<'_, AgentDb>) -> Result<Vec<Agent>, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    db.agents()
+}
 
-    let mut stmt = conn
-        .prepare("SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at FROM agents ORDER BY created_at DESC")
-        .map_err(|e| e.to_string())?;
+impl AgentDb {
+    /// All agents, newest first
+    pub fn agents(&self) -> Result<Vec<Agent>, String> {
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
 
-    let agents = stmt
-        .query_map([], |row| {
-            Ok(Agent {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                icon: row.get(2)?,
-                system_prompt: row.get(3)?,
-                default_task: row.get(4)?,
-                model: row
-                    .get::<_, String>(5)
-                    .unwrap_or_else(|_| "sonnet".to_string()),
-                enable_file_read: row.get::<_, bool>(6).unwrap_or(true),
-                enable_file_write: row.get::<_, bool>(7).unwrap_or(true),
-                enable_network: row.get::<_, bool>(8).unwrap_or(false),
-                hooks: row.get(9)?,
-                created_at: row.get(10)?,
-                updated_at: row.get(11)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM agents ORDER BY created_at DESC",
+                AGENT_COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+
+        let agents = stmt
+            .query_map([], agent_from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
 
-    Ok(agents)
+        Ok(agents)
+    }
+
+    /// A single agent run by ID
+    pub fn agent_run(&self, id: i64) -> Result<AgentRun, String> {
+        let conn = self.0.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            &format!("SELECT {} FROM agent_runs WHERE id = ?1", AGENT_RUN_COLUMNS),
+            params![id],
+            agent_run_from_row,
+        )
+        .map_err(|e| e.to_string())
+    }
 }
 
 /// Create a new agent
@@ -395,7 +1322,17 @@ pub async fn create_agent(
     enable_file_write: Option<bool>,
     enable_network: Option<bool>,
     hooks: Option<String>,
+    first_output_timeout_secs: Option<i64>,
+    max_runtime_secs: Option<i64>,
+    idle_timeout_secs: Option<i64>,
+    max_cost_usd: Option<f64>,
+    max_tokens: Option<i64>,
+    parameters: Option<String>,
+    task_template: Option<String>,
 ) -> Result<Agent, String> {
+    let definitions = task_template::parse_parameters(parameters.as_deref())?;
+    task_template::validate_definitions(&definitions, task_template.as_deref())?;
+
     let conn = db.0.lock().map_err(|e| e.to_string())?;
     let model = model.unwrap_or_else(|| "sonnet".to_string());
     let enable_file_read = enable_file_read.unwrap_or(true);
@@ -403,8 +1340,8 @@ pub async fn create_agent(
     let enable_network = enable_network.unwrap_or(false);
 
     conn.execute(
-        "INSERT INTO agents (name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-        params![name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks],
+        "INSERT INTO agents (name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, first_output_timeout_secs, max_runtime_secs, idle_timeout_secs, max_cost_usd, max_tokens, parameters, task_template) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+        params![name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, first_output_timeout_secs, max_runtime_secs, idle_timeout_secs, max_cost_usd, max_tokens, parameters, task_template],
     )
     .map_err(|e| e.to_string())?;
 
@@ -413,31 +1350,32 @@ pub async fn create_agent(
     // Fetch the created agent
     let agent = conn
         .query_row(
-            "SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at FROM agents WHERE id = ?1",
+            &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
             params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    system_prompt: row.get(3)?,
-                    default_task: row.get(4)?,
-                    model: row.get(5)?,
-                    enable_file_read: row.get(6)?,
-                    enable_file_write: row.get(7)?,
-                    enable_network: row.get(8)?,
-                    hooks: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
+            agent_from_row,
         )
         .map_err(|e| e.to_string())?;
+    record_agent_version(&conn, &agent)?;
 
     Ok(agent)
 }
 
+/// Agent time limits and budgets `update_agent` can remove
+const CLEARABLE_OVERRIDES: [&str; 5] = [
+    "first_output_timeout_secs",
+    "max_runtime_secs",
+    "idle_timeout_secs",
+    "max_cost_usd",
+    "max_tokens",
+];
+
 /// Update an existing agent
+///
+/// Permission flags, time limits, budgets and task parameters are only changed
+/// when provided. Time limits and budgets named in `clear_overrides` (e.g.
+/// `max_runtime_secs` or `max_cost_usd`) are removed, so the global time limit
+/// applies or the budget is unlimited. Passing an empty string removes the
+/// parameters or task template.
 #[tauri::command]
 #[allow(clippy::too_many_arguments)]
 pub async fn update_agent(
@@ -452,10 +1390,61 @@ pub async fn update_agent(
     enable_file_write: Option<bool>,
     enable_network: Option<bool>,
     hooks: Option<String>,
+    first_output_timeout_secs: Option<i64>,
+    max_runtime_secs: Option<i64>,
+    idle_timeout_secs: Option<i64>,
+    max_cost_usd: Option<f64>,
+    max_tokens: Option<i64>,
+    parameters: Option<String>,
+    task_template: Option<String>,
+    clear_overrides: Option<Vec<String>>,
 ) -> Result<Agent, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
     let model = model.unwrap_or_else(|| "sonnet".to_string());
 
+    let clear_overrides = clear_overrides.unwrap_or_default();
+    if let Some(field) = clear_overrides
+        .iter()
+        .find(|field| !CLEARABLE_OVERRIDES.contains(&field.as_str()))
+    {
+        return Err(format!("Unknown agent override: {}", field));
+    }
+    if [
+        first_output_timeout_secs,
+        max_runtime_secs,
+        idle_timeout_secs,
+    ]
+    .iter()
+    .flatten()
+    .any(|secs| *secs < 0)
+    {
+        return Err("Time limits can't be negative".to_string());
+    }
+    if max_cost_usd.is_some_and(|cost| cost <= 0.0) || max_tokens.is_some_and(|t| t <= 0) {
+        return Err("Budgets must be positive".to_string());
+    }
+
+    // Validate the task parameters against what they will be combined with
+    if parameters.is_some() || task_template.is_some() {
+        let (current_parameters, current_template) = conn
+            .query_row(
+                "SELECT parameters, task_template FROM agents WHERE id = ?1",
+                params![id],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<String>>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                    ))
+                },
+            )
+            .map_err(|e| e.to_string())?;
+        let definitions = task_template::parse_parameters(
+            parameters.as_deref().or(current_parameters.as_deref()),
+        )?;
+        let template = task_template.as_deref().or(current_template.as_deref());
+        task_template::validate_definitions(&definitions, template.filter(|t| !t.is_empty()))?;
+    }
+
     // Build dynamic query based on provided parameters
     let mut query =
         "UPDATE agents SET name = ?1, icon = ?2, system_prompt = ?3, default_task = ?4, model = ?5, hooks = ?6"
@@ -485,6 +1474,33 @@ pub async fn update_agent(
         query.push_str(&format!(", enable_network = ?{}", param_count));
         params_vec.push(Box::new(en));
     }
+    for (column, limit) in [
+        ("first_output_timeout_secs", first_output_timeout_secs),
+        ("max_runtime_secs", max_runtime_secs),
+        ("idle_timeout_secs", idle_timeout_secs),
+        ("max_tokens", max_tokens),
+    ] {
+        if let Some(value) = limit {
+            param_count += 1;
+            query.push_str(&format!(", {} = ?{}", column, param_count));
+            params_vec.push(Box::new(value));
+        }
+    }
+    if let Some(cost) = max_cost_usd {
+        param_count += 1;
+        query.push_str(&format!(", max_cost_usd = ?{}", param_count));
+        params_vec.push(Box::new(cost));
+    }
+    for column in &clear_overrides {
+        query.push_str(&format!(", {} = NULL", column));
+    }
+    for (column, value) in [("parameters", parameters), ("task_template", task_template)] {
+        if let Some(value) = value {
+            param_count += 1;
+            query.push_str(&format!(", {} = ?{}", column, param_count));
+            params_vec.push(Box::new(Some(value).filter(|v| !v.trim().is_empty())));
+        }
+    }
 
     param_count += 1;
     query.push_str(&format!(" WHERE id = ?{}", param_count));
@@ -499,26 +1515,12 @@ pub async fn update_agent(
     // Fetch the updated agent
     let agent = conn
         .query_row(
-            "SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at FROM agents WHERE id = ?1",
+            &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
             params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    system_prompt: row.get(3)?,
-                    default_task: row.get(4)?,
-                    model: row.get(5)?,
-                    enable_file_read: row.get(6)?,
-                    enable_file_write: row.get(7)?,
-                    enable_network: row.get(8)?,
-                    hooks: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
+            agent_from_row,
         )
         .map_err(|e| e.to_string())?;
+    record_agent_version(&conn, &agent)?;
 
     Ok(agent)
 }
@@ -541,26 +1543,96 @@ pub async fn get_agent(db: State<'_, AgentDb>, id: i64) -> Result<Agent, String>
 
     let agent = conn
         .query_row(
-            "SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at FROM agents WHERE id = ?1",
+            &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
             params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    system_prompt: row.get(3)?,
-                    default_task: row.get(4)?,
-                    model: row.get::<_, String>(5).unwrap_or_else(|_| "sonnet".to_string()),
-                    enable_file_read: row.get::<_, bool>(6).unwrap_or(true),
-                    enable_file_write: row.get::<_, bool>(7).unwrap_or(true),
-                    enable_network: row.get::<_, bool>(8).unwrap_or(false),
-                    hooks: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
+            agent_from_row,
+        )
+        .map_err(|e| e.to_string())?;
+
+    Ok(agent)
+}
+
+/// List the versions of an agent, newest first
+#[tauri::command]
+pub async fn list_agent_versions(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+) -> Result<Vec<AgentVersion>, String> {
+    let agent = get_agent(db.clone(), agent_id).await?;
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+
+    // Make sure agents created before versioning have a version to show
+    record_agent_version(&conn, &agent)?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM agent_versions WHERE agent_id = ?1 ORDER BY version DESC",
+            AGENT_VERSION_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let versions = stmt
+        .query_map(params![agent_id], agent_version_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(versions)
+}
+
+/// List the fields that changed between two versions of an agent
+#[tauri::command]
+pub async fn diff_agent_versions(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+    from_version: i64,
+    to_version: i64,
+) -> Result<Vec<AgentVersionChange>, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+
+    let from = get_agent_version(&conn, agent_id, from_version)?;
+    let to = get_agent_version(&conn, agent_id, to_version)?;
+
+    Ok(to.changes_from(&from))
+}
+
+/// Restore an agent's prompt, model, hooks and permissions from an earlier version
+///
+/// The rollback itself is recorded as a new version, so history is never rewritten.
+#[tauri::command]
+pub async fn rollback_agent_version(
+    db: State<'_, AgentDb>,
+    agent_id: i64,
+    version: i64,
+) -> Result<Agent, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let target = get_agent_version(&conn, agent_id, version)?;
+
+    conn.execute(
+        "UPDATE agents SET system_prompt = ?1, default_task = ?2, model = ?3, enable_file_read = ?4, enable_file_write = ?5, enable_network = ?6, hooks = ?7, parameters = ?8, task_template = ?9, updated_at = CURRENT_TIMESTAMP WHERE id = ?10",
+        params![
+            target.system_prompt,
+            target.default_task,
+            target.model,
+            target.enable_file_read,
+            target.enable_file_write,
+            target.enable_network,
+            target.hooks,
+            target.parameters,
+            target.task_template,
+            agent_id
+        ],
+    )
+    .map_err(|e| format!("Failed to roll back agent: {}", e))?;
+
+    let agent = conn
+        .query_row(
+            &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
+            params![agent_id],
+            agent_from_row,
         )
         .map_err(|e| e.to_string())?;
+    record_agent_version(&conn, &agent)?;
 
     Ok(agent)
 }
@@ -574,43 +1646,23 @@ pub async fn list_agent_runs(
     let conn = db.0.lock().map_err(|e| e.to_string())?;
 
     let query = if agent_id.is_some() {
-        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-         FROM agent_runs WHERE agent_id = ?1 ORDER BY created_at DESC"
+        format!(
+            "SELECT {} FROM agent_runs WHERE agent_id = ?1 ORDER BY created_at DESC",
+            AGENT_RUN_COLUMNS
+        )
     } else {
-        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-         FROM agent_runs ORDER BY created_at DESC"
+        format!(
+            "SELECT {} FROM agent_runs ORDER BY created_at DESC",
+            AGENT_RUN_COLUMNS
+        )
     };
 
-    let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
-
-    let run_mapper = |row: &rusqlite::Row| -> rusqlite::Result<AgentRun> {
-        Ok(AgentRun {
-            id: Some(row.get(0)?),
-            agent_id: row.get(1)?,
-            agent_name: row.get(2)?,
-            agent_icon: row.get(3)?,
-            task: row.get(4)?,
-            model: row.get(5)?,
-            project_path: row.get(6)?,
-            session_id: row.get(7)?,
-            status: row
-                .get::<_, String>(8)
-                .unwrap_or_else(|_| "pending".to_string()),
-            pid: row
-                .get::<_, Option<i64>>(9)
-                .ok()
-                .flatten()
-                .map(|p| p as u32),
-            process_started_at: row.get(10)?,
-            created_at: row.get(11)?,
-            completed_at: row.get(12)?,
-        })
-    };
+    let mut stmt = conn.prepare(&query).map_err(|e| e.to_string())?;
 
     let runs = if let Some(aid) = agent_id {
-        stmt.query_map(params![aid], run_mapper)
+        stmt.query_map(params![aid], agent_run_from_row)
     } else {
-        stmt.query_map(params![], run_mapper)
+        stmt.query_map(params![], agent_run_from_row)
     }
     .map_err(|e| e.to_string())?
     .collect::<Result<Vec<_>, _>>()
@@ -622,34 +1674,7 @@ pub async fn list_agent_runs(
 /// Get a single agent run by ID
 #[tauri::command]
 pub async fn get_agent_run(db: State<'_, AgentDb>, id: i64) -> Result<AgentRun, String> {
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
-
-    let run = conn
-        .query_row(
-            "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-             FROM agent_runs WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(AgentRun {
-                    id: Some(row.get(0)?),
-                    agent_id: row.get(1)?,
-                    agent_name: row.get(2)?,
-                    agent_icon: row.get(3)?,
-                    task: row.get(4)?,
-                    model: row.get(5)?,
-                    project_path: row.get(6)?,
-                    session_id: row.get(7)?,
-                    status: row.get::<_, String>(8).unwrap_or_else(|_| "pending".to_string()),
-                    pid: row.get::<_, Option<i64>>(9).ok().flatten().map(|p| p as u32),
-                    process_started_at: row.get(10)?,
-                    created_at: row.get(11)?,
-                    completed_at: row.get(12)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
-
-    Ok(run)
+    db.agent_run(id)
 }
 
 /// Get agent run with real-time metrics from JSONL
@@ -679,112 +1704,361 @@ pub async fn list_agent_runs_with_metrics(
     Ok(runs_with_metrics)
 }
 
+/// Write the agent's hooks to `.claude/settings.json` in `dir` unless the file exists
+///
+/// Returns whether the file was created.
+fn write_agent_hooks_settings(dir: &std::path::Path, hooks_json: &str) -> Result<bool, String> {
+    let claude_dir = dir.join(".claude");
+    let settings_path = claude_dir.join("settings.json");
+
+    // Create .claude directory if it doesn't exist
+    if !claude_dir.exists() {
+        std::fs::create_dir_all(&claude_dir)
+            .map_err(|e| format!("Failed to create .claude directory: {}", e))?;
+        info!("Created .claude directory at: {:?}", claude_dir);
+    }
+
+    // Check if settings.json already exists
+    if settings_path.exists() {
+        info!("settings.json already exists at: {:?}", settings_path);
+        return Ok(false);
+    }
+
+    // Parse the hooks JSON
+    let hooks: serde_json::Value = serde_json::from_str(hooks_json)
+        .map_err(|e| format!("Failed to parse agent hooks: {}", e))?;
+
+    // Create a settings object with just the hooks
+    let settings = serde_json::json!({
+        "hooks": hooks
+    });
+
+    // Write the settings file
+    let settings_content = serde_json::to_string_pretty(&settings)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+
+    std::fs::write(&settings_path, settings_content)
+        .map_err(|e| format!("Failed to write settings.json: {}", e))?;
+
+    info!(
+        "Created settings.json with agent hooks at: {:?}",
+        settings_path
+    );
+    Ok(true)
+}
+
 /// Execute a CC agent with streaming output
+///
+/// `max_cost_usd` and `max_tokens` override the agent's budget for this run.
+/// When `parameters` are given, the task is rendered from the agent's task
+/// template instead of taken from `task`.
+/// With `isolate`, the run executes in a fresh git worktree on its own branch
+/// instead of in `project_path`; see `get_agent_run_diff` for its changes.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn execute_agent(
     app: AppHandle,
     agent_id: i64,
     project_path: String,
     task: String,
     model: Option<String>,
+    max_cost_usd: Option<f64>,
+    max_tokens: Option<i64>,
+    isolate: Option<bool>,
+    parameters: Option<std::collections::HashMap<String, JsonValue>>,
     db: State<'_, AgentDb>,
     registry: State<'_, crate::process::ProcessRegistryState>,
 ) -> Result<i64, String> {
-    info!("Executing agent {} with task: {}", agent_id, task);
-
-    // Get the agent from database
-    let agent = get_agent(db.clone(), agent_id).await?;
-    let execution_model = model.unwrap_or(agent.model.clone());
+    let request = AgentRunRequest {
+        agent_id,
+        project_path,
+        task,
+        model,
+        max_cost_usd,
+        max_tokens,
+        isolate: isolate.unwrap_or(false),
+        parameters,
+        parent_run_id: None,
+    };
+    start_agent_run(
+        &AgentRunHost::from_app(&app)?,
+        &db,
+        registry.0.clone(),
+        request,
+    )
+    .await
+}
 
-    // Create .claude/settings.json with agent hooks if it doesn't exist
-    if let Some(hooks_json) = &agent.hooks {
-        let claude_dir = std::path::Path::new(&project_path).join(".claude");
-        let settings_path = claude_dir.join("settings.json");
+/// Continue a finished agent run with a follow-up prompt
+///
+/// Resumes the run's session with the agent's current system prompt, model and
+/// permissions. The follow-up is recorded as a new run linked to `run_id` through
+/// `parent_run_id`, in the same project (and worktree, while it exists).
+#[tauri::command]
+pub async fn continue_agent_run(
+    app: AppHandle,
+    run_id: i64,
+    prompt: String,
+    model: Option<String>,
+    db: State<'_, AgentDb>,
+    registry: State<'_, crate::process::ProcessRegistryState>,
+) -> Result<i64, String> {
+    let parent = db.agent_run(run_id)?;
+    let request = AgentRunRequest {
+        agent_id: parent.agent_id,
+        project_path: parent.project_path,
+        task: prompt,
+        model,
+        parent_run_id: Some(run_id),
+        ..Default::default()
+    };
+    start_agent_run(
+        &AgentRunHost::from_app(&app)?,
+        &db,
+        registry.0.clone(),
+        request,
+    )
+    .await
+}
 
-        // Create .claude directory if it doesn't exist
-        if !claude_dir.exists() {
-            std::fs::create_dir_all(&claude_dir)
-                .map_err(|e| format!("Failed to create .claude directory: {}", e))?;
-            info!("Created .claude directory at: {:?}", claude_dir);
+/// Start an agent run and return its ID; the run continues in the background
+///
+/// Shared by the app and the headless CLI.
+pub async fn start_agent_run(
+    host: &AgentRunHost,
+    db: &AgentDb,
+    registry: std::sync::Arc<crate::process::ProcessRegistry>,
+    request: AgentRunRequest,
+) -> Result<i64, String> {
+    let AgentRunRequest {
+        agent_id,
+        project_path,
+        task,
+        model,
+        max_cost_usd,
+        max_tokens,
+        isolate,
+        parameters,
+        parent_run_id,
+    } = request;
+
+    // Follow-ups resume the session of the run they continue
+    let parent = match parent_run_id {
+        Some(id) => {
+            let parent = db.agent_run(id)?;
+            if parent.session_id.is_empty() {
+                return Err("This agent run has no session to continue".to_string());
+            }
+            if matches!(parent.status.as_str(), "pending" | "running") {
+                return Err("This agent run is still running".to_string());
+            }
+            Some(parent)
         }
+        None => None,
+    };
 
-        // Check if settings.json already exists
-        if !settings_path.exists() {
-            // Parse the hooks JSON
-            let hooks: serde_json::Value = serde_json::from_str(hooks_json)
-                .map_err(|e| format!("Failed to parse agent hooks: {}", e))?;
-
-            // Create a settings object with just the hooks
-            let settings = serde_json::json!({
-                "hooks": hooks
-            });
-
-            // Write the settings file
-            let settings_content = serde_json::to_string_pretty(&settings)
-                .map_err(|e| format!("Failed to serialize settings: {}", e))?;
-
-            std::fs::write(&settings_path, settings_content)
-                .map_err(|e| format!("Failed to write settings.json: {}", e))?;
+    // Get the agent from database
+    let agent = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
+            params![agent_id],
+            agent_from_row,
+        )
+        .map_err(|e| e.to_string())?
+    };
 
-            info!(
-                "Created settings.json with agent hooks at: {:?}",
-                settings_path
-            );
-        } else {
-            info!("settings.json already exists at: {:?}", settings_path);
+    // Render the task from the agent's template when parameters are given
+    let (task, run_parameters) = match parameters {
+        Some(values) => {
+            let template = agent
+                .task_template
+                .as_deref()
+                .ok_or("This agent has no task template")?;
+            let definitions = task_template::parse_parameters(agent.parameters.as_deref())?;
+            let resolved = task_template::resolve_values(&definitions, &values)?;
+            let rendered = task_template::render(template, &resolved)?;
+            let resolved_json = serde_json::to_string(&resolved).map_err(|e| e.to_string())?;
+            (rendered, Some(resolved_json))
         }
-    }
+        None => (task, None),
+    };
+    info!("Executing agent {} with task: {}", agent_id, task);
+
+    let execution_model = model.unwrap_or(agent.model.clone());
+    let budget = AgentBudget::resolve(&agent, max_cost_usd, max_tokens);
 
-    // Create a new run record
-    let run_id = {
+    // Create a new run record and resolve the time limits for this run
+    let claude_md: Vec<ClaudeMdFragment>;
+    let (run_id, limits) = {
         let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let limits = AgentRunLimits::load_global(&conn).with_agent_overrides(&agent);
+        let agent_version = record_agent_version(&conn, &agent)?;
+        claude_md = load_agent_resources(&conn, agent_id)?.claude_md;
         conn.execute(
-            "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            params![agent_id, agent.name, agent.icon, task, execution_model, project_path, ""],
+            "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id, max_cost_usd, max_tokens, agent_version, parameters, parent_run_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![agent_id, agent.name, agent.icon, task, execution_model, project_path, "", budget.max_cost_usd, budget.max_tokens, agent_version, run_parameters, parent_run_id],
         )
         .map_err(|e| e.to_string())?;
-        conn.last_insert_rowid()
+        (conn.last_insert_rowid(), limits)
     };
 
-    // Find Claude binary
-    info!("Running agent '{}'", agent.name);
-    let claude_path = match find_claude_binary(&app) {
-        Ok(path) => path,
-        Err(e) => {
-            error!("Failed to find claude binary: {}", e);
-            return Err(e);
+    // From here on a failure discards the run again, so it doesn't stay `pending`
+    let mut created_worktree = None;
+    let mut bridge_session = None;
+    let started = async {
+        // Set up the worktree for isolated runs; follow-ups continue in the worktree of
+        // the run they resume, which is checked out again if it was removed. Resuming
+        // in the project instead would write to the user's checkout, and Claude keeps
+        // the session under the worktree's path.
+        let parent_worktree = parent.as_ref().and_then(|parent| parent.worktree());
+        let worktree = if let Some(worktree) = parent_worktree {
+            if !std::path::Path::new(&worktree.path).exists() {
+                crate::commands::worktree::restore(&project_path, &worktree).map_err(|e| {
+                    format!("Failed to restore the worktree of the continued run: {}", e)
+                })?;
+            }
+            Some(worktree)
+        } else if isolate {
+            let worktrees_dir = host.data_dir.join("worktrees");
+            let worktree =
+                crate::commands::worktree::create(&project_path, &worktrees_dir, run_id)?;
+            created_worktree = Some(worktree.clone());
+            Some(worktree)
+        } else {
+            None
+        };
+        if let Some(worktree) = &worktree {
+            let conn = db.0.lock().map_err(|e| e.to_string())?;
+            conn.execute(
+                "UPDATE agent_runs SET worktree_path = ?1, worktree_branch = ?2, base_commit = ?3 WHERE id = ?4",
+                params![worktree.path, worktree.branch, worktree.base_commit, run_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        let working_dir = worktree
+            .as_ref()
+            .map(|w| w.path.clone())
+            .unwrap_or_else(|| project_path.clone());
+
+        // Create .claude/settings.json with agent hooks if it doesn't exist. Follow-ups
+        // find the file the run they continue left in its worktree, which is still
+        // opcode's and not part of the agent's changes.
+        let hooks_settings_written = match &agent.hooks {
+            Some(hooks_json) => {
+                write_agent_hooks_settings(std::path::Path::new(&working_dir), hooks_json)?
+            }
+            None => false,
+        };
+        let hooks_settings_inherited = parent
+            .as_ref()
+            .is_some_and(|parent| parent.worktree_path.is_some() && parent.hooks_settings_created);
+        let hooks_settings_created = hooks_settings_written || hooks_settings_inherited;
+        if hooks_settings_created {
+            let conn = db.0.lock().map_err(|e| e.to_string())?;
+            conn.execute(
+                "UPDATE agent_runs SET hooks_settings_created = 1 WHERE id = ?1",
+                params![run_id],
+            )
+            .map_err(|e| e.to_string())?;
         }
-    };
 
-    // Build arguments
-    let args = vec![
-        "-p".to_string(),
-        task.clone(),
-        "--system-prompt".to_string(),
-        agent.system_prompt.clone(),
-        "--model".to_string(),
-        execution_model.clone(),
-        "--output-format".to_string(),
-        "stream-json".to_string(),
-        "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
-    ];
+        // Find Claude binary
+        info!("Running agent '{}'", agent.name);
+        let claude_path = crate::claude_binary::find_claude_binary_in(Some(&host.data_dir))?;
 
-    // Always use system binary execution (sidecar removed)
-    spawn_agent_system(
-        app,
-        run_id,
-        agent_id,
-        agent.name.clone(),
-        claude_path,
-        args,
-        project_path,
-        task,
-        execution_model,
-        db,
-        registry,
-    )
-    .await
+        // CLAUDE.md fragments bundled with the agent extend its system prompt
+        let mut system_prompt = agent.system_prompt.clone();
+        for fragment in &claude_md {
+            system_prompt.push_str("\n\n");
+            if let Some(title) = &fragment.title {
+                system_prompt.push_str(&format!("# {}\n\n", title));
+            }
+            system_prompt.push_str(&fragment.content);
+        }
+
+        // Build arguments
+        let mut args = vec![
+            "-p".to_string(),
+            task.clone(),
+            "--system-prompt".to_string(),
+            system_prompt,
+            "--model".to_string(),
+            execution_model.clone(),
+            "--output-format".to_string(),
+            "stream-json".to_string(),
+            "--verbose".to_string(),
+        ];
+        args.extend(match &host.permissions {
+            Some(broker) => broker.cli_args(&project_path).await?,
+            None => crate::commands::permissions::skip_permissions_args(),
+        });
+        bridge_session = crate::commands::permissions::bridge_session(&args);
+        if let Some(parent) = &parent {
+            args.push("--resume".to_string());
+            args.push(parent.session_id.clone());
+        }
+
+        // Always use system binary execution (sidecar removed)
+        spawn_agent_system(
+            host.clone(),
+            run_id,
+            agent_id,
+            agent.name.clone(),
+            claude_path,
+            args,
+            working_dir,
+            task,
+            execution_model,
+            limits,
+            budget,
+            worktree.map(|worktree| (worktree, hooks_settings_created)),
+            db,
+            registry,
+        )
+        .await
+    }
+    .await;
+
+    if let Err(e) = &started {
+        error!("Failed to start agent run {}: {}", run_id, e);
+        discard_unstarted_run(db, &project_path, run_id, created_worktree.as_ref());
+        if let (Some(broker), Some(session)) = (&host.permissions, &bridge_session) {
+            broker.end_session(session);
+        }
+    }
+    started
+}
+
+/// Paths left out of a worktree run's commit: the hooks settings opcode wrote for it
+fn hooks_settings_exclude(hooks_settings_created: bool) -> &'static [&'static str] {
+    if hooks_settings_created {
+        &[".claude/settings.json"]
+    } else {
+        &[]
+    }
+}
+
+/// Remove the record of a run that failed to start, and the worktree created for it
+fn discard_unstarted_run(
+    db: &AgentDb,
+    project_path: &str,
+    run_id: i64,
+    worktree: Option<&crate::commands::worktree::AgentWorktree>,
+) {
+    if let Some(worktree) = worktree {
+        if let Err(e) = crate::commands::worktree::discard(project_path, worktree) {
+            warn!("Failed to discard worktree of agent run {}: {}", run_id, e);
+        }
+    }
+    match db.0.lock() {
+        Ok(conn) => {
+            if let Err(e) = conn.execute("DELETE FROM agent_runs WHERE id = ?1", params![run_id]) {
+                error!("Failed to delete agent run {}: {}", run_id, e);
+            }
+        }
+        Err(e) => error!("Failed to delete agent run {}: {}", run_id, e),
+    }
 }
 
 /// Creates a system binary command for agent execution
@@ -804,6 +2078,7 @@ fn create_agent_system_command(
         .stdin(Stdio::null())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
+    crate::process::isolate_process_group(&mut cmd);
 
     cmd
 }
@@ -811,7 +2086,7 @@ fn create_agent_system_command(
 /// Spawn agent using system binary command
 #[allow(clippy::too_many_arguments)]
 async fn spawn_agent_system(
-    app: AppHandle,
+    host: AgentRunHost,
     run_id: i64,
     agent_id: i64,
     agent_name: String,
@@ -820,9 +2095,15 @@ async fn spawn_agent_system(
     project_path: String,
     task: String,
     execution_model: String,
-    db: State<'_, AgentDb>,
-    registry: State<'_, crate::process::ProcessRegistryState>,
+    limits: AgentRunLimits,
+    budget: AgentBudget,
+    worktree: Option<(crate::commands::worktree::AgentWorktree, bool)>,
+    db: &AgentDb,
+    registry: std::sync::Arc<crate::process::ProcessRegistry>,
 ) -> Result<i64, String> {
+    let bridge_session = crate::commands::permissions::bridge_session(&args);
+    let permissions = host.permissions.clone();
+
     // Build the command
     let mut cmd = create_agent_system_command(&claude_path, args, &project_path);
 
@@ -840,15 +2121,20 @@ async fn spawn_agent_system(
     let now = chrono::Utc::now().to_rfc3339();
     info!("✅ Claude process spawned successfully with PID: {}", pid);
 
-    // Update the database with PID and status
-    {
-        let conn = db.0.lock().map_err(|e| e.to_string())?;
+    // Update the database with PID and status; the run is discarded if that fails,
+    // so don't leave its process behind
+    let marked_running = db.0.lock().map_err(|e| e.to_string()).and_then(|conn| {
         conn.execute(
             "UPDATE agent_runs SET status = 'running', pid = ?1, process_started_at = ?2 WHERE id = ?3",
             params![pid as i64, now, run_id],
-        ).map_err(|e| e.to_string())?;
-        info!("📝 Updated database with running status and PID");
+        )
+        .map_err(|e| e.to_string())
+    });
+    if let Err(e) = marked_running {
+        let _ = child.start_kill();
+        return Err(e);
     }
+    info!("📝 Updated database with running status and PID");
 
     // Get stdout and stderr
     let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
@@ -860,41 +2146,50 @@ async fn spawn_agent_system(
     let stderr_reader = TokioBufReader::new(stderr);
 
     // Create variables we need for the spawned tasks
-    let app_dir = app
-        .path()
-        .app_data_dir()
-        .expect("Failed to get app data dir");
-    let db_path = app_dir.join("agents.db");
+    let db_path = host.data_dir.join("agents.db");
 
     // Shared state for collecting session ID and live output
     let session_id = std::sync::Arc::new(Mutex::new(String::new()));
     let live_output = std::sync::Arc::new(Mutex::new(String::new()));
+    // Final stream-json `result` message and the tail of stderr, used to determine the outcome
+    let result_message: std::sync::Arc<Mutex<Option<RunResult>>> =
+        std::sync::Arc::new(Mutex::new(None));
+    let stderr_tail = std::sync::Arc::new(Mutex::new(std::collections::VecDeque::<String>::new()));
+    // Reason the run was stopped by opcode itself (e.g. a time limit), if any
+    let termination_reason: std::sync::Arc<Mutex<Option<String>>> =
+        std::sync::Arc::new(Mutex::new(None));
     let start_time = std::time::Instant::now();
 
     // Spawn tasks to read stdout and stderr
-    let app_handle = app.clone();
+    let host_stdout = host.clone();
     let session_id_clone = session_id.clone();
     let live_output_clone = live_output.clone();
-    let registry_clone = registry.0.clone();
-    let first_output = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
-    let first_output_clone = first_output.clone();
+    let registry_clone = registry.clone();
+    let last_output: std::sync::Arc<Mutex<Option<std::time::Instant>>> =
+        std::sync::Arc::new(Mutex::new(None));
+    let last_output_clone = last_output.clone();
     let db_path_for_stdout = db_path.clone(); // Clone the db_path for the stdout task
+    let result_message_clone = result_message.clone();
+    let termination_reason_clone = termination_reason.clone();
 
     let stdout_task = tokio::spawn(async move {
         info!("📖 Starting to read Claude stdout...");
         let mut lines = stdout_reader.lines();
         let mut line_count = 0;
+        let mut budget_tracker = BudgetTracker::new(budget);
 
         while let Ok(Some(line)) = lines.next_line().await {
             line_count += 1;
 
-            // Log first output
-            if !first_output_clone.load(std::sync::atomic::Ordering::Relaxed) {
-                info!(
-                    "🎉 First output received from Claude process! Line: {}",
-                    line
-                );
-                first_output_clone.store(true, std::sync::atomic::Ordering::Relaxed);
+            // Log first output and track activity for the idle limit
+            if let Ok(mut last) = last_output_clone.lock() {
+                if last.is_none() {
+                    info!(
+                        "🎉 First output received from Claude process! Line: {}",
+                        line
+                    );
+                }
+                *last = Some(std::time::Instant::now());
             }
 
             if line_count <= 5 {
@@ -913,46 +2208,80 @@ async fn spawn_agent_system(
             let _ = registry_clone.append_live_output(run_id, &line);
 
             // Extract session ID from JSONL output
-            if let Ok(json) = serde_json::from_str::<JsonValue>(&line) {
-                // Claude Code uses "session_id" (underscore), not "sessionId"
-                if json.get("type").and_then(|t| t.as_str()) == Some("system")
-                    && json.get("subtype").and_then(|s| s.as_str()) == Some("init")
-                {
-                    if let Some(sid) = json.get("session_id").and_then(|s| s.as_str()) {
-                        if let Ok(mut current_session_id) = session_id_clone.lock() {
-                            if current_session_id.is_empty() {
-                                *current_session_id = sid.to_string();
-                                info!("🔑 Extracted session ID: {}", sid);
-
-                                // Update database immediately with session ID
-                                if let Ok(conn) = Connection::open(&db_path_for_stdout) {
-                                    match conn.execute(
-                                        "UPDATE agent_runs SET session_id = ?1 WHERE id = ?2",
-                                        params![sid, run_id],
-                                    ) {
-                                        Ok(rows) => {
-                                            if rows > 0 {
-                                                info!("✅ Updated agent run {} with session ID immediately", run_id);
-                                            }
-                                        }
-                                        Err(e) => {
-                                            error!(
-                                                "❌ Failed to update session ID immediately: {}",
-                                                e
-                                            );
+            if let Some(message) = StreamLine::parse(&line) {
+                if let Some(sid) = message.init_session_id() {
+                    if let Ok(mut current_session_id) = session_id_clone.lock() {
+                        if current_session_id.is_empty() {
+                            *current_session_id = sid.to_string();
+                            info!("🔑 Extracted session ID: {}", sid);
+
+                            // Update database immediately with session ID
+                            if let Ok(conn) = Connection::open(&db_path_for_stdout) {
+                                match conn.execute(
+                                    "UPDATE agent_runs SET session_id = ?1 WHERE id = ?2",
+                                    params![sid, run_id],
+                                ) {
+                                    Ok(rows) => {
+                                        if rows > 0 {
+                                            info!("✅ Updated agent run {} with session ID immediately", run_id);
                                         }
                                     }
+                                    Err(e) => {
+                                        error!("❌ Failed to update session ID immediately: {}", e);
+                                    }
                                 }
                             }
                         }
                     }
                 }
+
+                // Keep the final result message to determine the run outcome
+                if let Some(run_result) = message.result() {
+                    if let Ok(mut result) = result_message_clone.lock() {
+                        *result = Some(run_result.clone());
+                    }
+                }
+
+                // Track usage against the run's budget
+                match budget_tracker.record(&message) {
+                    Some(BudgetCheck::Warning(threshold)) => {
+                        let usage = budget_tracker.usage();
+                        warn!(
+                            "💰 Agent run {} reached {:.0}% of its budget ({} tokens, ${:.4})",
+                            run_id,
+                            threshold * 100.0,
+                            usage.total_tokens,
+                            usage.cost_usd
+                        );
+                        host_stdout.emit(&format!("agent-budget-warning:{}", run_id), &usage);
+                    }
+                    Some(BudgetCheck::Exceeded) => {
+                        let usage = budget_tracker.usage();
+                        warn!(
+                            "💰 Agent run {} exceeded its budget ({} tokens, ${:.4}), stopping it",
+                            run_id, usage.total_tokens, usage.cost_usd
+                        );
+                        host_stdout.emit(&format!("agent-budget-exceeded:{}", run_id), &usage);
+
+                        if let Ok(mut termination) = termination_reason_clone.lock() {
+                            termination.get_or_insert_with(|| "budget_exceeded".to_string());
+                        }
+                        // Kill from a separate task so this one keeps draining stdout
+                        let registry = registry_clone.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = registry.kill_process(run_id).await {
+                                error!("❌ Failed to kill agent run {}: {}", run_id, e);
+                            }
+                        });
+                    }
+                    None => {}
+                }
             }
 
             // Emit the line to the frontend with run_id for isolation
-            let _ = app_handle.emit(&format!("agent-output:{}", run_id), &line);
+            host_stdout.emit(&format!("agent-output:{}", run_id), &line);
             // Also emit to the generic event for backward compatibility
-            let _ = app_handle.emit("agent-output", &line);
+            host_stdout.emit("agent-output", &line);
         }
 
         info!(
@@ -961,7 +2290,8 @@ async fn spawn_agent_system(
         );
     });
 
-    let app_handle_stderr = app.clone();
+    let host_stderr = host.clone();
+    let stderr_tail_clone = stderr_tail.clone();
     let first_error = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
     let first_error_clone = first_error.clone();
 
@@ -980,10 +2310,16 @@ async fn spawn_agent_system(
             }
 
             error!("stderr[{}]: {}", error_count, line);
+            if let Ok(mut tail) = stderr_tail_clone.lock() {
+                if tail.len() == STDERR_TAIL_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(line.clone());
+            }
             // Emit error lines to the frontend with run_id for isolation
-            let _ = app_handle_stderr.emit(&format!("agent-error:{}", run_id), &line);
+            host_stderr.emit(&format!("agent-error:{}", run_id), &line);
             // Also emit to the generic event for backward compatibility
-            let _ = app_handle_stderr.emit("agent-error", &line);
+            host_stderr.emit("agent-error", &line);
         }
 
         if error_count > 0 {
@@ -998,11 +2334,10 @@ async fn spawn_agent_system(
 
     // Register the process in the registry for live output tracking (after stdout/stderr setup)
     registry
-        .0
         .register_process(
             run_id,
             agent_id,
-            agent_name,
+            agent_name.clone(),
             pid,
             project_path.clone(),
             task.clone(),
@@ -1013,71 +2348,61 @@ async fn spawn_agent_system(
     info!("📋 Registered process in registry");
 
     let db_path_for_monitor = db_path.clone(); // Clone for the monitor task
+    let registry_for_monitor = registry.clone();
 
     // Monitor process status and wait for completion
     tokio::spawn(async move {
         info!("🕐 Starting process monitoring...");
 
-        // Wait for first output with timeout
-        for i in 0..300 {
-            // 30 seconds (300 * 100ms)
-            if first_output.load(std::sync::atomic::Ordering::Relaxed) {
-                info!(
-                    "✅ Output detected after {}ms, continuing normal execution",
-                    i * 100
-                );
-                break;
-            }
+        // Enforce the run's time limits until the process exits, which may be
+        // long after it closed its stdout
+        let exit_wait = registry_for_monitor.wait_for_exit(run_id);
+        tokio::pin!(exit_wait);
+        let mut enforce_limits = true;
+        let exit_status = loop {
+            tokio::select! {
+                status = &mut exit_wait => break status,
+                _ = tokio::time::sleep(tokio::time::Duration::from_millis(100)), if enforce_limits => {
+                    let since_last_output = last_output
+                        .lock()
+                        .ok()
+                        .and_then(|last| last.map(|instant| instant.elapsed()));
+
+                    if let Some(reason) = limits.exceeded(start_time.elapsed(), since_last_output) {
+                        warn!(
+                            "⏰ Agent run {} exceeded its time limit ({}), stopping PID {}",
+                            run_id, reason, pid
+                        );
+                        if reason == "first_output_timeout" {
+                            warn!("💡 No output usually means slow MCP servers, network or authentication issues");
+                        }
 
-            if i == 299 {
-                warn!("⏰ TIMEOUT: No output from Claude process after 30 seconds");
-                warn!("💡 This usually means:");
-                warn!("   1. Claude process is waiting for user input");
-                warn!("   3. Claude failed to initialize but didn't report an error");
-                warn!("   4. Network connectivity issues");
-                warn!("   5. Authentication issues (API key not found/invalid)");
-
-                // Process timed out - kill it via PID
-                warn!(
-                    "🔍 Process likely stuck waiting for input, attempting to kill PID: {}",
-                    pid
-                );
-                let kill_result = std::process::Command::new("kill")
-                    .arg("-TERM")
-                    .arg(pid.to_string())
-                    .output();
-
-                match kill_result {
-                    Ok(output) if output.status.success() => {
-                        warn!("🔍 Successfully sent TERM signal to process");
-                    }
-                    Ok(_) => {
-                        warn!("🔍 Failed to kill process with TERM, trying KILL");
-                        let _ = std::process::Command::new("kill")
-                            .arg("-KILL")
-                            .arg(pid.to_string())
-                            .output();
-                    }
-                    Err(e) => {
-                        warn!("🔍 Error killing process: {}", e);
+                        if let Ok(mut termination) = termination_reason.lock() {
+                            termination.get_or_insert_with(|| reason.to_string());
+                        }
+                        if let Err(e) = registry_for_monitor.kill_process(run_id).await {
+                            error!("❌ Failed to kill agent run {}: {}", run_id, e);
+                        }
+                        enforce_limits = false;
                     }
                 }
-
-                // Update database
-                if let Ok(conn) = Connection::open(&db_path_for_monitor) {
-                    let _ = conn.execute(
-                        "UPDATE agent_runs SET status = 'failed', completed_at = CURRENT_TIMESTAMP WHERE id = ?1",
-                        params![run_id],
-                    );
-                }
-
-                let _ = app.emit("agent-complete", false);
-                let _ = app.emit(&format!("agent-complete:{}", run_id), false);
-                return;
             }
-
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        };
+        if let (Some(broker), Some(session)) = (&permissions, &bridge_session) {
+            broker.end_session(session);
         }
+        let exit_status = match exit_status {
+            Ok(status) => status,
+            Err(e) => {
+                error!("❌ Failed to wait for agent run {}: {}", run_id, e);
+                None
+            }
+        };
+        let exit_code = exit_status.and_then(|status| status.code());
+        info!(
+            "✅ Claude process execution monitoring complete (exit status: {:?})",
+            exit_status
+        );
 
         // Wait for reading tasks to complete
         info!("⏳ Waiting for stdout/stderr reading to complete...");
@@ -1094,18 +2419,60 @@ async fn spawn_agent_system(
             String::new()
         };
 
-        // Wait for process completion and update status
-        info!("✅ Claude process execution monitoring complete");
+        let result = result_message.lock().ok().and_then(|r| r.clone());
+        let mut outcome = AgentRunOutcome::determine(exit_code, result.as_ref());
+        if let Some(reason) = termination_reason.lock().ok().and_then(|r| r.clone()) {
+            outcome = AgentRunOutcome::stopped(reason, outcome.result_text);
+        }
+        let stderr_text = stderr_tail
+            .lock()
+            .map(|tail| tail.iter().cloned().collect::<Vec<_>>().join("\n"))
+            .unwrap_or_default();
+        let stderr_text = Some(stderr_text).filter(|s| !s.is_empty());
+
+        // Keep the changes of isolated runs on the worktree's branch
+        if let Some((worktree, hooks_settings_created)) = &worktree {
+            let exclude = hooks_settings_exclude(*hooks_settings_created);
+            let message = format!("Agent run {}: {}", run_id, agent_name);
+            match crate::commands::worktree::commit_changes(&worktree.path, &message, exclude) {
+                Ok(true) => info!(
+                    "📦 Committed agent run {} changes to {}",
+                    run_id, worktree.branch
+                ),
+                Ok(false) => info!("📦 Agent run {} made no changes", run_id),
+                Err(e) => error!("❌ Failed to commit agent run {} changes: {}", run_id, e),
+            }
+        }
 
-        // Update the run record with session ID and mark as completed - open a new connection
+        // Record the outcome - open a new connection. Runs that were cancelled in the
+        // meantime keep their status, but still get the exit details.
         if let Ok(conn) = Connection::open(&db_path_for_monitor) {
+            // Keep the output in case the session JSONL goes missing
+            let output = live_output.lock().map(|o| o.clone()).unwrap_or_default();
+            if !output.is_empty() {
+                if let Err(e) = crate::commands::agent_output::store(&conn, run_id, &output) {
+                    error!("❌ Failed to store output of agent run {}: {}", run_id, e);
+                }
+            }
+
             info!(
-                "🔄 Updating database with extracted session ID: {}",
-                extracted_session_id
+                "🔄 Updating agent run {} with session ID {} and status {}",
+                run_id, extracted_session_id, outcome.status
             );
             match conn.execute(
-                "UPDATE agent_runs SET session_id = ?1, status = 'completed', completed_at = CURRENT_TIMESTAMP WHERE id = ?2",
-                params![extracted_session_id, run_id],
+                "UPDATE agent_runs SET session_id = ?1, exit_code = ?2, error_subtype = ?3, stderr_tail = ?4, result_text = ?5,
+                 status = CASE WHEN status = 'running' THEN ?6 ELSE status END,
+                 completed_at = COALESCE(completed_at, CURRENT_TIMESTAMP)
+                 WHERE id = ?7",
+                params![
+                    extracted_session_id,
+                    exit_code,
+                    outcome.error_subtype,
+                    stderr_text,
+                    outcome.result_text,
+                    outcome.status,
+                    run_id
+                ],
             ) {
                 Ok(rows_affected) => {
                     if rows_affected > 0 {
@@ -1125,10 +2492,9 @@ async fn spawn_agent_system(
             );
         }
 
-        // Cleanup will be handled by the cleanup_finished_processes function
-
-        let _ = app.emit("agent-complete", true);
-        let _ = app.emit(&format!("agent-complete:{}", run_id), true);
+        let success = outcome.status == "completed";
+        host.emit("agent-complete", success);
+        host.emit(&format!("agent-complete:{}", run_id), success);
     });
 
     Ok(run_id)
@@ -1143,35 +2509,15 @@ pub async fn list_running_sessions(
     let conn = db.0.lock().map_err(|e| e.to_string())?;
 
     // First get all running sessions from the database
-    let mut stmt = conn.prepare(
-        "SELECT id, agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at, created_at, completed_at 
-         FROM agent_runs WHERE status = 'running' ORDER BY process_started_at DESC"
-    ).map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM agent_runs WHERE status = 'running' ORDER BY process_started_at DESC",
+            AGENT_RUN_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
 
     let mut runs = stmt
-        .query_map([], |row| {
-            Ok(AgentRun {
-                id: Some(row.get(0)?),
-                agent_id: row.get(1)?,
-                agent_name: row.get(2)?,
-                agent_icon: row.get(3)?,
-                task: row.get(4)?,
-                model: row.get(5)?,
-                project_path: row.get(6)?,
-                session_id: row.get(7)?,
-                status: row
-                    .get::<_, String>(8)
-                    .unwrap_or_else(|_| "pending".to_string()),
-                pid: row
-                    .get::<_, Option<i64>>(9)
-                    .ok()
-                    .flatten()
-                    .map(|p| p as u32),
-                process_started_at: row.get(10)?,
-                created_at: row.get(11)?,
-                completed_at: row.get(12)?,
-            })
-        })
+        .query_map([], agent_run_from_row)
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
@@ -1208,16 +2554,35 @@ pub async fn kill_agent_session(
 ) -> Result<bool, String> {
     info!("Attempting to kill agent session {}", run_id);
 
+    // Record the cancellation before signalling the process: it exits cleanly on
+    // SIGINT, and the monitor only records an outcome for runs still `running`
+    let (updated, pid) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let pid = match conn.query_row(
+            "SELECT pid FROM agent_runs WHERE id = ?1 AND status = 'running'",
+            params![run_id],
+            |row| row.get::<_, Option<i64>>(0),
+        ) {
+            Ok(pid) => pid,
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(e.to_string()),
+        };
+        let updated = conn.execute(
+            "UPDATE agent_runs SET status = 'cancelled', completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'running'",
+            params![run_id],
+        ).map_err(|e| e.to_string())?;
+        (updated, pid)
+    };
+
     // First try to kill using the process registry
-    let killed_via_registry = match registry.0.kill_process(run_id).await {
-        Ok(success) => {
-            if success {
-                info!("Successfully killed process {} via registry", run_id);
-                true
-            } else {
-                warn!("Process {} not found in registry", run_id);
-                false
-            }
+    let killed_via_registry = match registry.0.terminate_process(run_id).await {
+        Ok(Some(stage)) => {
+            info!("Stopped process {} via registry ({:?})", run_id, stage);
+            true
+        }
+        Ok(None) => {
+            warn!("Process {} not found in registry", run_id);
+            false
         }
         Err(e) => {
             warn!("Failed to kill process {} via registry: {}", run_id, e);
@@ -1227,29 +2592,13 @@ pub async fn kill_agent_session(
 
     // If registry kill didn't work, try fallback with PID from database
     if !killed_via_registry {
-        let pid_result = {
-            let conn = db.0.lock().map_err(|e| e.to_string())?;
-            conn.query_row(
-                "SELECT pid FROM agent_runs WHERE id = ?1 AND status = 'running'",
-                params![run_id],
-                |row| row.get::<_, Option<i64>>(0),
-            )
-            .map_err(|e| e.to_string())?
-        };
-
-        if let Some(pid) = pid_result {
+        if let Some(pid) = pid {
             info!("Attempting fallback kill for PID {} from database", pid);
-            let _ = registry.0.kill_process_by_pid(run_id, pid as u32)?;
+            let stage = registry.0.kill_process_by_pid(run_id, pid as u32).await?;
+            info!("Stopped PID {} ({:?})", pid, stage);
         }
     }
 
-    // Update the database to mark as cancelled
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
-    let updated = conn.execute(
-        "UPDATE agent_runs SET status = 'cancelled', completed_at = CURRENT_TIMESTAMP WHERE id = ?1 AND status = 'running'",
-        params![run_id],
-    ).map_err(|e| e.to_string())?;
-
     // Emit cancellation event with run_id for proper isolation
     let _ = app.emit(&format!("agent-cancelled:{}", run_id), true);
 
@@ -1277,64 +2626,85 @@ pub async fn get_session_status(
 
 /// Cleanup finished processes and update their status
 #[tauri::command]
-pub async fn cleanup_finished_processes(db: State<'_, AgentDb>) -> Result<Vec<i64>, String> {
+pub async fn cleanup_finished_processes(
+    db: State<'_, AgentDb>,
+    registry: State<'_, crate::process::ProcessRegistryState>,
+) -> Result<Vec<i64>, String> {
     let conn = db.0.lock().map_err(|e| e.to_string())?;
 
-    // Get all running processes
+    let mut cleaned_up = Vec::new();
+
+    for run in crate::commands::recovery::running_runs(&conn)? {
+        let run_id = run.id.unwrap_or_default();
+        // Runs tracked by the registry are finalized by their monitor task, or the
+        // tail task of an adopted run, which record the real outcome
+        if registry.0.get_process(run_id)?.is_some() {
+            continue;
+        }
+
+        if crate::commands::recovery::run_process_is_gone(&run)
+            && crate::commands::recovery::mark_interrupted(&conn, run_id)?
+        {
+            cleaned_up.push(run_id);
+            info!(
+                "Marked agent run {} as interrupted (PID {:?} no longer running)",
+                run_id, run.pid
+            );
+        }
+    }
+
+    // Remove the worktrees of finished isolated runs; their changes stay on the run's
+    // branch. Follow-ups share the worktree of the run they continue, so it is kept
+    // while any of them has not finished.
     let mut stmt = conn
-        .prepare("SELECT id, pid FROM agent_runs WHERE status = 'running' AND pid IS NOT NULL")
+        .prepare(
+            "SELECT id, project_path, worktree_path, EXISTS (
+                 SELECT 1 FROM agent_runs AS shared
+                 WHERE shared.worktree_path = run.worktree_path AND shared.hooks_settings_created
+             )
+             FROM agent_runs AS run
+             WHERE status IN ('completed', 'failed', 'cancelled', 'interrupted', 'budget_exceeded')
+             AND worktree_path IS NOT NULL
+             AND NOT EXISTS (
+                 SELECT 1 FROM agent_runs AS follow_up
+                 WHERE follow_up.worktree_path = run.worktree_path
+                 AND follow_up.status NOT IN ('completed', 'failed', 'cancelled', 'interrupted', 'budget_exceeded')
+             )",
+        )
         .map_err(|e| e.to_string())?;
 
-    let running_processes = stmt
-        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))
+    let finished_worktrees = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, bool>(3)?,
+            ))
+        })
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
 
     drop(stmt);
 
-    let mut cleaned_up = Vec::new();
-
-    for (run_id, pid) in running_processes {
-        // Check if the process is still running
-        let is_running = if cfg!(target_os = "windows") {
-            // On Windows, use tasklist to check if process exists
-            match std::process::Command::new("tasklist")
-                .args(["/FI", &format!("PID eq {}", pid)])
-                .args(["/FO", "CSV"])
-                .output()
-            {
-                Ok(output) => {
-                    let output_str = String::from_utf8_lossy(&output.stdout);
-                    output_str.lines().count() > 1 // Header + process line if exists
-                }
-                Err(_) => false,
-            }
-        } else {
-            // On Unix-like systems, use kill -0 to check if process exists
-            match std::process::Command::new("kill")
-                .args(["-0", &pid.to_string()])
-                .output()
-            {
-                Ok(output) => output.status.success(),
-                Err(_) => false,
-            }
-        };
+    for (run_id, project_path, worktree_path, hooks_settings_created) in finished_worktrees {
+        if !std::path::Path::new(&worktree_path).exists() || registry.0.has_child_handle(run_id)? {
+            continue;
+        }
 
-        if !is_running {
-            // Process has finished, update status
-            let updated = conn.execute(
-                "UPDATE agent_runs SET status = 'completed', completed_at = CURRENT_TIMESTAMP WHERE id = ?1",
-                params![run_id],
-            ).map_err(|e| e.to_string())?;
+        // Normally done by the monitor task, unless the app exited before the run ended
+        let message = format!("Agent run {} (uncommitted changes)", run_id);
+        let exclude = hooks_settings_exclude(hooks_settings_created);
+        if let Err(e) = crate::commands::worktree::commit_changes(&worktree_path, &message, exclude)
+        {
+            warn!("Keeping worktree of agent run {}: {}", run_id, e);
+            continue;
+        }
 
-            if updated > 0 {
-                cleaned_up.push(run_id);
-                info!(
-                    "Marked agent run {} as completed (PID {} no longer running)",
-                    run_id, pid
-                );
-            }
+        match crate::commands::worktree::remove(&project_path, &worktree_path) {
+            Ok(_) => info!("Removed worktree of agent run {}", run_id),
+            Err(e) => warn!("Failed to remove worktree of agent run {}: {}", run_id, e),
         }
     }
 
@@ -1358,15 +2728,11 @@ pub async fn get_session_output(
     run_id: i64,
 ) -> Result<String, String> {
     // Get the session information
-    let run = get_agent_run(db, run_id).await?;
+    let run = db.agent_run(run_id)?;
 
-    // If no session ID yet, try to get live output from registry
+    // If no session ID yet, use the live or stored output
     if run.session_id.is_empty() {
-        let live_output = registry.0.get_live_output(run_id)?;
-        if !live_output.is_empty() {
-            return Ok(live_output);
-        }
-        return Ok(String::new());
+        return fallback_output(&db, &registry, run_id);
     }
 
     // Get the Claude directory
@@ -1421,9 +2787,8 @@ pub async fn get_session_output(
                     session_path.display(),
                     e
                 );
-                // Fallback to live output if file read fails
-                let live_output = registry.0.get_live_output(run_id)?;
-                Ok(live_output)
+                // Fallback to live or stored output if file read fails
+                fallback_output(&db, &registry, run_id)
             }
         }
     } else {
@@ -1432,17 +2797,31 @@ pub async fn get_session_output(
             "Session file not found for {}, trying legacy method",
             run.session_id
         );
-        match read_session_jsonl(&run.session_id, &run.project_path).await {
+        match read_session_jsonl(&run.session_id, run.working_dir()).await {
             Ok(content) => Ok(content),
             Err(_) => {
-                // Final fallback to live output
-                let live_output = registry.0.get_live_output(run_id)?;
-                Ok(live_output)
+                // Final fallback to live or stored output
+                fallback_output(&db, &registry, run_id)
             }
         }
     }
 }
 
+/// Output of a run when its session JSONL can't be read: the live output while it runs,
+/// otherwise the output stored when it finished
+fn fallback_output(
+    db: &AgentDb,
+    registry: &crate::process::ProcessRegistryState,
+    run_id: i64,
+) -> Result<String, String> {
+    let live_output = registry.0.get_live_output(run_id)?;
+    if !live_output.is_empty() {
+        return Ok(live_output);
+    }
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(crate::commands::agent_output::load(&conn, run_id)?.unwrap_or_default())
+}
+
 /// Stream real-time session output by watching the JSONL file
 #[tauri::command]
 pub async fn stream_session_output(
@@ -1459,7 +2838,7 @@ pub async fn stream_session_output(
     }
 
     let session_id = run.session_id.clone();
-    let project_path = run.project_path.clone();
+    let project_path = run.working_dir().to_string();
 
     // Spawn a task to monitor the file
     tokio::spawn(async move {
@@ -1468,7 +2847,7 @@ pub async fn stream_session_output(
             None => return,
         };
 
-        let encoded_project = project_path.replace('/', "-");
+        let encoded_project = encode_project_path(&project_path);
         let project_dir = claude_dir.join(&encoded_project);
         let session_file = project_dir.join(format!("{}.jsonl", session_id));
 
@@ -1530,39 +2909,15 @@ pub async fn stream_session_output(
     Ok(())
 }
 
-/// Export a single agent to JSON format
+/// Export a single agent as a package, including its bundled resources
 #[tauri::command]
 pub async fn export_agent(db: State<'_, AgentDb>, id: i64) -> Result<String, String> {
+    let agent = get_agent(db.clone(), id).await?;
     let conn = db.0.lock().map_err(|e| e.to_string())?;
-
-    // Fetch the agent
-    let agent = conn
-        .query_row(
-            "SELECT name, icon, system_prompt, default_task, model, hooks FROM agents WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(serde_json::json!({
-                    "name": row.get::<_, String>(0)?,
-                    "icon": row.get::<_, String>(1)?,
-                    "system_prompt": row.get::<_, String>(2)?,
-                    "default_task": row.get::<_, Option<String>>(3)?,
-                    "model": row.get::<_, String>(4)?,
-                    "hooks": row.get::<_, Option<String>>(5)?
-                }))
-            },
-        )
-        .map_err(|e| format!("Failed to fetch agent: {}", e))?;
-
-    // Create the export wrapper
-    let export_data = serde_json::json!({
-        "version": 1,
-        "exported_at": chrono::Utc::now().to_rfc3339(),
-        "agent": agent
-    });
+    let package = load_agent_resources(&conn, id)?.into_package(packaged_agent(&agent));
 
     // Convert to pretty JSON string
-    serde_json::to_string_pretty(&export_data)
-        .map_err(|e| format!("Failed to serialize agent: {}", e))
+    serde_json::to_string_pretty(&package).map_err(|e| format!("Failed to serialize agent: {}", e))
 }
 
 /// Export agent to file with native dialog
@@ -1581,6 +2936,17 @@ pub async fn export_agent_to_file(
     Ok(())
 }
 
+/// Get the changes an isolated agent run made, as a unified diff against its base commit
+#[tauri::command]
+pub async fn get_agent_run_diff(db: State<'_, AgentDb>, run_id: i64) -> Result<String, String> {
+    let run = get_agent_run(db, run_id).await?;
+    let worktree = run
+        .worktree()
+        .ok_or("This agent run was not isolated in a worktree")?;
+
+    crate::commands::worktree::diff(&run.project_path, &worktree)
+}
+
 /// Get the stored Claude binary path from settings
 #[tauri::command]
 pub async fn get_claude_binary_path(db: State<'_, AgentDb>) -> Result<Option<String>, String> {
@@ -1631,6 +2997,38 @@ pub async fn set_claude_binary_path(db: State<'_, AgentDb>, path: String) -> Res
     Ok(())
 }
 
+/// Get the global time limits applied to agent runs
+#[tauri::command]
+pub async fn get_agent_run_limits(db: State<'_, AgentDb>) -> Result<AgentRunLimits, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(AgentRunLimits::load_global(&conn))
+}
+
+/// Save the global time limits applied to agent runs
+#[tauri::command]
+pub async fn save_agent_run_limits(
+    db: State<'_, AgentDb>,
+    limits: AgentRunLimits,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+
+    let values = [
+        limits.first_output_timeout_secs,
+        limits.max_runtime_secs,
+        limits.idle_timeout_secs,
+    ];
+    for (key, value) in AgentRunLimits::SETTINGS_KEYS.iter().zip(values) {
+        conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = ?2",
+            params![key, value.to_string()],
+        )
+        .map_err(|e| format!("Failed to save agent run limits: {}", e))?;
+    }
+
+    Ok(())
+}
+
 /// List all available Claude installations on the system
 #[tauri::command]
 pub async fn list_claude_installations(
@@ -1703,238 +3101,445 @@ fn create_command_with_env(program: &str) -> Command {
     tokio_cmd
 }
 
-/// Import an agent from JSON data
+/// Import an agent package from JSON data
+///
+/// Version 1 exports are upgraded on the fly. Pasted JSON can't be verified,
+/// so this is refused when the trust settings require verification. Bundled
+/// slash commands and MCP servers are only installed with `install_resources`.
 #[tauri::command]
-pub async fn import_agent(db: State<'_, AgentDb>, json_data: String) -> Result<Agent, String> {
-    // Parse the JSON data
-    let export_data: AgentExport =
-        serde_json::from_str(&json_data).map_err(|e| format!("Invalid JSON format: {}", e))?;
-
-    // Validate version
-    if export_data.version != 1 {
-        return Err(format!(
-            "Unsupported export version: {}. This version of the app only supports version 1.",
-            export_data.version
-        ));
+pub async fn import_agent(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    json_data: String,
+    install_resources: Option<bool>,
+) -> Result<AgentImportResult, String> {
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        if AgentTrustSettings::load(&conn).require_verification {
+            return Err(
+                "Agent verification is required; import a signed or pinned agent file instead"
+                    .to_string(),
+            );
+        }
     }
 
-    let agent_data = export_data.agent;
-    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let package = AgentPackage::parse(&json_data)?;
+    install_agent_package(&app, &db, package, install_resources.unwrap_or(false)).await
+}
 
-    // Check if an agent with the same name already exists
-    let existing_count: i64 = conn
-        .query_row(
-            "SELECT COUNT(*) FROM agents WHERE name = ?1",
-            params![agent_data.name],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+/// Create an agent from a package
+///
+/// Bundled resources are stored with the agent. With `install_resources`, the
+/// user opted in after reviewing them, and its slash commands and MCP servers
+/// are installed at user scope unless they clash with existing ones, which is
+/// reported in the result.
+async fn install_agent_package(
+    app: &AppHandle,
+    db: &State<'_, AgentDb>,
+    package: AgentPackage,
+    install_resources: bool,
+) -> Result<AgentImportResult, String> {
+    let agent = {
+        let agent_data = &package.agent;
+        let definitions = task_template::parse_parameters(agent_data.parameters.as_deref())?;
+        task_template::validate_definitions(&definitions, agent_data.task_template.as_deref())?;
 
-    // If agent with same name exists, append a suffix
-    let final_name = if existing_count > 0 {
-        format!("{} (Imported)", agent_data.name)
-    } else {
-        agent_data.name
-    };
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
 
-    // Create the agent
-    conn.execute(
-        "INSERT INTO agents (name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks) VALUES (?1, ?2, ?3, ?4, ?5, 1, 1, 0, ?6)",
-        params![
-            final_name,
-            agent_data.icon,
-            agent_data.system_prompt,
-            agent_data.default_task,
-            agent_data.model,
-            agent_data.hooks
-        ],
-    )
-    .map_err(|e| format!("Failed to create agent: {}", e))?;
+        // Check if an agent with the same name already exists
+        let existing_count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM agents WHERE name = ?1",
+                params![agent_data.name],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
 
-    let id = conn.last_insert_rowid();
+        // If agent with same name exists, append a suffix
+        let final_name = if existing_count > 0 {
+            format!("{} (Imported)", agent_data.name)
+        } else {
+            agent_data.name.clone()
+        };
 
-    // Fetch the created agent
-    let agent = conn
-        .query_row(
-            "SELECT id, name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, created_at, updated_at FROM agents WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(Agent {
-                    id: Some(row.get(0)?),
-                    name: row.get(1)?,
-                    icon: row.get(2)?,
-                    system_prompt: row.get(3)?,
-                    default_task: row.get(4)?,
-                    model: row.get(5)?,
-                    enable_file_read: row.get(6)?,
-                    enable_file_write: row.get(7)?,
-                    enable_network: row.get(8)?,
-                    hooks: row.get(9)?,
-                    created_at: row.get(10)?,
-                    updated_at: row.get(11)?,
-                })
-            },
+        // Create the agent
+        conn.execute(
+            "INSERT INTO agents (name, icon, system_prompt, default_task, model, enable_file_read, enable_file_write, enable_network, hooks, first_output_timeout_secs, max_runtime_secs, idle_timeout_secs, max_cost_usd, max_tokens, parameters, task_template) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            params![
+                final_name,
+                agent_data.icon,
+                agent_data.system_prompt,
+                agent_data.default_task,
+                agent_data.model,
+                agent_data.enable_file_read,
+                agent_data.enable_file_write,
+                agent_data.enable_network,
+                agent_data.hooks,
+                agent_data.first_output_timeout_secs,
+                agent_data.max_runtime_secs,
+                agent_data.idle_timeout_secs,
+                agent_data.max_cost_usd,
+                agent_data.max_tokens,
+                agent_data.parameters,
+                agent_data.task_template
+            ],
         )
-        .map_err(|e| format!("Failed to fetch created agent: {}", e))?;
+        .map_err(|e| format!("Failed to create agent: {}", e))?;
 
-    Ok(agent)
+        let id = conn.last_insert_rowid();
+        save_agent_resources(&conn, id, &package)?;
+
+        // Fetch the created agent
+        let agent = conn
+            .query_row(
+                &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
+                params![id],
+                agent_from_row,
+            )
+            .map_err(|e| format!("Failed to fetch created agent: {}", e))?;
+        record_agent_version(&conn, &agent)?;
+        agent
+    };
+
+    let (installed, conflicts) = if install_resources {
+        agent_package::install_resources(app, &package, None).await
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
+    Ok(AgentImportResult {
+        agent,
+        installed,
+        conflicts,
+    })
 }
 
 /// Import agent from file
+///
+/// The file is verified against the signature and checksum next to it.
+/// `expected_sha256` pins the import to the file that was previewed, and
+/// `install_resources` installs its bundled slash commands and MCP servers.
 #[tauri::command]
 pub async fn import_agent_from_file(
+    app: AppHandle,
     db: State<'_, AgentDb>,
     file_path: String,
-) -> Result<Agent, String> {
-    // Read the file
-    let mut json_data =
-        std::fs::read_to_string(&file_path).map_err(|e| format!("Failed to read file: {}", e))?;
-
-    // Normalize potential BOM and whitespace issues
-    if json_data.starts_with('\u{feff}') {
-        json_data = json_data.trim_start_matches('\u{feff}').to_string();
-    }
-    // Also trim leading/trailing whitespace to avoid parse surprises
-    json_data = json_data.trim().to_string();
+    expected_sha256: Option<String>,
+    install_resources: Option<bool>,
+) -> Result<AgentImportResult, String> {
+    let settings = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        AgentTrustSettings::load(&conn)
+    };
+    let preview = agent_trust::preview_file(&file_path, &settings, expected_sha256.as_deref())?;
+    agent_trust::check_expected_sha256(&preview.verification, expected_sha256.as_deref())?;
 
     // Import the agent
-    import_agent(db, json_data).await
+    install_agent_package(
+        &app,
+        &db,
+        preview.package,
+        install_resources.unwrap_or(false),
+    )
+    .await
 }
 
-// GitHub Agent Import functionality
+/// Import an agent directly from one of the configured agent sources
+///
+/// Bundled slash commands and MCP servers are only installed with `install_resources`.
+#[tauri::command]
+pub async fn import_agent_from_github(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+    download_url: String,
+    source_id: Option<String>,
+    sha: Option<String>,
+    expected_sha256: Option<String>,
+    install_resources: Option<bool>,
+) -> Result<AgentImportResult, String> {
+    info!("Importing agent from registry: {}", download_url);
+
+    // First, fetch and verify the agent content
+    let preview = agent_registry::fetch_agent_preview(
+        &app,
+        &db,
+        &download_url,
+        source_id.clone(),
+        expected_sha256.as_deref(),
+    )
+    .await?;
+    agent_trust::check_expected_sha256(&preview.verification, expected_sha256.as_deref())?;
+
+    // Remember where the agent came from, and what it looked like, for updates
+    let snapshot = serde_json::to_string(&preview.package.agent).map_err(|e| e.to_string())?;
+    let mut result = install_agent_package(
+        &app,
+        &db,
+        preview.package,
+        install_resources.unwrap_or(false),
+    )
+    .await?;
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE agents SET source_id = ?1, source_url = ?2, source_sha = ?3, source_snapshot = ?4 WHERE id = ?5",
+        params![source_id, download_url, sha, snapshot, result.agent.id],
+    )
+    .map_err(|e| format!("Failed to record agent source: {}", e))?;
+    result.agent.source_id = source_id;
+    result.agent.source_url = Some(download_url);
+    result.agent.source_sha = sha;
 
-/// Represents a GitHub agent file from the API
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct GitHubAgentFile {
-    pub name: String,
-    pub path: String,
-    pub download_url: String,
-    pub size: i64,
-    pub sha: String,
+    Ok(result)
 }
 
-/// Represents the GitHub API response for directory contents
-#[derive(Debug, Deserialize)]
-struct GitHubApiResponse {
-    name: String,
-    path: String,
-    sha: String,
-    size: i64,
-    download_url: Option<String>,
-    #[serde(rename = "type")]
-    file_type: String,
+/// An available upstream update of an imported agent
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AgentUpdate {
+    pub agent_id: i64,
+    pub agent_name: String,
+    pub source_url: String,
+    pub upstream_sha: Option<String>,
+    /// Checksum of the upstream file, which pins `apply_agent_update` to it
+    pub upstream_sha256: String,
+    /// Changes applying the update makes to the agent
+    pub changes: Vec<AgentVersionChange>,
+    /// Fields changed both upstream and locally; applying keeps the local values
+    pub preserved_overrides: Vec<String>,
 }
 
-/// Fetch list of agents from GitHub repository
-#[tauri::command]
-pub async fn fetch_github_agents() -> Result<Vec<GitHubAgentFile>, String> {
-    info!("Fetching agents from GitHub repository...");
-
-    let client = reqwest::Client::new();
-    let url = "https://api.github.com/repos/getAsterisk/opcode/contents/cc_agents";
-
-    let response = client
-        .get(url)
-        .header("Accept", "application/vnd.github+json")
-        .header("User-Agent", "opcode-App")
-        .send()
-        .await
-        .map_err(|e| format!("Failed to fetch from GitHub: {}", e))?;
+/// Fetch the upstream version of an imported agent and merge it with the local one
+///
+/// Returns `None` if nothing changed upstream since the agent was imported or
+/// last updated. Otherwise returns the update along with the merged agent and
+/// the upstream package, whose agent becomes the new base for future merges.
+/// `expected_sha256` pins the upstream file to the one that was checked.
+async fn fetch_agent_update(
+    app: &AppHandle,
+    db: &State<'_, AgentDb>,
+    agent: &Agent,
+    upstream_sha: Option<String>,
+    expected_sha256: Option<&str>,
+) -> Result<Option<(AgentUpdate, PackagedAgent, AgentPackage)>, String> {
+    let agent_id = agent.id.ok_or("Agent has no ID")?;
+    let source_url = agent
+        .source_url
+        .clone()
+        .ok_or_else(|| format!("Agent '{}' was not imported from a source", agent.name))?;
+
+    let (snapshot, resources): (Option<String>, _) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let snapshot = conn
+            .query_row(
+                "SELECT source_snapshot FROM agents WHERE id = ?1",
+                params![agent_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        (snapshot, load_agent_resources(&conn, agent_id)?)
+    };
+    let local = packaged_agent(agent);
+    let base = match snapshot
+        .as_deref()
+        .map(serde_json::from_str::<PackagedAgent>)
+    {
+        Some(Ok(base)) => base,
+        _ => local.clone(),
+    };
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("GitHub API error ({}): {}", status, error_text));
+    let preview = agent_registry::fetch_agent_preview(
+        app,
+        db,
+        &source_url,
+        agent.source_id.clone(),
+        expected_sha256,
+    )
+    .await?;
+    agent_trust::check_expected_sha256(&preview.verification, expected_sha256)?;
+    let upstream_sha256 = preview.verification.sha256;
+    let upstream = preview.package;
+
+    let merge = PackagedAgent::merge_update(&base, &local, &upstream.agent)?;
+    let resource_changes = resources.changes(&upstream);
+    if merge.updated.is_empty() && merge.preserved.is_empty() && resource_changes.is_empty() {
+        return Ok(None);
     }
 
-    let api_files: Vec<GitHubApiResponse> = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse GitHub response: {}", e))?;
-
-    // Filter only .opcode.json agent files
-    let agent_files: Vec<GitHubAgentFile> = api_files
-        .into_iter()
-        .filter(|f| f.name.ends_with(".opcode.json") && f.file_type == "file")
-        .filter_map(|f| {
-            f.download_url.map(|download_url| GitHubAgentFile {
-                name: f.name,
-                path: f.path,
-                download_url,
-                size: f.size,
-                sha: f.sha,
-            })
+    let value_string = |value: Option<&JsonValue>| match value {
+        None | Some(JsonValue::Null) => None,
+        Some(JsonValue::String(s)) => Some(s.clone()),
+        Some(value) => Some(value.to_string()),
+    };
+    let (old, new) = (
+        serde_json::to_value(&local).map_err(|e| e.to_string())?,
+        serde_json::to_value(&merge.merged).map_err(|e| e.to_string())?,
+    );
+    let changes = merge
+        .updated
+        .iter()
+        .map(|field| AgentVersionChange {
+            field: field.clone(),
+            old_value: value_string(old.get(field)),
+            new_value: value_string(new.get(field)),
         })
+        .chain(resource_changes)
         .collect();
 
-    info!("Found {} agents on GitHub", agent_files.len());
-    Ok(agent_files)
+    let update = AgentUpdate {
+        agent_id,
+        agent_name: agent.name.clone(),
+        source_url,
+        upstream_sha,
+        upstream_sha256,
+        changes,
+        preserved_overrides: merge.preserved,
+    };
+    Ok(Some((update, merge.merged, upstream)))
 }
 
-/// Fetch and preview a specific agent from GitHub
+/// Check all agents imported from agent sources for upstream changes
 #[tauri::command]
-pub async fn fetch_github_agent_content(download_url: String) -> Result<AgentExport, String> {
-    info!("Fetching agent content from: {}", download_url);
-
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&download_url)
-        .header("Accept", "application/json")
-        .header("User-Agent", "opcode-App")
-        .send()
-        .await
-        .map_err(|e| format!("Failed to download agent: {}", e))?;
-
-    if !response.status().is_success() {
-        return Err(format!(
-            "Failed to download agent: HTTP {}",
-            response.status()
-        ));
+pub async fn check_agent_updates(
+    app: AppHandle,
+    db: State<'_, AgentDb>,
+) -> Result<Vec<AgentUpdate>, String> {
+    let (agents, sources) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM agents WHERE source_url IS NOT NULL ORDER BY name",
+                AGENT_COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+        let agents = stmt
+            .query_map([], agent_from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        (agents, agent_registry::load_sources(&conn))
+    };
+    if agents.is_empty() {
+        return Ok(Vec::new());
     }
 
-    let json_text = response
-        .text()
-        .await
-        .map_err(|e| format!("Failed to read response: {}", e))?;
-
-    // Parse and validate the agent data
-    let export_data: AgentExport = serde_json::from_str(&json_text)
-        .map_err(|e| format!("Invalid agent JSON format: {}", e))?;
+    let listing = agent_registry::list_agents(&app, &sources).await?;
+
+    let mut updates = Vec::new();
+    for agent in &agents {
+        // The listing's SHA tells whether the file changed without downloading it
+        let listed = listing.agents.iter().find(|f| {
+            Some(&f.download_url) == agent.source_url.as_ref()
+                && Some(&f.source_id) == agent.source_id.as_ref()
+        });
+        let upstream_sha = listed.map(|f| f.sha.clone());
+        if upstream_sha.is_some() && upstream_sha == agent.source_sha {
+            continue;
+        }
 
-    // Validate version
-    if export_data.version != 1 {
-        return Err(format!(
-            "Unsupported agent version: {}",
-            export_data.version
-        ));
+        match fetch_agent_update(&app, &db, agent, upstream_sha, None).await {
+            Ok(Some((update, _, _))) => updates.push(update),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to check agent '{}' for updates: {}", agent.name, e),
+        }
     }
 
-    Ok(export_data)
+    info!("Found {} agent updates", updates.len());
+    Ok(updates)
 }
 
-/// Import an agent directly from GitHub
+/// Apply the upstream update of an imported agent, keeping local overrides
+///
+/// `upstream_sha256` is the checksum reported by `check_agent_updates`; the update
+/// fails if the upstream file changed since. The agent's bundled resources are replaced by the upstream ones. With
+/// `install_resources`, slash commands and MCP servers installed from the
+/// previous version are updated and new ones installed.
 #[tauri::command]
-pub async fn import_agent_from_github(
+pub async fn apply_agent_update(
+    app: AppHandle,
     db: State<'_, AgentDb>,
-    download_url: String,
-) -> Result<Agent, String> {
-    info!("Importing agent from GitHub: {}", download_url);
+    agent_id: i64,
+    upstream_sha: Option<String>,
+    upstream_sha256: String,
+    install_resources: Option<bool>,
+) -> Result<AgentImportResult, String> {
+    let agent = get_agent(db.clone(), agent_id).await?;
+    let Some((_, merged, upstream)) = fetch_agent_update(
+        &app,
+        &db,
+        &agent,
+        upstream_sha.clone(),
+        Some(&upstream_sha256),
+    )
+    .await?
+    else {
+        return Ok(AgentImportResult {
+            agent,
+            installed: Vec::new(),
+            conflicts: Vec::new(),
+        });
+    };
+
+    let snapshot = serde_json::to_string(&upstream.agent).map_err(|e| e.to_string())?;
+    let (agent, previous) = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let previous = load_agent_resources(&conn, agent_id)?.into_package(packaged_agent(&agent));
+        conn.execute(
+            "UPDATE agents SET name = ?1, icon = ?2, system_prompt = ?3, default_task = ?4, model = ?5, enable_file_read = ?6, enable_file_write = ?7, enable_network = ?8, hooks = ?9, first_output_timeout_secs = ?10, max_runtime_secs = ?11, idle_timeout_secs = ?12, max_cost_usd = ?13, max_tokens = ?14, parameters = ?15, task_template = ?16, source_sha = COALESCE(?17, source_sha), source_snapshot = ?18, updated_at = CURRENT_TIMESTAMP WHERE id = ?19",
+            params![
+                merged.name,
+                merged.icon,
+                merged.system_prompt,
+                merged.default_task,
+                merged.model,
+                merged.enable_file_read,
+                merged.enable_file_write,
+                merged.enable_network,
+                merged.hooks,
+                merged.first_output_timeout_secs,
+                merged.max_runtime_secs,
+                merged.idle_timeout_secs,
+                merged.max_cost_usd,
+                merged.max_tokens,
+                merged.parameters,
+                merged.task_template,
+                upstream_sha,
+                snapshot,
+                agent_id
+            ],
+        )
+        .map_err(|e| format!("Failed to update agent: {}", e))?;
 
-    // First, fetch the agent content
-    let export_data = fetch_github_agent_content(download_url).await?;
+        let agent = conn
+            .query_row(
+                &format!("SELECT {} FROM agents WHERE id = ?1", AGENT_COLUMNS),
+                params![agent_id],
+                agent_from_row,
+            )
+            .map_err(|e| e.to_string())?;
+        save_agent_resources(&conn, agent_id, &upstream)?;
+        record_agent_version(&conn, &agent)?;
+        (agent, previous)
+    };
+    info!("Updated agent '{}' from {:?}", agent.name, agent.source_url);
 
-    // Convert to JSON string and use existing import logic
-    let json_data = serde_json::to_string(&export_data)
-        .map_err(|e| format!("Failed to serialize agent data: {}", e))?;
+    let (installed, conflicts) = if install_resources.unwrap_or(false) {
+        agent_package::install_resources(&app, &upstream, Some(&previous)).await
+    } else {
+        (Vec::new(), Vec::new())
+    };
 
-    // Import using existing function
-    import_agent(db, json_data).await
+    Ok(AgentImportResult {
+        agent,
+        installed,
+        conflicts,
+    })
 }
 
 /// Load agent session history from JSONL file
-/// Similar to Claude Code's load_session_history, but searches across all project directories
+/// Similar to Claude Code's load_session_history, but searches across all project directories.
+/// Falls back to the output stored for the run when the JSONL file is missing.
 #[tauri::command]
 pub async fn load_agent_session_history(
+    db: State<'_, AgentDb>,
     session_id: String,
 ) -> Result<Vec<serde_json::Value>, String> {
     log::info!("Loading agent session history for session: {}", session_id);
@@ -1993,6 +3598,239 @@ pub async fn load_agent_session_history(
 
         Ok(messages)
     } else {
-        Err(format!("Session file not found: {}", session_id))
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let output = conn
+            .query_row(
+                "SELECT id FROM agent_runs WHERE session_id = ?1 ORDER BY id DESC LIMIT 1",
+                params![session_id],
+                |row| row.get::<_, i64>(0),
+            )
+            .ok()
+            .map(|run_id| crate::commands::agent_output::load(&conn, run_id))
+            .transpose()?
+            .flatten()
+            .ok_or_else(|| format!("Session file not found: {}", session_id))?;
+
+        Ok(output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_project_path() {
+        assert_eq!(
+            encode_project_path("/home/me/my_app.v2"),
+            "-home-me-my-app-v2"
+        );
+    }
+
+    #[test]
+    fn test_run_outcome_success() {
+        let result = StreamLine::from_value(&serde_json::json!({
+            "type": "result",
+            "subtype": "success",
+            "is_error": false,
+            "result": "All done"
+        }))
+        .unwrap();
+
+        let outcome = AgentRunOutcome::determine(Some(0), result.result());
+        assert_eq!(outcome.status, "completed");
+        assert_eq!(outcome.error_subtype, None);
+        assert_eq!(outcome.result_text.as_deref(), Some("All done"));
+    }
+
+    #[test]
+    fn test_run_outcome_max_turns() {
+        let result = StreamLine::from_value(&serde_json::json!({
+            "type": "result",
+            "subtype": "error_max_turns",
+            "is_error": true
+        }))
+        .unwrap();
+
+        let outcome = AgentRunOutcome::determine(Some(0), result.result());
+        assert_eq!(outcome.status, "failed");
+        assert_eq!(outcome.error_subtype.as_deref(), Some("error_max_turns"));
+    }
+
+    #[test]
+    fn test_run_outcome_auth_failure() {
+        let result = StreamLine::from_value(&serde_json::json!({
+            "type": "result",
+            "subtype": "success",
+            "is_error": true,
+            "result": "Invalid API key · Please run /login"
+        }))
+        .unwrap();
+
+        let outcome = AgentRunOutcome::determine(Some(1), result.result());
+        assert_eq!(outcome.status, "failed");
+        assert_eq!(
+            outcome.error_subtype.as_deref(),
+            Some("authentication_failed")
+        );
+    }
+
+    #[test]
+    fn test_run_outcome_without_result_message() {
+        let outcome = AgentRunOutcome::determine(Some(0), None);
+        assert_eq!(outcome.status, "completed");
+
+        let outcome = AgentRunOutcome::determine(Some(2), None);
+        assert_eq!(outcome.status, "failed");
+        assert_eq!(outcome.error_subtype.as_deref(), Some("nonzero_exit"));
+
+        let outcome = AgentRunOutcome::determine(None, None);
+        assert_eq!(outcome.status, "failed");
+        assert_eq!(
+            outcome.error_subtype.as_deref(),
+            Some("terminated_by_signal")
+        );
+    }
+
+    #[test]
+    fn test_run_outcome_stopped() {
+        let outcome = AgentRunOutcome::stopped("budget_exceeded".to_string(), None);
+        assert_eq!(outcome.status, "budget_exceeded");
+
+        let outcome = AgentRunOutcome::stopped("idle_timeout".to_string(), None);
+        assert_eq!(outcome.status, "failed");
+        assert_eq!(outcome.error_subtype.as_deref(), Some("idle_timeout"));
+    }
+
+    #[test]
+    fn test_run_limits_exceeded() {
+        let secs = std::time::Duration::from_secs;
+        let limits = AgentRunLimits {
+            first_output_timeout_secs: 30,
+            max_runtime_secs: 600,
+            idle_timeout_secs: 60,
+        };
+
+        assert_eq!(limits.exceeded(secs(10), None), None);
+        assert_eq!(
+            limits.exceeded(secs(30), None),
+            Some("first_output_timeout")
+        );
+        assert_eq!(limits.exceeded(secs(120), Some(secs(5))), None);
+        assert_eq!(
+            limits.exceeded(secs(120), Some(secs(60))),
+            Some("idle_timeout")
+        );
+        assert_eq!(
+            limits.exceeded(secs(600), Some(secs(1))),
+            Some("max_runtime_exceeded")
+        );
+
+        let unlimited = AgentRunLimits {
+            first_output_timeout_secs: 0,
+            max_runtime_secs: 0,
+            idle_timeout_secs: 0,
+        };
+        assert_eq!(unlimited.exceeded(secs(100_000), None), None);
+        assert_eq!(unlimited.exceeded(secs(100_000), Some(secs(100_000))), None);
+    }
+
+    #[test]
+    fn test_budget_tracker_counts_each_message_once() {
+        let mut tracker = BudgetTracker::new(AgentBudget {
+            max_cost_usd: None,
+            max_tokens: Some(1000),
+        });
+        let assistant = StreamLine::from_value(&serde_json::json!({
+            "type": "assistant",
+            "message": {
+                "id": "msg_1",
+                "model": "claude-sonnet-4-20250514",
+                "usage": { "input_tokens": 400, "output_tokens": 120 }
+            }
+        }))
+        .unwrap();
+
+        assert_eq!(tracker.record(&assistant), Some(BudgetCheck::Warning(0.5)));
+        // Further content blocks of the same message repeat its usage
+        assert_eq!(tracker.record(&assistant), None);
+        assert_eq!(tracker.usage().total_tokens, 520);
+        assert!(tracker.usage().cost_usd > 0.0);
+
+        let next = StreamLine::from_value(&serde_json::json!({
+            "type": "assistant",
+            "message": {
+                "id": "msg_2",
+                "usage": { "input_tokens": 450, "output_tokens": 50 }
+            }
+        }))
+        .unwrap();
+        assert_eq!(tracker.record(&next), Some(BudgetCheck::Exceeded));
+        assert_eq!(tracker.record(&next), None);
+    }
+
+    #[test]
+    fn test_budget_tracker_uses_result_cost() {
+        let mut tracker = BudgetTracker::new(AgentBudget {
+            max_cost_usd: Some(1.0),
+            max_tokens: None,
+        });
+        let result = StreamLine::from_value(&serde_json::json!({
+            "type": "result",
+            "subtype": "success",
+            "total_cost_usd": 0.8
+        }))
+        .unwrap();
+
+        assert_eq!(tracker.record(&result), Some(BudgetCheck::Warning(0.75)));
+        assert_eq!(tracker.usage().cost_usd, 0.8);
+
+        let mut unlimited = BudgetTracker::new(AgentBudget::default());
+        assert_eq!(unlimited.record(&result), None);
+    }
+
+    #[test]
+    fn test_agent_version_changes() {
+        let previous = AgentVersion {
+            id: Some(1),
+            agent_id: 1,
+            version: 1,
+            system_prompt: "You are helpful".to_string(),
+            default_task: None,
+            model: "sonnet".to_string(),
+            enable_file_read: true,
+            enable_file_write: true,
+            enable_network: false,
+            hooks: None,
+            parameters: None,
+            task_template: None,
+            created_at: "2025-01-01 00:00:00".to_string(),
+        };
+        let current = AgentVersion {
+            version: 2,
+            system_prompt: "You are very helpful".to_string(),
+            enable_network: true,
+            ..previous.clone()
+        };
+
+        assert!(previous.changes_from(&previous).is_empty());
+        assert_eq!(
+            current.changes_from(&previous),
+            vec![
+                AgentVersionChange {
+                    field: "system_prompt".to_string(),
+                    old_value: Some("You are helpful".to_string()),
+                    new_value: Some("You are very helpful".to_string()),
+                },
+                AgentVersionChange {
+                    field: "enable_network".to_string(),
+                    old_value: Some("false".to_string()),
+                    new_value: Some("true".to_string()),
+                },
+            ]
+        );
     }
 }
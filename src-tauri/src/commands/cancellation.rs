@@ -0,0 +1,54 @@
+#![allow(dead_code)]
+use rusqlite::{params, Connection};
+use tauri::State;
+
+use crate::commands::agents::AgentDb;
+use crate::process::{CancellationSettings, ProcessRegistryState};
+
+const CANCELLATION_SETTINGS_KEY: &str = "cancellation_settings";
+
+/// Longest grace period accepted for a single cancellation stage
+const MAX_GRACE_MS: u64 = 5 * 60 * 1000;
+
+/// Load the cancellation grace periods from app_settings
+pub fn load_cancellation_settings(conn: &Connection) -> CancellationSettings {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?1",
+        params![CANCELLATION_SETTINGS_KEY],
+        |row| row.get::<_, String>(0),
+    )
+    .ok()
+    .and_then(|json| serde_json::from_str(&json).ok())
+    .unwrap_or_default()
+}
+
+/// Get how long cancelled sessions and agent runs get to exit at each stage
+#[tauri::command]
+pub async fn get_cancellation_settings(
+    db: State<'_, AgentDb>,
+) -> Result<CancellationSettings, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(load_cancellation_settings(&conn))
+}
+
+/// Save the cancellation grace periods and use them for the next cancellation
+#[tauri::command]
+pub async fn save_cancellation_settings(
+    db: State<'_, AgentDb>,
+    registry: State<'_, ProcessRegistryState>,
+    settings: CancellationSettings,
+) -> Result<(), String> {
+    if settings.interrupt_grace_ms > MAX_GRACE_MS || settings.terminate_grace_ms > MAX_GRACE_MS {
+        return Err(format!("Grace periods must not exceed {} ms", MAX_GRACE_MS));
+    }
+    let json = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+        params![CANCELLATION_SETTINGS_KEY, json],
+    )
+    .map_err(|e| format!("Failed to save cancellation settings: {}", e))?;
+
+    registry.0.set_cancellation_settings(settings)
+}
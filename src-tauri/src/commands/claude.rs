@@ -8,21 +8,12 @@ use std::process::Stdio;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tauri::{AppHandle, Emitter, Manager};
-use tokio::process::{Child, Command};
-use tokio::sync::Mutex;
+use tokio::process::Command;
 
-/// Global state to track current Claude process
-pub struct ClaudeProcessState {
-    pub current_process: Arc<Mutex<Option<Child>>>,
-}
-
-impl Default for ClaudeProcessState {
-    fn default() -> Self {
-        Self {
-            current_process: Arc::new(Mutex::new(None)),
-        }
-    }
-}
+use crate::commands::agents::AgentDb;
+use crate::commands::claude_options::{session_option_args, ClaudeSessionOptions};
+use crate::commands::session_catalog::{self, CatalogQuery, ProjectSort, SessionSort};
+use crate::stream_event::StreamLine;
 
 /// Represents a project in the ~/.claude/projects directory
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -56,23 +47,16 @@ pub struct Session {
     pub first_message: Option<String>,
     /// Timestamp of the first user message (if available)
     pub message_timestamp: Option<String>,
-}
-
-/// Represents a message entry in the JSONL file
-#[derive(Debug, Deserialize)]
-struct JsonlEntry {
-    #[serde(rename = "type")]
-    #[allow(dead_code)]
-    entry_type: Option<String>,
-    message: Option<MessageContent>,
-    timestamp: Option<String>,
-}
-
-/// Represents the message content
-#[derive(Debug, Deserialize)]
-struct MessageContent {
-    role: Option<String>,
-    content: Option<String>,
+    /// Unix timestamp when the session file was last written to
+    pub last_modified: u64,
+    /// Timestamp of the last message (if available)
+    pub last_message_timestamp: Option<String>,
+    /// Number of user and assistant messages, excluding tool results
+    pub message_count: u64,
+    /// Model of the last assistant message (if any)
+    pub model: Option<String>,
+    /// Cost of the session in USD
+    pub cost_usd: f64,
 }
 
 /// Represents the settings from ~/.claude/settings.json
@@ -136,7 +120,7 @@ fn find_claude_binary(app_handle: &AppHandle) -> Result<String, String> {
 }
 
 /// Gets the path to the ~/.claude directory
-fn get_claude_dir() -> Result<PathBuf> {
+pub(crate) fn get_claude_dir() -> Result<PathBuf> {
     dirs::home_dir()
         .context("Could not find home directory")?
         .join(".claude")
@@ -144,84 +128,16 @@ fn get_claude_dir() -> Result<PathBuf> {
         .context("Could not find ~/.claude directory")
 }
 
-/// Gets the actual project path by reading the cwd from the JSONL entries
-fn get_project_path_from_sessions(project_dir: &PathBuf) -> Result<String, String> {
-    // Try to read any JSONL file in the directory
-    let entries = fs::read_dir(project_dir)
-        .map_err(|e| format!("Failed to read project directory: {}", e))?;
-
-    for entry in entries.flatten() {
-        let path = entry.path();
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
-            // Read the JSONL file and find the first line with a valid cwd
-            if let Ok(file) = fs::File::open(&path) {
-                let reader = BufReader::new(file);
-                // Check first few lines instead of just the first line
-                // Some session files may have null cwd in the first line
-                for line in reader.lines().take(10).map_while(Result::ok) {
-                    // Parse the JSON and extract cwd
-                    if let Ok(json) = serde_json::from_str::<serde_json::Value>(&line) {
-                        if let Some(cwd) = json.get("cwd").and_then(|v| v.as_str()) {
-                            if !cwd.is_empty() {
-                                return Ok(cwd.to_string());
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    Err("Could not determine project path from session files".to_string())
-}
-
 /// Decodes a project directory name back to its original path
 /// The directory names in ~/.claude/projects are encoded paths
-/// DEPRECATED: Use get_project_path_from_sessions instead when possible
-fn decode_project_path(encoded: &str) -> String {
+/// Only a fallback for projects whose sessions record no cwd, see session_catalog
+pub(crate) fn decode_project_path(encoded: &str) -> String {
     // This is a fallback - the encoding isn't reversible when paths contain hyphens
     // For example: -Users-mufeedvh-dev-jsonl-viewer could be /Users/mufeedvh/dev/jsonl-viewer
     // or /Users/mufeedvh/dev/jsonl/viewer
     encoded.replace('-', "/")
 }
 
-/// Extracts the first valid user message from a JSONL file
-fn extract_first_user_message(jsonl_path: &PathBuf) -> (Option<String>, Option<String>) {
-    let file = match fs::File::open(jsonl_path) {
-        Ok(file) => file,
-        Err(_) => return (None, None),
-    };
-
-    let reader = BufReader::new(file);
-
-    for line in reader.lines().map_while(Result::ok) {
-        if let Ok(entry) = serde_json::from_str::<JsonlEntry>(&line) {
-            if let Some(message) = entry.message {
-                if message.role.as_deref() == Some("user") {
-                    if let Some(content) = message.content {
-                        // Skip if it contains the caveat message
-                        if content.contains("Caveat: The messages below were generated by the user while running local commands") {
-                            continue;
-                        }
-
-                        // Skip if it starts with command tags
-                        if content.starts_with("<command-name>")
-                            || content.starts_with("<local-command-stdout>")
-                        {
-                            continue;
-                        }
-
-                        // Found a valid user message
-                        return (Some(content), entry.timestamp);
-                    }
-                }
-            }
-        }
-    }
-
-    (None, None)
-}
-
 /// Helper function to create a tokio Command with proper environment variables
 /// This ensures commands like Claude can find Node.js and other dependencies
 fn create_command_with_env(program: &str) -> Command {
@@ -294,6 +210,7 @@ fn create_system_command(claude_path: &str, args: Vec<String>, project_path: &st
     cmd.current_dir(project_path)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped());
+    crate::process::isolate_process_group(&mut cmd);
 
     cmd
 }
@@ -306,112 +223,29 @@ pub async fn get_home_directory() -> Result<String, String> {
         .ok_or_else(|| "Could not determine home directory".to_string())
 }
 
-/// Lists all projects in the ~/.claude/projects directory
+/// Lists the projects in the ~/.claude/projects directory
+///
+/// Backed by the session catalog, so only session files that changed since
+/// the last call are read.
 #[tauri::command]
-pub async fn list_projects() -> Result<Vec<Project>, String> {
+pub async fn list_projects(
+    db: tauri::State<'_, AgentDb>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    sort: Option<ProjectSort>,
+) -> Result<Vec<Project>, String> {
     log::info!("Listing projects from ~/.claude/projects");
 
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
-    let projects_dir = claude_dir.join("projects");
-
-    if !projects_dir.exists() {
-        log::warn!("Projects directory does not exist: {:?}", projects_dir);
-        return Ok(Vec::new());
-    }
-
-    let mut projects = Vec::new();
-
-    // Read all directories in the projects folder
-    let entries = fs::read_dir(&projects_dir)
-        .map_err(|e| format!("Failed to read projects directory: {}", e))?;
-
-    for entry in entries {
-        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
-        let path = entry.path();
-
-        if path.is_dir() {
-            let dir_name = path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .ok_or_else(|| "Invalid directory name".to_string())?;
-
-            // Get directory creation time
-            let metadata = fs::metadata(&path)
-                .map_err(|e| format!("Failed to read directory metadata: {}", e))?;
-
-            let created_at = metadata
-                .created()
-                .or_else(|_| metadata.modified())
-                .unwrap_or(SystemTime::UNIX_EPOCH)
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs();
-
-            // Get the actual project path from JSONL files
-            let project_path = match get_project_path_from_sessions(&path) {
-                Ok(path) => path,
-                Err(e) => {
-                    log::warn!("Failed to get project path from sessions for {}: {}, falling back to decode", dir_name, e);
-                    decode_project_path(dir_name)
-                }
-            };
-
-            // List all JSONL files (sessions) in this project directory
-            let mut sessions = Vec::new();
-            let mut most_recent_session: Option<u64> = None;
-
-            if let Ok(session_entries) = fs::read_dir(&path) {
-                for session_entry in session_entries.flatten() {
-                    let session_path = session_entry.path();
-                    if session_path.is_file()
-                        && session_path.extension().and_then(|s| s.to_str()) == Some("jsonl")
-                    {
-                        if let Some(session_id) = session_path.file_stem().and_then(|s| s.to_str())
-                        {
-                            sessions.push(session_id.to_string());
-
-                            // Track the most recent session timestamp
-                            if let Ok(metadata) = fs::metadata(&session_path) {
-                                let modified = metadata
-                                    .modified()
-                                    .unwrap_or(SystemTime::UNIX_EPOCH)
-                                    .duration_since(UNIX_EPOCH)
-                                    .unwrap_or_default()
-                                    .as_secs();
-
-                                most_recent_session = Some(match most_recent_session {
-                                    Some(current) => current.max(modified),
-                                    None => modified,
-                                });
-                            }
-                        }
-                    }
-                }
-            }
-
-            projects.push(Project {
-                id: dir_name.to_string(),
-                path: project_path,
-                sessions,
-                created_at,
-                most_recent_session,
-            });
-        }
-    }
-
-    // Sort projects by most recent session activity, then by creation time
-    projects.sort_by(|a, b| {
-        // First compare by most recent session
-        match (a.most_recent_session, b.most_recent_session) {
-            (Some(a_time), Some(b_time)) => b_time.cmp(&a_time),
-            (Some(_), None) => std::cmp::Ordering::Less,
-            (None, Some(_)) => std::cmp::Ordering::Greater,
-            (None, None) => b.created_at.cmp(&a.created_at),
-        }
-    });
-
-    log::info!("Found {} projects", projects.len());
-    Ok(projects)
+    session_catalog::list_projects(
+        &db,
+        &claude_dir.join("projects"),
+        CatalogQuery {
+            offset,
+            limit,
+            sort,
+        },
+    )
 }
 
 /// Creates a new project for the given directory path
@@ -420,7 +254,7 @@ pub async fn create_project(path: String) -> Result<Project, String> {
     log::info!("Creating project for path: {}", path);
 
     // Encode the path to create a project ID
-    let project_id = path.replace('/', "-");
+    let project_id = crate::commands::agents::encode_project_path(&path);
 
     // Get claude directory
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
@@ -461,91 +295,29 @@ pub async fn create_project(path: String) -> Result<Project, String> {
     })
 }
 
-/// Gets sessions for a specific project
+/// Gets sessions for a specific project, from the session catalog
 #[tauri::command]
-pub async fn get_project_sessions(project_id: String) -> Result<Vec<Session>, String> {
+pub async fn get_project_sessions(
+    db: tauri::State<'_, AgentDb>,
+    project_id: String,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    sort: Option<SessionSort>,
+) -> Result<Vec<Session>, String> {
     log::info!("Getting sessions for project: {}", project_id);
 
     let claude_dir = get_claude_dir().map_err(|e| e.to_string())?;
-    let project_dir = claude_dir.join("projects").join(&project_id);
-    let todos_dir = claude_dir.join("todos");
-
-    if !project_dir.exists() {
-        return Err(format!("Project directory not found: {}", project_id));
-    }
-
-    // Get the actual project path from JSONL files
-    let project_path = match get_project_path_from_sessions(&project_dir) {
-        Ok(path) => path,
-        Err(e) => {
-            log::warn!(
-                "Failed to get project path from sessions for {}: {}, falling back to decode",
-                project_id,
-                e
-            );
-            decode_project_path(&project_id)
-        }
-    };
-
-    let mut sessions = Vec::new();
-
-    // Read all JSONL files in the project directory
-    let entries = fs::read_dir(&project_dir)
-        .map_err(|e| format!("Failed to read project directory: {}", e))?;
-
-    for entry in entries {
-        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
-        let path = entry.path();
-
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("jsonl") {
-            if let Some(session_id) = path.file_stem().and_then(|s| s.to_str()) {
-                // Get file creation time
-                let metadata = fs::metadata(&path)
-                    .map_err(|e| format!("Failed to read file metadata: {}", e))?;
-
-                let created_at = metadata
-                    .created()
-                    .or_else(|_| metadata.modified())
-                    .unwrap_or(SystemTime::UNIX_EPOCH)
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs();
-
-                // Extract first user message and timestamp
-                let (first_message, message_timestamp) = extract_first_user_message(&path);
-
-                // Try to load associated todo data
-                let todo_path = todos_dir.join(format!("{}.json", session_id));
-                let todo_data = if todo_path.exists() {
-                    fs::read_to_string(&todo_path)
-                        .ok()
-                        .and_then(|content| serde_json::from_str(&content).ok())
-                } else {
-                    None
-                };
-
-                sessions.push(Session {
-                    id: session_id.to_string(),
-                    project_id: project_id.clone(),
-                    project_path: project_path.clone(),
-                    todo_data,
-                    created_at,
-                    first_message,
-                    message_timestamp,
-                });
-            }
-        }
-    }
-
-    // Sort sessions by creation time (newest first)
-    sessions.sort_by(|a, b| b.created_at.cmp(&a.created_at));
-
-    log::info!(
-        "Found {} sessions for project {}",
-        sessions.len(),
-        project_id
-    );
-    Ok(sessions)
+    session_catalog::project_sessions(
+        &db,
+        &claude_dir.join("projects"),
+        &claude_dir.join("todos"),
+        &project_id,
+        CatalogQuery {
+            offset,
+            limit,
+            sort,
+        },
+    )
 }
 
 /// Reads the Claude settings file
@@ -908,13 +680,17 @@ pub async fn load_session_history(
 }
 
 /// Execute a new interactive Claude Code session with streaming output
+///
+/// Returns the run ID its events are scoped to until Claude reports the session ID;
+/// see `session_event`.
 #[tauri::command]
 pub async fn execute_claude_code(
     app: AppHandle,
     project_path: String,
     prompt: String,
     model: String,
-) -> Result<(), String> {
+    options: Option<ClaudeSessionOptions>,
+) -> Result<i64, String> {
     log::info!(
         "Starting new Claude Code session in: {} with model: {}",
         project_path,
@@ -923,7 +699,7 @@ pub async fn execute_claude_code(
 
     let claude_path = find_claude_binary(&app)?;
 
-    let args = vec![
+    let mut args = vec![
         "-p".to_string(),
         prompt.clone(),
         "--model".to_string(),
@@ -931,21 +707,22 @@ pub async fn execute_claude_code(
         "--output-format".to_string(),
         "stream-json".to_string(),
         "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
     ];
+    args.extend(session_args(&app, &claude_path, &project_path, options.as_ref()).await?);
 
     let cmd = create_system_command(&claude_path, args, &project_path);
     spawn_claude_process(app, cmd, prompt, model, project_path).await
 }
 
-/// Continue an existing Claude Code conversation with streaming output
+/// Continue an existing Claude Code conversation with streaming output, returning its run ID
 #[tauri::command]
 pub async fn continue_claude_code(
     app: AppHandle,
     project_path: String,
     prompt: String,
     model: String,
-) -> Result<(), String> {
+    options: Option<ClaudeSessionOptions>,
+) -> Result<i64, String> {
     log::info!(
         "Continuing Claude Code conversation in: {} with model: {}",
         project_path,
@@ -954,7 +731,7 @@ pub async fn continue_claude_code(
 
     let claude_path = find_claude_binary(&app)?;
 
-    let args = vec![
+    let mut args = vec![
         "-c".to_string(), // Continue flag
         "-p".to_string(),
         prompt.clone(),
@@ -963,14 +740,14 @@ pub async fn continue_claude_code(
         "--output-format".to_string(),
         "stream-json".to_string(),
         "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
     ];
+    args.extend(session_args(&app, &claude_path, &project_path, options.as_ref()).await?);
 
     let cmd = create_system_command(&claude_path, args, &project_path);
     spawn_claude_process(app, cmd, prompt, model, project_path).await
 }
 
-/// Resume an existing Claude Code session by ID with streaming output
+/// Resume an existing Claude Code session by ID with streaming output, returning its run ID
 #[tauri::command]
 pub async fn resume_claude_code(
     app: AppHandle,
@@ -978,7 +755,8 @@ pub async fn resume_claude_code(
     session_id: String,
     prompt: String,
     model: String,
-) -> Result<(), String> {
+    options: Option<ClaudeSessionOptions>,
+) -> Result<i64, String> {
     log::info!(
         "Resuming Claude Code session: {} in: {} with model: {}",
         session_id,
@@ -988,7 +766,7 @@ pub async fn resume_claude_code(
 
     let claude_path = find_claude_binary(&app)?;
 
-    let args = vec![
+    let mut args = vec![
         "--resume".to_string(),
         session_id.clone(),
         "-p".to_string(),
@@ -998,145 +776,142 @@ pub async fn resume_claude_code(
         "--output-format".to_string(),
         "stream-json".to_string(),
         "--verbose".to_string(),
-        "--dangerously-skip-permissions".to_string(),
     ];
+    args.extend(session_args(&app, &claude_path, &project_path, options.as_ref()).await?);
 
     let cmd = create_system_command(&claude_path, args, &project_path);
     spawn_claude_process(app, cmd, prompt, model, project_path).await
 }
 
-/// Cancel the currently running Claude Code execution
+/// Claude CLI arguments granting permissions according to the permission settings
+async fn permission_args(app: &AppHandle, project_path: &str) -> Result<Vec<String>, String> {
+    match app.try_state::<crate::commands::permissions::PermissionBrokerState>() {
+        Some(broker) => broker.0.cli_args(project_path).await,
+        None => Ok(crate::commands::permissions::skip_permissions_args()),
+    }
+}
+
+/// End the permission bridge session a Claude process was started with, if any
+fn end_bridge_session(app: &AppHandle, session: Option<&str>) {
+    let Some(session) = session else {
+        return;
+    };
+    if let Some(broker) = app.try_state::<crate::commands::permissions::PermissionBrokerState>() {
+        broker.0.end_session(session);
+    }
+}
+
+/// Claude CLI arguments for permissions and the session `options`
+///
+/// An explicit `permission_mode` replaces `--dangerously-skip-permissions`.
+async fn session_args(
+    app: &AppHandle,
+    claude_path: &str,
+    project_path: &str,
+    options: Option<&ClaudeSessionOptions>,
+) -> Result<Vec<String>, String> {
+    let option_args = session_option_args(claude_path, options)?;
+    let mut args = permission_args(app, project_path).await?;
+    if option_args.iter().any(|arg| arg == "--permission-mode") {
+        args.retain(|arg| arg != "--dangerously-skip-permissions");
+    }
+    args.extend(option_args);
+    Ok(args)
+}
+
+/// A Claude process stopped by `cancel_claude_execution`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CancelledProcess {
+    pub run_id: i64,
+    /// The signal that ended it; `interrupt` means it could flush its result
+    pub stage: crate::process::TerminationStage,
+}
+
+/// Cancel a running Claude Code execution
+///
+/// Only the session with `session_id`, or the process with `run_id`, is stopped;
+/// other sessions keep running. Without either, sessions that have not reported
+/// their session ID yet are stopped. Each process is interrupted first so it can
+/// write its final result, and killed with its process group if it does not exit
+/// within the configured grace periods.
 #[tauri::command]
 pub async fn cancel_claude_execution(
     app: AppHandle,
     session_id: Option<String>,
-) -> Result<(), String> {
+    run_id: Option<i64>,
+) -> Result<Vec<CancelledProcess>, String> {
     log::info!(
-        "Cancelling Claude Code execution for session: {:?}",
-        session_id
+        "Cancelling Claude Code execution for session: {:?}, run: {:?}",
+        session_id,
+        run_id
     );
 
-    let mut killed = false;
-    let mut attempted_methods = Vec::new();
-
-    // Method 1: Try to find and kill via ProcessRegistry using session ID
-    if let Some(sid) = &session_id {
-        let registry = app.state::<crate::process::ProcessRegistryState>();
-        match registry.0.get_claude_session_by_id(sid) {
-            Ok(Some(process_info)) => {
-                log::info!(
-                    "Found process in registry for session {}: run_id={}, PID={}",
-                    sid,
-                    process_info.run_id,
-                    process_info.pid
-                );
-                match registry.0.kill_process(process_info.run_id).await {
-                    Ok(success) => {
-                        if success {
-                            log::info!("Successfully killed process via registry");
-                            killed = true;
-                        } else {
-                            log::warn!("Registry kill returned false");
-                        }
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to kill via registry: {}", e);
-                    }
-                }
-                attempted_methods.push("registry");
-            }
-            Ok(None) => {
-                log::warn!("Session {} not found in ProcessRegistry", sid);
-            }
-            Err(e) => {
-                log::error!("Error querying ProcessRegistry: {}", e);
-            }
-        }
-    }
-
-    // Method 2: Try the legacy approach via ClaudeProcessState
-    if !killed {
-        let claude_state = app.state::<ClaudeProcessState>();
-        let mut current_process = claude_state.current_process.lock().await;
-
-        if let Some(mut child) = current_process.take() {
-            // Try to get the PID before killing
-            let pid = child.id();
-            log::info!(
-                "Attempting to kill Claude process via ClaudeProcessState with PID: {:?}",
-                pid
-            );
+    let registry = app.state::<crate::process::ProcessRegistryState>();
+    let sessions = registry.0.get_running_claude_sessions()?;
+    let session_of = |info: &crate::process::ProcessInfo| match &info.process_type {
+        crate::process::ProcessType::ClaudeSession { session_id } => session_id.clone(),
+        _ => String::new(),
+    };
 
-            // Kill the process
-            match child.kill().await {
-                Ok(_) => {
-                    log::info!("Successfully killed Claude process via ClaudeProcessState");
-                    killed = true;
-                }
-                Err(e) => {
-                    log::error!(
-                        "Failed to kill Claude process via ClaudeProcessState: {}",
-                        e
-                    );
-
-                    // Method 3: If we have a PID, try system kill as last resort
-                    if let Some(pid) = pid {
-                        log::info!("Attempting system kill as last resort for PID: {}", pid);
-                        let kill_result = if cfg!(target_os = "windows") {
-                            std::process::Command::new("taskkill")
-                                .args(["/F", "/PID", &pid.to_string()])
-                                .output()
-                        } else {
-                            std::process::Command::new("kill")
-                                .args(["-KILL", &pid.to_string()])
-                                .output()
-                        };
-
-                        match kill_result {
-                            Ok(output) if output.status.success() => {
-                                log::info!("Successfully killed process via system command");
-                                killed = true;
-                            }
-                            Ok(output) => {
-                                let stderr = String::from_utf8_lossy(&output.stderr);
-                                log::error!("System kill failed: {}", stderr);
-                            }
-                            Err(e) => {
-                                log::error!("Failed to execute system kill command: {}", e);
-                            }
-                        }
-                    }
-                }
+    let targets: Vec<(i64, String)> = sessions
+        .iter()
+        .filter(|info| match (&session_id, run_id) {
+            (_, Some(run_id)) => info.run_id == run_id,
+            (Some(sid), None) => &session_of(info) == sid,
+            (None, None) => session_of(info).is_empty(),
+        })
+        .map(|info| (info.run_id, session_of(info)))
+        .collect();
+
+    if targets.is_empty() {
+        log::warn!("No active Claude process found to cancel");
+    }
+    let mut cancelled = Vec::new();
+    for (run_id, _) in &targets {
+        // Subscribers of the output learn that the run ended unsuccessfully
+        let _ = registry.0.finish_output(*run_id, false);
+        match registry.0.terminate_process(*run_id).await {
+            Ok(Some(stage)) => {
+                log::info!("Stopped Claude process {} ({:?})", run_id, stage);
+                cancelled.push(CancelledProcess {
+                    run_id: *run_id,
+                    stage,
+                });
             }
-            attempted_methods.push("claude_state");
-        } else {
-            log::warn!("No active Claude process in ClaudeProcessState");
+            Ok(None) => log::warn!("Claude process {} had already exited", run_id),
+            Err(e) => log::error!("Failed to kill Claude process {}: {}", run_id, e),
         }
     }
 
-    if !killed && attempted_methods.is_empty() {
-        log::warn!("No active Claude process found to cancel");
+    // Always emit cancellation events for UI consistency, also when the process just exited
+    let mut scopes: Vec<(i64, Option<String>)> = targets
+        .into_iter()
+        .map(|(run_id, sid)| (run_id, Some(sid).filter(|sid| !sid.is_empty())))
+        .collect();
+    if scopes.is_empty() {
+        if let Some(run_id) = run_id {
+            scopes.push((run_id, session_id));
+        } else if session_id.is_some() {
+            scopes.push((0, session_id));
+        }
     }
-
-    // Always emit cancellation events for UI consistency
-    if let Some(sid) = session_id {
-        let _ = app.emit(&format!("claude-cancelled:{}", sid), true);
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-        let _ = app.emit(&format!("claude-complete:{}", sid), false);
+    for (run_id, sid) in &scopes {
+        let _ = app.emit(&session_event("cancelled", *run_id, sid.as_deref()), true);
     }
-
-    // Also emit generic events for backward compatibility
-    let _ = app.emit("claude-cancelled", true);
     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-    let _ = app.emit("claude-complete", false);
-
-    if killed {
-        log::info!("Claude process cancellation completed successfully");
-    } else if !attempted_methods.is_empty() {
-        log::warn!("Claude process cancellation attempted but process may have already exited. Attempted methods: {:?}", attempted_methods);
+    for (run_id, sid) in &scopes {
+        let _ = app.emit(&session_event("complete", *run_id, sid.as_deref()), false);
     }
 
-    Ok(())
+    let registry = registry.0.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(FINISHED_OUTPUT_RETENTION).await;
+        for (run_id, _) in scopes {
+            let _ = registry.forget_output(run_id);
+        }
+    });
+
+    Ok(cancelled)
 }
 
 /// Get all running Claude sessions
@@ -1161,25 +936,114 @@ pub async fn get_claude_session_output(
     }
 }
 
+/// Replay the output of a running Claude session from `from_seq`, then follow it
+///
+/// Lines are sent on `on_output` with their sequence number, so a webview that
+/// reloads mid-run can pass the last sequence number it saw and pick up where it
+/// left off. Lines older than the session's output buffer are skipped. The
+/// subscription ends when the session exits, with an empty line whose `success`
+/// is the exit status. Returns the sequence number of the oldest line still
+/// buffered.
+#[tauri::command]
+pub async fn subscribe_claude_session_output(
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    session_id: String,
+    from_seq: Option<u64>,
+    on_output: tauri::ipc::Channel<crate::process::OutputLine>,
+) -> Result<u64, String> {
+    let process_info = registry
+        .0
+        .get_claude_session_by_id(&session_id)?
+        .ok_or_else(|| format!("No running session {}", session_id))?;
+    follow_claude_output(&registry.0, process_info.run_id, from_seq, on_output)
+}
+
+/// Like `subscribe_claude_session_output`, for the process with a run ID returned
+/// by `execute_claude_code` and friends
+///
+/// Replaying from the start picks up the lines written before the caller knew
+/// the run ID, including the init message with the session ID. The output stays
+/// available for a while after the process exits, so a run that finishes before
+/// the caller subscribes still reports its output and exit status.
+#[tauri::command]
+pub async fn subscribe_claude_run_output(
+    registry: tauri::State<'_, crate::process::ProcessRegistryState>,
+    run_id: i64,
+    from_seq: Option<u64>,
+    on_output: tauri::ipc::Channel<crate::process::OutputLine>,
+) -> Result<u64, String> {
+    follow_claude_output(&registry.0, run_id, from_seq, on_output)
+}
+
+/// Send the output of a Claude process on `on_output` from `from_seq` on
+fn follow_claude_output(
+    registry: &crate::process::ProcessRegistry,
+    run_id: i64,
+    from_seq: Option<u64>,
+    on_output: tauri::ipc::Channel<crate::process::OutputLine>,
+) -> Result<u64, String> {
+    let buffer = registry
+        .get_output_buffer(run_id)?
+        .ok_or_else(|| format!("No running Claude process {}", run_id))?;
+    let first_seq = buffer.lock().map_err(|e| e.to_string())?.first_seq();
+
+    tokio::spawn(crate::process::follow_output(
+        buffer,
+        from_seq.unwrap_or(0),
+        move |line| on_output.send(line).is_ok(),
+    ));
+    Ok(first_seq)
+}
+
+/// How long the output of an exited Claude process stays available to
+/// subscribers that attach after it exited
+const FINISHED_OUTPUT_RETENTION: tokio::time::Duration = tokio::time::Duration::from_secs(60);
+
+/// Name of a session event such as `output` or `complete`
+///
+/// Events are scoped to the session once Claude has reported its ID
+/// (`claude-output:<session>`), and to the run until then
+/// (`claude-run-output:<run>`), so every session's lifecycle is independent.
+fn session_event(event: &str, run_id: i64, session_id: Option<&str>) -> String {
+    match session_id {
+        Some(session_id) => format!("claude-{}:{}", event, session_id),
+        None => format!("claude-run-{}:{}", event, run_id),
+    }
+}
+
 /// Helper function to spawn Claude process and handle streaming
+///
+/// Every session is tracked separately in the `ProcessRegistry`, so several
+/// sessions can run at the same time. When `cmd` pipes stdin, it is kept open
+/// in the registry for `send_session_message`. Returns the registry's run ID.
 async fn spawn_claude_process(
     app: AppHandle,
     mut cmd: Command,
     prompt: String,
     model: String,
     project_path: String,
-) -> Result<(), String> {
+) -> Result<i64, String> {
     use std::sync::Mutex;
     use tokio::io::{AsyncBufReadExt, BufReader};
 
-    // Spawn the process
+    let args: Vec<String> = cmd
+        .as_std()
+        .get_args()
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect();
+    let bridge_session = crate::commands::permissions::bridge_session(&args);
+
+    // Spawn the process; until it is registered, failures end its bridge session
     let mut child = cmd
         .spawn()
-        .map_err(|e| format!("Failed to spawn Claude: {}", e))?;
+        .map_err(|e| format!("Failed to spawn Claude: {}", e))
+        .inspect_err(|_| end_bridge_session(&app, bridge_session.as_deref()))?;
 
     // Get stdout and stderr
     let stdout = child.stdout.take().ok_or("Failed to get stdout")?;
     let stderr = child.stderr.take().ok_or("Failed to get stderr")?;
+    let stdin = child.stdin.take();
+    let streaming_input = stdin.is_some();
 
     // Get the child PID for logging
     let pid = child.id().unwrap_or(0);
@@ -1189,78 +1053,70 @@ async fn spawn_claude_process(
     let stdout_reader = BufReader::new(stdout);
     let stderr_reader = BufReader::new(stderr);
 
-    // We'll extract the session ID from Claude's init message
-    let session_id_holder: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
-    let run_id_holder: Arc<Mutex<Option<i64>>> = Arc::new(Mutex::new(None));
-
-    // Store the child process in the global state (for backward compatibility)
-    let claude_state = app.state::<ClaudeProcessState>();
-    {
-        let mut current_process = claude_state.current_process.lock().await;
-        // If there's already a process running, kill it first
-        if let Some(mut existing_child) = current_process.take() {
-            log::warn!("Killing existing Claude process before starting new one");
-            let _ = existing_child.kill().await;
-        }
-        *current_process = Some(child);
+    // Track the process in the registry; its session ID follows from Claude's init message
+    let registry = app
+        .state::<crate::process::ProcessRegistryState>()
+        .0
+        .clone();
+    let run_id = registry
+        .register_claude_session(pid, project_path, prompt, model, child)
+        .inspect_err(|_| end_bridge_session(&app, bridge_session.as_deref()))?;
+    log::info!("Registered Claude process with run_id: {}", run_id);
+    if let Some(stdin) = stdin {
+        registry
+            .set_stdin(run_id, stdin)
+            .await
+            .inspect_err(|_| end_bridge_session(&app, bridge_session.as_deref()))?;
     }
+    let session_id_holder: Arc<Mutex<Option<String>>> = Arc::new(Mutex::new(None));
 
     // Spawn tasks to read stdout and stderr
     let app_handle = app.clone();
     let session_id_holder_clone = session_id_holder.clone();
-    let run_id_holder_clone = run_id_holder.clone();
-    let registry = app.state::<crate::process::ProcessRegistryState>();
-    let registry_clone = registry.0.clone();
-    let project_path_clone = project_path.clone();
-    let prompt_clone = prompt.clone();
-    let model_clone = model.clone();
+    let registry_clone = registry.clone();
     let stdout_task = tokio::spawn(async move {
         let mut lines = stdout_reader.lines();
         while let Ok(Some(line)) = lines.next_line().await {
             log::debug!("Claude stdout: {}", line);
+            // The init message still goes to the run's listeners, which learn the session from it
+            let output_event = session_event(
+                "output",
+                run_id,
+                session_id_holder_clone.lock().unwrap().as_deref(),
+            );
 
-            // Parse the line to check for init message with session ID
-            if let Ok(msg) = serde_json::from_str::<serde_json::Value>(&line) {
-                if msg["type"] == "system" && msg["subtype"] == "init" {
-                    if let Some(claude_session_id) = msg["session_id"].as_str() {
-                        let mut session_id_guard = session_id_holder_clone.lock().unwrap();
-                        if session_id_guard.is_none() {
-                            *session_id_guard = Some(claude_session_id.to_string());
-                            log::info!("Extracted Claude session ID: {}", claude_session_id);
-
-                            // Now register with ProcessRegistry using Claude's session ID
-                            match registry_clone.register_claude_session(
-                                claude_session_id.to_string(),
-                                pid,
-                                project_path_clone.clone(),
-                                prompt_clone.clone(),
-                                model_clone.clone(),
-                            ) {
-                                Ok(run_id) => {
-                                    log::info!("Registered Claude session with run_id: {}", run_id);
-                                    let mut run_id_guard = run_id_holder_clone.lock().unwrap();
-                                    *run_id_guard = Some(run_id);
-                                }
-                                Err(e) => {
-                                    log::error!("Failed to register Claude session: {}", e);
-                                }
-                            }
-                        }
+            // Check for the init message with the session ID
+            let message = StreamLine::parse(&line);
+            if let Some(claude_session_id) = message.as_ref().and_then(|m| m.init_session_id()) {
+                let mut session_id_guard = session_id_holder_clone.lock().unwrap();
+                if session_id_guard.is_none() {
+                    *session_id_guard = Some(claude_session_id.to_string());
+                    log::info!("Extracted Claude session ID: {}", claude_session_id);
+                    if let Err(e) = registry_clone.set_claude_session_id(run_id, claude_session_id)
+                    {
+                        log::error!("Failed to record Claude session ID: {}", e);
                     }
                 }
             }
 
-            // Store live output in registry if we have a run_id
-            if let Some(run_id) = *run_id_holder_clone.lock().unwrap() {
-                let _ = registry_clone.append_live_output(run_id, &line);
+            // Store live output in registry
+            let _ = registry_clone.append_live_output(run_id, &line);
+
+            // Long-lived sessions stay alive between turns, so report the end of each turn
+            let turn_result = message
+                .as_ref()
+                .and_then(|m| m.result())
+                .filter(|_| streaming_input);
+            if let Some(result) = turn_result {
+                let event = session_event(
+                    "turn-complete",
+                    run_id,
+                    session_id_holder_clone.lock().unwrap().as_deref(),
+                );
+                let _ = app_handle.emit(&event, !result.is_error);
             }
 
-            // Emit the line to the frontend with session isolation if we have session ID
-            if let Some(ref session_id) = *session_id_holder_clone.lock().unwrap() {
-                let _ = app_handle.emit(&format!("claude-output:{}", session_id), &line);
-            }
-            // Also emit to the generic event for backward compatibility
-            let _ = app_handle.emit("claude-output", &line);
+            let _ = app_handle.emit(&output_event, &line);
         }
     });
 
@@ -1270,63 +1126,205 @@ async fn spawn_claude_process(
         let mut lines = stderr_reader.lines();
         while let Ok(Some(line)) = lines.next_line().await {
             log::error!("Claude stderr: {}", line);
-            // Emit error lines to the frontend with session isolation if we have session ID
-            if let Some(ref session_id) = *session_id_holder_clone2.lock().unwrap() {
-                let _ = app_handle_stderr.emit(&format!("claude-error:{}", session_id), &line);
-            }
-            // Also emit to the generic event for backward compatibility
-            let _ = app_handle_stderr.emit("claude-error", &line);
+            let event = session_event(
+                "error",
+                run_id,
+                session_id_holder_clone2.lock().unwrap().as_deref(),
+            );
+            let _ = app_handle_stderr.emit(&event, &line);
         }
     });
 
     // Wait for the process to complete
     let app_handle_wait = app.clone();
-    let claude_state_wait = claude_state.current_process.clone();
     let session_id_holder_clone3 = session_id_holder.clone();
-    let run_id_holder_clone2 = run_id_holder.clone();
-    let registry_clone2 = registry.0.clone();
     tokio::spawn(async move {
         let _ = stdout_task.await;
         let _ = stderr_task.await;
 
-        // Get the child from the state to wait on it
-        let mut current_process = claude_state_wait.lock().await;
-        if let Some(mut child) = current_process.take() {
-            match child.wait().await {
-                Ok(status) => {
-                    log::info!("Claude process exited with status: {}", status);
-                    // Add a small delay to ensure all messages are processed
-                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                    if let Some(ref session_id) = *session_id_holder_clone3.lock().unwrap() {
-                        let _ = app_handle_wait
-                            .emit(&format!("claude-complete:{}", session_id), status.success());
-                    }
-                    // Also emit to the generic event for backward compatibility
-                    let _ = app_handle_wait.emit("claude-complete", status.success());
-                }
-                Err(e) => {
-                    log::error!("Failed to wait for Claude process: {}", e);
-                    // Add a small delay to ensure all messages are processed
-                    tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                    if let Some(ref session_id) = *session_id_holder_clone3.lock().unwrap() {
-                        let _ =
-                            app_handle_wait.emit(&format!("claude-complete:{}", session_id), false);
-                    }
-                    // Also emit to the generic event for backward compatibility
-                    let _ = app_handle_wait.emit("claude-complete", false);
-                }
+        let exit_status = registry.wait_for_exit(run_id).await;
+        end_bridge_session(&app_handle_wait, bridge_session.as_deref());
+
+        let success = match exit_status {
+            Ok(Some(status)) => {
+                log::info!("Claude process exited with status: {}", status);
+                status.success()
             }
-        }
+            Ok(None) if !matches!(registry.get_process(run_id), Ok(Some(_))) => {
+                // Killed by `cancel_claude_execution`, which emits the completion events
+                log::info!("Claude process {} was cancelled", run_id);
+                return;
+            }
+            // Reaped elsewhere, e.g. by `cleanup_finished_processes`
+            Ok(None) => false,
+            Err(e) => {
+                log::error!("Failed to wait for Claude process: {}", e);
+                false
+            }
+        };
 
-        // Unregister from ProcessRegistry if we have a run_id
-        if let Some(run_id) = *run_id_holder_clone2.lock().unwrap() {
-            let _ = registry_clone2.unregister_process(run_id);
-        }
+        // Subscribers of the output receive the exit status as its last line
+        let _ = registry.finish_output(run_id, success);
+
+        // Add a small delay to ensure all messages are processed
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        let event = session_event(
+            "complete",
+            run_id,
+            session_id_holder_clone3.lock().unwrap().as_deref(),
+        );
+        let _ = app_handle_wait.emit(&event, success);
 
-        // Clear the process from state
-        *current_process = None;
+        let _ = registry.unregister_process(run_id);
+        tokio::time::sleep(FINISHED_OUTPUT_RETENTION).await;
+        let _ = registry.forget_output(run_id);
     });
 
+    Ok(run_id)
+}
+
+/// A stream-json user message carrying `prompt`
+fn session_user_message(prompt: &str) -> serde_json::Value {
+    serde_json::json!({
+        "type": "user",
+        "message": {
+            "role": "user",
+            "content": [{ "type": "text", "text": prompt }],
+        },
+    })
+}
+
+/// A stream-json control request interrupting the current turn
+fn session_interrupt_request() -> serde_json::Value {
+    serde_json::json!({
+        "type": "control_request",
+        "request_id": uuid::Uuid::new_v4().to_string(),
+        "request": { "subtype": "interrupt" },
+    })
+}
+
+/// Write a stream-json message to the stdin of a long-lived session
+async fn write_session_input(
+    registry: &crate::process::ProcessRegistry,
+    run_id: i64,
+    message: &serde_json::Value,
+) -> Result<(), String> {
+    use tokio::io::AsyncWriteExt;
+
+    let stdin = registry
+        .get_stdin(run_id)?
+        .ok_or("Claude session is not running")?;
+    let mut stdin = stdin.lock().await;
+    let stdin = stdin
+        .as_mut()
+        .ok_or("This session does not accept messages; start it with start_claude_session")?;
+
+    let mut line = serde_json::to_string(message).map_err(|e| e.to_string())?;
+    line.push('\n');
+    stdin
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|e| format!("Failed to write to Claude session: {}", e))?;
+    stdin
+        .flush()
+        .await
+        .map_err(|e| format!("Failed to write to Claude session: {}", e))
+}
+
+/// Run ID of the running session with `session_id`
+fn session_run_id(app: &AppHandle, session_id: &str) -> Result<i64, String> {
+    let registry = app.state::<crate::process::ProcessRegistryState>();
+    registry
+        .0
+        .get_claude_session_by_id(session_id)?
+        .map(|info| info.run_id)
+        .ok_or_else(|| format!("Claude session {} is not running", session_id))
+}
+
+/// Start a long-lived Claude Code session that reads prompts from stdin
+///
+/// Unlike `execute_claude_code`, the process keeps running between turns:
+/// follow-ups are sent with `send_session_message` and the current turn can be
+/// stopped with `interrupt_session`. Output streams on `claude-output:<session>`,
+/// the end of each turn is reported on `claude-turn-complete:<session>` and
+/// `claude-complete:<session>` follows once the session is closed. Pass
+/// `resume_session_id` to continue an existing session. Returns the run ID.
+#[tauri::command]
+pub async fn start_claude_session(
+    app: AppHandle,
+    project_path: String,
+    prompt: String,
+    model: String,
+    resume_session_id: Option<String>,
+    options: Option<ClaudeSessionOptions>,
+) -> Result<i64, String> {
+    log::info!(
+        "Starting long-lived Claude Code session in: {} with model: {}",
+        project_path,
+        model
+    );
+
+    let claude_path = find_claude_binary(&app)?;
+
+    let mut args = vec![
+        "-p".to_string(),
+        "--input-format".to_string(),
+        "stream-json".to_string(),
+        "--output-format".to_string(),
+        "stream-json".to_string(),
+        "--model".to_string(),
+        model.clone(),
+        "--verbose".to_string(),
+    ];
+    args.extend(session_args(&app, &claude_path, &project_path, options.as_ref()).await?);
+    if let Some(session_id) = resume_session_id {
+        args.push("--resume".to_string());
+        args.push(session_id);
+    }
+
+    let mut cmd = create_system_command(&claude_path, args, &project_path);
+    cmd.stdin(Stdio::piped());
+    let run_id =
+        spawn_claude_process(app.clone(), cmd, prompt.clone(), model, project_path).await?;
+
+    let registry = app.state::<crate::process::ProcessRegistryState>();
+    write_session_input(&registry.0, run_id, &session_user_message(&prompt)).await?;
+
+    Ok(run_id)
+}
+
+/// Send a follow-up prompt to a session started with `start_claude_session`
+#[tauri::command]
+pub async fn send_session_message(
+    app: AppHandle,
+    session_id: String,
+    prompt: String,
+) -> Result<(), String> {
+    let run_id = session_run_id(&app, &session_id)?;
+    let registry = app.state::<crate::process::ProcessRegistryState>();
+    write_session_input(&registry.0, run_id, &session_user_message(&prompt)).await
+}
+
+/// Interrupt the current turn of a session started with `start_claude_session`;
+/// the session stays open for further messages
+#[tauri::command]
+pub async fn interrupt_session(app: AppHandle, session_id: String) -> Result<(), String> {
+    log::info!("Interrupting Claude session: {}", session_id);
+    let run_id = session_run_id(&app, &session_id)?;
+    let registry = app.state::<crate::process::ProcessRegistryState>();
+    write_session_input(&registry.0, run_id, &session_interrupt_request()).await
+}
+
+/// Close the input of a session started with `start_claude_session`, letting
+/// Claude finish the current turn and exit
+#[tauri::command]
+pub async fn close_claude_session(app: AppHandle, session_id: String) -> Result<(), String> {
+    log::info!("Closing Claude session: {}", session_id);
+    let run_id = session_run_id(&app, &session_id)?;
+    let registry = app.state::<crate::process::ProcessRegistryState>();
+    if let Some(stdin) = registry.0.get_stdin(run_id)? {
+        stdin.lock().await.take();
+    }
     Ok(())
 }
 
@@ -2180,152 +2178,16 @@ pub async fn validate_hook_command(command: String) -> Result<serde_json::Value,
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Write;
-    use tempfile::TempDir;
-
-    /// Helper function to create a test session file
-    fn create_test_session_file(
-        dir: &PathBuf,
-        filename: &str,
-        content: &str,
-    ) -> Result<(), std::io::Error> {
-        let file_path = dir.join(filename);
-        let mut file = fs::File::create(file_path)?;
-        file.write_all(content.as_bytes())?;
-        Ok(())
-    }
 
     #[test]
-    fn test_get_project_path_from_sessions_normal_case() {
-        let temp_dir = TempDir::new().unwrap();
-        let project_dir = temp_dir.path().to_path_buf();
-
-        // Create a session file with cwd on the first line
-        let content = r#"{"type":"system","cwd":"/Users/test/my-project"}"#;
-        create_test_session_file(&project_dir, "session1.jsonl", content).unwrap();
-
-        let result = get_project_path_from_sessions(&project_dir);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "/Users/test/my-project");
-    }
-
-    #[test]
-    fn test_get_project_path_from_sessions_with_hyphen() {
-        let temp_dir = TempDir::new().unwrap();
-        let project_dir = temp_dir.path().to_path_buf();
-
-        // This is the bug scenario - project path contains hyphens
-        let content = r#"{"type":"system","cwd":"/Users/test/data-discovery"}"#;
-        create_test_session_file(&project_dir, "session1.jsonl", content).unwrap();
-
-        let result = get_project_path_from_sessions(&project_dir);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "/Users/test/data-discovery");
-    }
-
-    #[test]
-    fn test_get_project_path_from_sessions_null_cwd_first_line() {
-        let temp_dir = TempDir::new().unwrap();
-        let project_dir = temp_dir.path().to_path_buf();
-
-        // First line has null cwd, second line has valid path
-        let content = format!(
-            "{}\n{}",
-            r#"{"type":"system","cwd":null}"#,
-            r#"{"type":"system","cwd":"/Users/test/valid-path"}"#
-        );
-        create_test_session_file(&project_dir, "session1.jsonl", &content).unwrap();
-
-        let result = get_project_path_from_sessions(&project_dir);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "/Users/test/valid-path");
-    }
-
-    #[test]
-    fn test_get_project_path_from_sessions_multiple_lines() {
-        let temp_dir = TempDir::new().unwrap();
-        let project_dir = temp_dir.path().to_path_buf();
-
-        // Multiple lines with cwd appearing on line 5
-        let content = format!(
-            "{}\n{}\n{}\n{}\n{}",
-            r#"{"type":"other"}"#,
-            r#"{"type":"system","cwd":null}"#,
-            r#"{"type":"message"}"#,
-            r#"{"type":"system"}"#,
-            r#"{"type":"system","cwd":"/Users/test/project"}"#
-        );
-        create_test_session_file(&project_dir, "session1.jsonl", &content).unwrap();
-
-        let result = get_project_path_from_sessions(&project_dir);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "/Users/test/project");
-    }
-
-    #[test]
-    fn test_get_project_path_from_sessions_empty_dir() {
-        let temp_dir = TempDir::new().unwrap();
-        let project_dir = temp_dir.path().to_path_buf();
-
-        let result = get_project_path_from_sessions(&project_dir);
-        assert!(result.is_err());
-        assert_eq!(
-            result.unwrap_err(),
-            "Could not determine project path from session files"
-        );
-    }
-
-    #[test]
-    fn test_get_project_path_from_sessions_no_jsonl_files() {
-        let temp_dir = TempDir::new().unwrap();
-        let project_dir = temp_dir.path().to_path_buf();
-
-        // Create a non-JSONL file
-        create_test_session_file(&project_dir, "readme.txt", "Some text").unwrap();
-
-        let result = get_project_path_from_sessions(&project_dir);
-        assert!(result.is_err());
-    }
-
-    #[test]
-    fn test_get_project_path_from_sessions_no_cwd() {
-        let temp_dir = TempDir::new().unwrap();
-        let project_dir = temp_dir.path().to_path_buf();
-
-        // JSONL file without any cwd field
-        let content = format!(
-            "{}\n{}\n{}",
-            r#"{"type":"system"}"#, r#"{"type":"message"}"#, r#"{"type":"other"}"#
-        );
-        create_test_session_file(&project_dir, "session1.jsonl", &content).unwrap();
-
-        let result = get_project_path_from_sessions(&project_dir);
-        assert!(result.is_err());
-    }
-
-    #[test]
-    fn test_get_project_path_from_sessions_multiple_sessions() {
-        let temp_dir = TempDir::new().unwrap();
-        let project_dir = temp_dir.path().to_path_buf();
-
-        // Create multiple session files - should return from first valid one
-        create_test_session_file(
-            &project_dir,
-            "session1.jsonl",
-            r#"{"type":"system","cwd":"/path1"}"#,
-        )
-        .unwrap();
-        create_test_session_file(
-            &project_dir,
-            "session2.jsonl",
-            r#"{"type":"system","cwd":"/path2"}"#,
-        )
-        .unwrap();
-
-        let result = get_project_path_from_sessions(&project_dir);
-        assert!(result.is_ok());
-        // Should get one of the paths (implementation checks first file it finds)
-        let path = result.unwrap();
-        assert!(path == "/path1" || path == "/path2");
+    fn test_session_input_messages() {
+        let message = session_user_message("Hello");
+        assert_eq!(message["type"], "user");
+        assert_eq!(message["message"]["content"][0]["text"], "Hello");
+
+        let interrupt = session_interrupt_request();
+        assert_eq!(interrupt["type"], "control_request");
+        assert_eq!(interrupt["request"]["subtype"], "interrupt");
+        assert!(interrupt["request_id"].is_string());
     }
 }
@@ -0,0 +1,193 @@
+#![allow(dead_code)]
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use crate::claude_binary::{compare_versions, get_claude_version};
+
+/// Values accepted by `--permission-mode`
+const PERMISSION_MODES: &[&str] = &["default", "acceptEdits", "bypassPermissions", "plan"];
+
+/// First Claude CLI release supporting each option
+const MIN_VERSIONS: &[(&str, &str)] = &[
+    ("--append-system-prompt", "1.0.0"),
+    ("--max-turns", "1.0.0"),
+    ("--allowedTools", "1.0.0"),
+    ("--disallowedTools", "1.0.0"),
+    ("--mcp-config", "1.0.0"),
+    ("--add-dir", "1.0.18"),
+    ("--permission-mode", "1.0.18"),
+    ("--fallback-model", "1.0.28"),
+];
+
+/// Claude CLI options for a session, on top of the prompt and model
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ClaudeSessionOptions {
+    pub append_system_prompt: Option<String>,
+    pub max_turns: Option<u32>,
+    pub allowed_tools: Vec<String>,
+    pub disallowed_tools: Vec<String>,
+    /// Directories the session may access besides the project (`--add-dir`)
+    pub add_dirs: Vec<String>,
+    /// MCP server config files, or inline JSON configs
+    pub mcp_configs: Vec<String>,
+    /// One of `default`, `acceptEdits`, `bypassPermissions` or `plan`
+    pub permission_mode: Option<String>,
+    /// Model to switch to when the main model is overloaded
+    pub fallback_model: Option<String>,
+}
+
+impl ClaudeSessionOptions {
+    /// Validate the options and convert them to CLI arguments
+    ///
+    /// With a known CLI `version`, options the CLI doesn't support yet are rejected
+    /// instead of failing the process at startup.
+    pub fn cli_args(&self, version: Option<&str>) -> Result<Vec<String>, String> {
+        let mut args: Vec<String> = Vec::new();
+        let mut push = |flag: &str, value: String| {
+            args.push(flag.to_string());
+            args.push(value);
+        };
+
+        if let Some(prompt) = &self.append_system_prompt {
+            push("--append-system-prompt", prompt.clone());
+        }
+        if let Some(max_turns) = self.max_turns {
+            if max_turns == 0 {
+                return Err("max_turns must be at least 1".to_string());
+            }
+            push("--max-turns", max_turns.to_string());
+        }
+        for (flag, tools) in [
+            ("--allowedTools", &self.allowed_tools),
+            ("--disallowedTools", &self.disallowed_tools),
+        ] {
+            if tools.iter().any(|tool| tool.trim().is_empty()) {
+                return Err(format!("{} contains an empty tool name", flag));
+            }
+            if !tools.is_empty() {
+                push(flag, tools.join(","));
+            }
+        }
+        for dir in &self.add_dirs {
+            if !Path::new(dir).is_dir() {
+                return Err(format!("Additional directory does not exist: {}", dir));
+            }
+            push("--add-dir", dir.clone());
+        }
+        for config in &self.mcp_configs {
+            let inline = serde_json::from_str::<serde_json::Value>(config).is_ok();
+            if !inline && !Path::new(config).is_file() {
+                return Err(format!("MCP config file does not exist: {}", config));
+            }
+            push("--mcp-config", config.clone());
+        }
+        if let Some(mode) = &self.permission_mode {
+            if !PERMISSION_MODES.contains(&mode.as_str()) {
+                return Err(format!(
+                    "Unknown permission mode '{}', expected one of: {}",
+                    mode,
+                    PERMISSION_MODES.join(", ")
+                ));
+            }
+            push("--permission-mode", mode.clone());
+        }
+        if let Some(model) = &self.fallback_model {
+            push("--fallback-model", model.clone());
+        }
+
+        if let Some(version) = version {
+            for (flag, min_version) in MIN_VERSIONS {
+                if args.iter().any(|arg| arg == flag)
+                    && compare_versions(version, min_version) == Ordering::Less
+                {
+                    return Err(format!(
+                        "{} requires Claude Code {} or newer, found {}",
+                        flag, min_version, version
+                    ));
+                }
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+/// The version of the Claude binary at `claude_path`, cached per path
+pub fn detected_version(claude_path: &str) -> Option<String> {
+    static VERSIONS: OnceLock<Mutex<HashMap<String, Option<String>>>> = OnceLock::new();
+    let versions = VERSIONS.get_or_init(Default::default);
+
+    if let Some(version) = versions.lock().ok()?.get(claude_path) {
+        return version.clone();
+    }
+    let version = get_claude_version(claude_path).ok().flatten();
+    if let Ok(mut versions) = versions.lock() {
+        versions.insert(claude_path.to_string(), version.clone());
+    }
+    version
+}
+
+/// CLI arguments for `options`, validated against the Claude binary at `claude_path`
+pub fn session_option_args(
+    claude_path: &str,
+    options: Option<&ClaudeSessionOptions>,
+) -> Result<Vec<String>, String> {
+    match options {
+        Some(options) if *options != ClaudeSessionOptions::default() => {
+            options.cli_args(detected_version(claude_path).as_deref())
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_args() {
+        let dir = std::env::temp_dir().to_string_lossy().into_owned();
+        let options = ClaudeSessionOptions {
+            max_turns: Some(5),
+            allowed_tools: vec!["Read".to_string(), "Bash(git:*)".to_string()],
+            add_dirs: vec![dir.clone()],
+            mcp_configs: vec![r#"{"mcpServers":{}}"#.to_string()],
+            permission_mode: Some("plan".to_string()),
+            fallback_model: Some("sonnet".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            options.cli_args(Some("1.0.40")).unwrap(),
+            vec![
+                "--max-turns",
+                "5",
+                "--allowedTools",
+                "Read,Bash(git:*)",
+                "--add-dir",
+                &dir,
+                "--mcp-config",
+                r#"{"mcpServers":{}}"#,
+                "--permission-mode",
+                "plan",
+                "--fallback-model",
+                "sonnet",
+            ]
+        );
+        assert!(options
+            .cli_args(Some("1.0.17"))
+            .unwrap_err()
+            .contains("--add-dir requires Claude Code 1.0.18"));
+        assert!(options.cli_args(None).is_ok());
+
+        let invalid = ClaudeSessionOptions {
+            permission_mode: Some("yolo".to_string()),
+            ..Default::default()
+        };
+        assert!(invalid.cli_args(None).is_err());
+    }
+}
@@ -1,7 +1,21 @@
+pub mod agent_batch;
+pub mod agent_compare;
+pub mod agent_output;
+pub mod agent_package;
+pub mod agent_registry;
+pub mod agent_trust;
 pub mod agents;
+pub mod cancellation;
 pub mod claude;
+pub mod claude_options;
 pub mod mcp;
+pub mod permissions;
 pub mod proxy;
+pub mod recovery;
+pub mod session_catalog;
+pub mod session_watcher;
 pub mod slash_commands;
 pub mod storage;
+pub mod task_template;
 pub mod usage;
+pub mod worktree;
@@ -0,0 +1,535 @@
+#![allow(dead_code)]
+use axum::extract::{Path, State as AxumState};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use log::{info, warn};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+use tokio::sync::oneshot;
+
+use crate::commands::agents::{AgentDb, AgentEventEmitter};
+
+const PERMISSION_SETTINGS_KEY: &str = "permission_settings";
+
+/// Name of the MCP server opcode registers with Claude for permission prompts
+const BRIDGE_SERVER_NAME: &str = "opcode";
+/// Tool Claude calls to ask for permission, passed as `--permission-prompt-tool`
+pub const PERMISSION_PROMPT_TOOL: &str = "mcp__opcode__approve";
+
+/// Requests nobody answers within this time are denied
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(600);
+
+/// How Claude processes started by opcode get permission to use tools
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionMode {
+    /// Run with `--dangerously-skip-permissions`
+    #[default]
+    SkipPermissions,
+    /// Ask the user through `permission-request` events
+    Prompt,
+}
+
+/// Permission mode and the tools always allowed in each project
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct PermissionSettings {
+    #[serde(default)]
+    pub mode: PermissionMode,
+    /// Project path -> names of tools allowed without asking
+    #[serde(default)]
+    pub allowed_tools: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl PermissionSettings {
+    /// Load the permission settings from app_settings
+    pub fn load(conn: &Connection) -> Self {
+        conn.query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![PERMISSION_SETTINGS_KEY],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+    }
+
+    fn save(&self, conn: &Connection) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+            params![PERMISSION_SETTINGS_KEY, json],
+        )
+        .map_err(|e| format!("Failed to save permission settings: {}", e))?;
+        Ok(())
+    }
+
+    fn is_allowed(&self, project_path: &str, tool_name: &str) -> bool {
+        self.allowed_tools
+            .get(project_path)
+            .is_some_and(|tools| tools.contains(tool_name))
+    }
+}
+
+/// The user's answer to a permission request
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionDecision {
+    AllowOnce,
+    /// Allow the tool for the rest of the Claude session
+    AllowSession,
+    /// Allow the tool in this project from now on
+    AllowProject,
+    Deny,
+}
+
+/// A tool use waiting for the user's approval, emitted as `permission-request`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PermissionRequest {
+    pub id: String,
+    /// Bridge session of the Claude process asking
+    pub session: String,
+    pub project_path: String,
+    pub tool_name: String,
+    pub input: JsonValue,
+}
+
+struct BridgeSession {
+    project_path: String,
+    allowed_tools: HashSet<String>,
+}
+
+/// Answers the permission prompts of Claude processes
+///
+/// Claude calls the `approve` tool of a small MCP server served on localhost,
+/// at a URL unique to each process. Requests that no rule allows are emitted as
+/// `permission-request` events and wait for `respond_permission_request`.
+pub struct PermissionBroker {
+    db_path: std::path::PathBuf,
+    emitter: AgentEventEmitter,
+    port: tokio::sync::OnceCell<u16>,
+    sessions: Mutex<HashMap<String, BridgeSession>>,
+    pending: Mutex<HashMap<String, (PermissionRequest, oneshot::Sender<PermissionDecision>)>>,
+}
+
+/// Global permission broker state
+pub struct PermissionBrokerState(pub Arc<PermissionBroker>);
+
+impl PermissionBroker {
+    pub fn new(data_dir: &std::path::Path, emitter: AgentEventEmitter) -> Arc<Self> {
+        Arc::new(Self {
+            db_path: data_dir.join("agents.db"),
+            emitter,
+            port: tokio::sync::OnceCell::new(),
+            sessions: Mutex::new(HashMap::new()),
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn settings(&self) -> PermissionSettings {
+        Connection::open(&self.db_path)
+            .map(|conn| PermissionSettings::load(&conn))
+            .unwrap_or_default()
+    }
+
+    /// Claude CLI arguments granting permissions for a process in `project_path`
+    pub async fn cli_args(self: &Arc<Self>, project_path: &str) -> Result<Vec<String>, String> {
+        if self.settings().mode == PermissionMode::SkipPermissions {
+            return Ok(skip_permissions_args());
+        }
+
+        let port = self.ensure_server().await?;
+        let session = uuid::Uuid::new_v4().to_string();
+        self.sessions.lock().map_err(|e| e.to_string())?.insert(
+            session.clone(),
+            BridgeSession {
+                project_path: project_path.to_string(),
+                allowed_tools: HashSet::new(),
+            },
+        );
+
+        let config = json!({
+            "mcpServers": {
+                BRIDGE_SERVER_NAME: {
+                    "type": "http",
+                    "url": format!("http://127.0.0.1:{}/mcp/{}", port, session),
+                }
+            }
+        });
+        Ok(vec![
+            "--mcp-config".to_string(),
+            config.to_string(),
+            "--permission-prompt-tool".to_string(),
+            PERMISSION_PROMPT_TOOL.to_string(),
+        ])
+    }
+
+    /// Start the MCP server on first use and return its port
+    async fn ensure_server(self: &Arc<Self>) -> Result<u16, String> {
+        let broker = self.clone();
+        self.port
+            .get_or_try_init(|| async move {
+                let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+                    .await
+                    .map_err(|e| format!("Failed to start permission bridge: {}", e))?;
+                let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+                let router = Router::new()
+                    .route("/mcp/{session}", post(handle_mcp))
+                    .with_state(broker);
+                tokio::spawn(async move {
+                    if let Err(e) = axum::serve(listener, router).await {
+                        warn!("Permission bridge stopped: {}", e);
+                    }
+                });
+                info!("Permission bridge listening on 127.0.0.1:{}", port);
+                Ok(port)
+            })
+            .await
+            .copied()
+    }
+
+    /// Decide whether the process of `session` may use a tool, asking the user if needed
+    async fn decide(&self, session: &str, tool_name: &str, input: JsonValue) -> bool {
+        let project_path = {
+            let Ok(sessions) = self.sessions.lock() else {
+                return false;
+            };
+            match sessions.get(session) {
+                Some(s) if s.allowed_tools.contains(tool_name) => return true,
+                Some(s) => s.project_path.clone(),
+                None => return false,
+            }
+        };
+        if self.settings().is_allowed(&project_path, tool_name) {
+            return true;
+        }
+
+        let request = PermissionRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            session: session.to_string(),
+            project_path,
+            tool_name: tool_name.to_string(),
+            input,
+        };
+        let (tx, rx) = oneshot::channel();
+        match self.pending.lock() {
+            Ok(mut pending) => pending.insert(request.id.clone(), (request.clone(), tx)),
+            Err(_) => return false,
+        };
+        if let Ok(payload) = serde_json::to_value(&request) {
+            (self.emitter)("permission-request", payload);
+        }
+
+        let decision = tokio::time::timeout(REQUEST_TIMEOUT, rx).await;
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.remove(&request.id);
+        }
+        match decision {
+            Ok(Ok(decision)) => decision != PermissionDecision::Deny,
+            _ => {
+                warn!("Permission request for {} timed out", request.tool_name);
+                false
+            }
+        }
+    }
+
+    /// Close the bridge session of a process that has exited
+    ///
+    /// Its bridge URL stops working, and its requests still waiting for an
+    /// answer are denied.
+    pub fn end_session(&self, session: &str) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.remove(session);
+        }
+        let Ok(mut pending) = self.pending.lock() else {
+            return;
+        };
+        let ids: Vec<String> = pending
+            .values()
+            .filter(|(request, _)| request.session == session)
+            .map(|(request, _)| request.id.clone())
+            .collect();
+        for id in ids {
+            if let Some((_, tx)) = pending.remove(&id) {
+                let _ = tx.send(PermissionDecision::Deny);
+            }
+        }
+    }
+
+    /// Requests waiting for an answer
+    pub fn pending_requests(&self) -> Result<Vec<PermissionRequest>, String> {
+        let pending = self.pending.lock().map_err(|e| e.to_string())?;
+        Ok(pending
+            .values()
+            .map(|(request, _)| request.clone())
+            .collect())
+    }
+
+    /// Answer a request; returns it so project-wide decisions can be saved
+    pub fn respond(
+        &self,
+        request_id: &str,
+        decision: PermissionDecision,
+    ) -> Result<PermissionRequest, String> {
+        let (request, tx) = self
+            .pending
+            .lock()
+            .map_err(|e| e.to_string())?
+            .remove(request_id)
+            .ok_or("Permission request not found or already answered")?;
+
+        if decision == PermissionDecision::AllowSession {
+            if let Some(session) = self
+                .sessions
+                .lock()
+                .map_err(|e| e.to_string())?
+                .get_mut(&request.session)
+            {
+                session.allowed_tools.insert(request.tool_name.clone());
+            }
+        }
+        let _ = tx.send(decision);
+        Ok(request)
+    }
+}
+
+/// Bridge session of a process started with arguments from `PermissionBroker::cli_args`
+pub fn bridge_session<S: AsRef<str>>(args: &[S]) -> Option<String> {
+    let config = args
+        .iter()
+        .skip_while(|arg| arg.as_ref() != "--mcp-config")
+        .nth(1)?;
+    let config: JsonValue = serde_json::from_str(config.as_ref()).ok()?;
+    let url = config["mcpServers"][BRIDGE_SERVER_NAME]["url"].as_str()?;
+    url.rsplit_once("/mcp/")
+        .map(|(_, session)| session.to_string())
+}
+
+/// Arguments used when permissions are not prompted for
+pub fn skip_permissions_args() -> Vec<String> {
+    vec!["--dangerously-skip-permissions".to_string()]
+}
+
+/// Result of the `approve` tool in the format `--permission-prompt-tool` expects
+fn approval_result(allowed: bool, input: &JsonValue) -> JsonValue {
+    let decision = if allowed {
+        json!({ "behavior": "allow", "updatedInput": input })
+    } else {
+        json!({ "behavior": "deny", "message": "Permission denied by the user" })
+    };
+    json!({ "content": [{ "type": "text", "text": decision.to_string() }] })
+}
+
+/// Minimal MCP endpoint (streamable HTTP, JSON responses) exposing the `approve` tool
+async fn handle_mcp(
+    AxumState(broker): AxumState<Arc<PermissionBroker>>,
+    Path(session): Path<String>,
+    Json(message): Json<JsonValue>,
+) -> Response {
+    let known = broker
+        .sessions
+        .lock()
+        .map(|sessions| sessions.contains_key(&session))
+        .unwrap_or(false);
+    if !known {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    // Notifications get no response
+    let Some(id) = message.get("id").cloned() else {
+        return StatusCode::ACCEPTED.into_response();
+    };
+
+    let params = &message["params"];
+    let result = match message["method"].as_str().unwrap_or_default() {
+        "initialize" => json!({
+            "protocolVersion": params["protocolVersion"].as_str().unwrap_or("2025-03-26"),
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": BRIDGE_SERVER_NAME, "version": env!("CARGO_PKG_VERSION") },
+        }),
+        "ping" => json!({}),
+        "tools/list" => json!({
+            "tools": [{
+                "name": "approve",
+                "description": "Ask the opcode user for permission to use a tool",
+                "inputSchema": {
+                    "type": "object",
+                    "properties": {
+                        "tool_name": { "type": "string" },
+                        "input": { "type": "object" },
+                        "tool_use_id": { "type": "string" },
+                    },
+                    "required": ["tool_name", "input"],
+                },
+            }],
+        }),
+        "tools/call" if params["name"] == "approve" => {
+            let arguments = &params["arguments"];
+            let tool_name = arguments["tool_name"].as_str().unwrap_or_default();
+            let input = arguments["input"].clone();
+            let allowed = broker.decide(&session, tool_name, input.clone()).await;
+            approval_result(allowed, &input)
+        }
+        method => {
+            return Json(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32601, "message": format!("Method not found: {}", method) },
+            }))
+            .into_response();
+        }
+    };
+
+    Json(json!({ "jsonrpc": "2.0", "id": id, "result": result })).into_response()
+}
+
+/// Get the permission mode and the tools allowed per project
+#[tauri::command]
+pub async fn get_permission_settings(db: State<'_, AgentDb>) -> Result<PermissionSettings, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(PermissionSettings::load(&conn))
+}
+
+/// Save the permission mode and the tools allowed per project
+#[tauri::command]
+pub async fn save_permission_settings(
+    db: State<'_, AgentDb>,
+    settings: PermissionSettings,
+) -> Result<(), String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    settings.save(&conn)
+}
+
+/// List the permission requests waiting for an answer
+#[tauri::command]
+pub async fn list_permission_requests(
+    broker: State<'_, PermissionBrokerState>,
+) -> Result<Vec<PermissionRequest>, String> {
+    broker.0.pending_requests()
+}
+
+/// Answer a permission request emitted as `permission-request`
+#[tauri::command]
+pub async fn respond_permission_request(
+    db: State<'_, AgentDb>,
+    broker: State<'_, PermissionBrokerState>,
+    request_id: String,
+    decision: PermissionDecision,
+) -> Result<(), String> {
+    let request = broker.0.respond(&request_id, decision)?;
+
+    if decision == PermissionDecision::AllowProject {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let mut settings = PermissionSettings::load(&conn);
+        settings
+            .allowed_tools
+            .entry(request.project_path)
+            .or_default()
+            .insert(request.tool_name);
+        settings.save(&conn)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_approval_result() {
+        let input = json!({ "command": "ls" });
+        let allow = approval_result(true, &input);
+        let text: JsonValue =
+            serde_json::from_str(allow["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(text["behavior"], "allow");
+        assert_eq!(text["updatedInput"], input);
+
+        let deny = approval_result(false, &input);
+        let text: JsonValue =
+            serde_json::from_str(deny["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(text["behavior"], "deny");
+    }
+
+    #[tokio::test]
+    async fn test_session_and_project_decisions() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let conn = Connection::open(dir.path().join("agents.db")).unwrap();
+        conn.execute(
+            "CREATE TABLE app_settings (key TEXT PRIMARY KEY, value TEXT NOT NULL)",
+            [],
+        )
+        .unwrap();
+        let mut settings = PermissionSettings::default();
+        settings
+            .allowed_tools
+            .entry("/project".to_string())
+            .or_default()
+            .insert("Read".to_string());
+        settings.save(&conn).unwrap();
+
+        let emitted = Arc::new(Mutex::new(Vec::<JsonValue>::new()));
+        let emitted_clone = emitted.clone();
+        let broker = PermissionBroker::new(
+            dir.path(),
+            Arc::new(move |_, payload| emitted_clone.lock().unwrap().push(payload)),
+        );
+        broker.sessions.lock().unwrap().insert(
+            "s1".to_string(),
+            BridgeSession {
+                project_path: "/project".to_string(),
+                allowed_tools: HashSet::new(),
+            },
+        );
+
+        // Allowed for the project without asking
+        assert!(broker.decide("s1", "Read", json!({})).await);
+        // Unknown sessions are denied
+        assert!(!broker.decide("other", "Read", json!({})).await);
+
+        // Asks, then remembers the session-wide approval
+        let asking = {
+            let broker = broker.clone();
+            tokio::spawn(async move { broker.decide("s1", "Bash", json!({})).await })
+        };
+        while emitted.lock().unwrap().is_empty() {
+            tokio::task::yield_now().await;
+        }
+        let id = emitted.lock().unwrap()[0]["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        broker
+            .respond(&id, PermissionDecision::AllowSession)
+            .unwrap();
+        assert!(asking.await.unwrap());
+        assert!(broker.decide("s1", "Bash", json!({})).await);
+
+        // Ending the session denies what it still waits for and forgets it
+        let asking = {
+            let broker = broker.clone();
+            tokio::spawn(async move { broker.decide("s1", "Write", json!({})).await })
+        };
+        while emitted.lock().unwrap().len() < 2 {
+            tokio::task::yield_now().await;
+        }
+        broker.end_session("s1");
+        assert!(!asking.await.unwrap());
+        assert!(broker.pending_requests().unwrap().is_empty());
+        assert!(!broker.decide("s1", "Bash", json!({})).await);
+    }
+
+    #[test]
+    fn test_bridge_session() {
+        let config = json!({
+            "mcpServers": { BRIDGE_SERVER_NAME: { "type": "http", "url": "http://127.0.0.1:1234/mcp/abc" } }
+        });
+        let args = ["-p", "task", "--mcp-config", &config.to_string()];
+        assert_eq!(bridge_session(&args).as_deref(), Some("abc"));
+        assert_eq!(bridge_session(&skip_permissions_args()), None);
+    }
+}
@@ -0,0 +1,409 @@
+#![allow(dead_code)]
+use log::{error, info};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+use crate::commands::agents::{
+    agent_run_from_row, encode_project_path, AgentDb, AgentRun, AgentRunHost, AgentRunOutcome,
+    AGENT_RUN_COLUMNS,
+};
+use crate::commands::session_watcher::JsonlTail;
+use crate::process::{is_same_process, pid_is_running, ProcessRegistry};
+use crate::stream_event::{RunResult, StreamEvent, StreamLine};
+
+const RECOVERY_SETTINGS_KEY: &str = "crash_recovery";
+
+/// How often the session file of an adopted run is checked for new lines
+const TAIL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// What to do with agent runs left behind by a previous instance of the app
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct RecoverySettings {
+    /// Track runs whose process is still alive instead of leaving them untracked
+    pub readopt_running: bool,
+}
+
+impl Default for RecoverySettings {
+    fn default() -> Self {
+        Self {
+            readopt_running: true,
+        }
+    }
+}
+
+impl RecoverySettings {
+    /// Load the recovery settings from app_settings
+    pub fn load(conn: &Connection) -> Self {
+        conn.query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![RECOVERY_SETTINGS_KEY],
+            |row| row.get::<_, String>(0),
+        )
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+    }
+}
+
+/// What startup recovery did with a run that was still marked running
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecoveryAction {
+    /// Its process is gone, so it was marked `interrupted`
+    Interrupted,
+    /// Its process is alive and is followed through its session file
+    Readopted,
+    /// Its process is alive but re-adopting is disabled
+    LeftRunning,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveredRun {
+    pub run_id: i64,
+    pub agent_name: String,
+    pub pid: Option<u32>,
+    pub action: RecoveryAction,
+}
+
+/// The outcome of reconciling `agent_runs` with the processes still alive
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecoveryReport {
+    pub checked_at: String,
+    pub runs: Vec<RecoveredRun>,
+}
+
+/// The report of the recovery run at startup, once it has finished
+#[derive(Default)]
+pub struct RecoveryState(pub Mutex<Option<RecoveryReport>>);
+
+/// The runs marked `running` in the database
+pub fn running_runs(conn: &Connection) -> Result<Vec<AgentRun>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM agent_runs WHERE status = 'running'",
+            AGENT_RUN_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let runs = stmt
+        .query_map([], agent_run_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(runs)
+}
+
+/// Whether the process of a run is gone, or its PID now belongs to a process
+/// started at another time than the run
+pub fn run_process_is_gone(run: &AgentRun) -> bool {
+    !run.pid
+        .is_some_and(|pid| is_same_process(pid, run.process_started_at.as_deref()))
+}
+
+/// Mark a run `interrupted` unless it has finished in the meantime; returns
+/// whether it was
+pub fn mark_interrupted(conn: &Connection, run_id: i64) -> Result<bool, String> {
+    let updated = conn
+        .execute(
+            "UPDATE agent_runs SET status = 'interrupted', completed_at = CURRENT_TIMESTAMP
+             WHERE id = ?1 AND status = 'running'",
+            params![run_id],
+        )
+        .map_err(|e| format!("Failed to mark agent run {} interrupted: {}", run_id, e))?;
+    Ok(updated > 0)
+}
+
+/// Reconcile the runs marked `running` with the processes that are alive
+///
+/// A run whose PID is gone, or now belongs to a process started at another
+/// time than the run, is marked `interrupted`. Returns the report together
+/// with the runs whose process is still alive and should be re-adopted.
+pub fn reconcile_orphaned_runs(
+    conn: &Connection,
+    settings: &RecoverySettings,
+) -> Result<(RecoveryReport, Vec<AgentRun>), String> {
+    let runs = running_runs(conn)?;
+
+    let mut report = RecoveryReport {
+        checked_at: chrono::Utc::now().to_rfc3339(),
+        runs: Vec::new(),
+    };
+    let mut alive = Vec::new();
+    for run in runs {
+        let run_id = run.id.unwrap_or_default();
+        let action = if run_process_is_gone(&run) {
+            mark_interrupted(conn, run_id)?;
+            RecoveryAction::Interrupted
+        } else if settings.readopt_running {
+            RecoveryAction::Readopted
+        } else {
+            RecoveryAction::LeftRunning
+        };
+        info!(
+            "Recovered agent run {} (PID {:?}): {:?}",
+            run_id, run.pid, action
+        );
+
+        report.runs.push(RecoveredRun {
+            run_id,
+            agent_name: run.agent_name.clone(),
+            pid: run.pid,
+            action,
+        });
+        if action == RecoveryAction::Readopted {
+            alive.push(run);
+        }
+    }
+
+    Ok((report, alive))
+}
+
+/// Reconcile the runs left running by a previous instance and re-adopt the
+/// ones that are still alive
+pub fn recover_agent_runs(
+    host: &AgentRunHost,
+    registry: Arc<ProcessRegistry>,
+) -> Result<RecoveryReport, String> {
+    let db_path = host.data_dir.join("agents.db");
+    let conn = Connection::open(&db_path).map_err(|e| e.to_string())?;
+    let settings = RecoverySettings::load(&conn);
+    let (report, alive) = reconcile_orphaned_runs(&conn, &settings)?;
+
+    // Batches are driven by a task of the previous instance, which is gone
+    let batches = crate::commands::agent_batch::reconcile_interrupted_batches(&conn)?;
+    if batches > 0 {
+        info!("Marked {} agent batches as interrupted", batches);
+    }
+
+    for run in alive {
+        if let Err(e) = adopt_run(host.clone(), db_path.clone(), registry.clone(), run) {
+            error!("Failed to re-adopt agent run: {}", e);
+        }
+    }
+    Ok(report)
+}
+
+/// Track a run started by a previous instance of the app
+///
+/// opcode is not the parent of the process, so its output is read from the
+/// session file instead of stdout, and its exit status is unknown: once the
+/// process is gone, see `adopted_run_outcome` for the status it gets.
+fn adopt_run(
+    host: AgentRunHost,
+    db_path: PathBuf,
+    registry: Arc<ProcessRegistry>,
+    run: AgentRun,
+) -> Result<(), String> {
+    let run_id = run.id.ok_or("Agent run has no ID")?;
+    let pid = run.pid.ok_or("Agent run has no PID")?;
+    registry.register_sidecar_process(
+        run_id,
+        run.agent_id,
+        run.agent_name.clone(),
+        pid,
+        run.project_path.clone(),
+        run.task.clone(),
+        run.model.clone(),
+    )?;
+
+    let session_file = Some(&run.session_id)
+        .filter(|session_id| !session_id.is_empty())
+        .and_then(|session_id| {
+            let projects_dir = dirs::home_dir()?.join(".claude").join("projects");
+            Some(
+                projects_dir
+                    .join(encode_project_path(run.working_dir()))
+                    .join(format!("{}.jsonl", session_id)),
+            )
+        });
+
+    tauri::async_runtime::spawn(async move {
+        let mut tail = JsonlTail::default();
+        let mut last_assistant_text = None;
+        let mut result = None;
+        loop {
+            // Read once more after the process is gone to catch its last lines
+            let exited = !pid_is_running(pid);
+            if let Some(session_file) = &session_file {
+                for line in tail.read_new_lines(session_file).await {
+                    for event in StreamLine::parse(&line).into_iter().flat_map(|m| m.events) {
+                        match event {
+                            StreamEvent::AssistantText { text } => last_assistant_text = Some(text),
+                            StreamEvent::Result(run_result) => result = Some(run_result),
+                            _ => {}
+                        }
+                    }
+                    let _ = registry.append_live_output(run_id, &line);
+                    host.emit(&format!("agent-output:{}", run_id), &line);
+                }
+            }
+            if exited {
+                break;
+            }
+            // Runs cancelled through the registry are finalized by the canceller
+            if !matches!(registry.get_process(run_id), Ok(Some(_))) {
+                return;
+            }
+            tokio::time::sleep(TAIL_INTERVAL).await;
+        }
+        // Let a cancellation that stopped the process record its own status
+        tokio::time::sleep(TAIL_INTERVAL).await;
+        if !matches!(registry.get_process(run_id), Ok(Some(_))) {
+            return;
+        }
+
+        info!("Adopted agent run {} (PID {}) has exited", run_id, pid);
+        let outcome = adopted_run_outcome(result.as_ref(), last_assistant_text);
+        match Connection::open(&db_path) {
+            Ok(conn) => {
+                if let Err(e) = conn.execute(
+                    "UPDATE agent_runs SET status = ?1, error_subtype = ?2,
+                     result_text = COALESCE(?3, result_text), completed_at = CURRENT_TIMESTAMP
+                     WHERE id = ?4 AND status = 'running'",
+                    params![
+                        outcome.status,
+                        outcome.error_subtype,
+                        outcome.result_text,
+                        run_id
+                    ],
+                ) {
+                    error!("Failed to finalize adopted agent run {}: {}", run_id, e);
+                }
+            }
+            Err(e) => error!("Failed to open database for agent run {}: {}", run_id, e),
+        }
+        let _ = registry.unregister_process(run_id);
+
+        let success = outcome.status == "completed";
+        host.emit("agent-complete", success);
+        host.emit(&format!("agent-complete:{}", run_id), success);
+    });
+
+    Ok(())
+}
+
+/// Outcome of an adopted run whose process is gone
+///
+/// Without the exit status, only the session's final `result` line tells how
+/// the run ended; a run that wrote none was cut short and is `interrupted`.
+fn adopted_run_outcome(
+    result: Option<&RunResult>,
+    last_assistant_text: Option<String>,
+) -> AgentRunOutcome {
+    match result {
+        // The CLI exits cleanly after writing its result
+        Some(result) => AgentRunOutcome::determine(Some(0), Some(result)),
+        None => AgentRunOutcome {
+            status: "interrupted".to_string(),
+            error_subtype: None,
+            result_text: last_assistant_text,
+        },
+    }
+}
+
+/// Get what startup recovery did, once it has finished
+#[tauri::command]
+pub async fn get_recovery_report(
+    state: State<'_, RecoveryState>,
+) -> Result<Option<RecoveryReport>, String> {
+    Ok(state.0.lock().map_err(|e| e.to_string())?.clone())
+}
+
+/// Get whether agent runs still alive at startup are re-adopted
+#[tauri::command]
+pub async fn get_recovery_settings(db: State<'_, AgentDb>) -> Result<RecoverySettings, String> {
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    Ok(RecoverySettings::load(&conn))
+}
+
+/// Save whether agent runs still alive at startup are re-adopted
+#[tauri::command]
+pub async fn save_recovery_settings(
+    db: State<'_, AgentDb>,
+    settings: RecoverySettings,
+) -> Result<(), String> {
+    let json = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?1, ?2)",
+        params![RECOVERY_SETTINGS_KEY, json],
+    )
+    .map_err(|e| format!("Failed to save recovery settings: {}", e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert_running_run(conn: &Connection, pid: u32, started_at: &str) -> i64 {
+        conn.execute(
+            "INSERT INTO agent_runs (agent_id, agent_name, agent_icon, task, model, project_path, session_id, status, pid, process_started_at)
+             VALUES (1, 'agent', 'bot', 'task', 'sonnet', '/tmp', '', 'running', ?1, ?2)",
+            params![pid as i64, started_at],
+        )
+        .unwrap();
+        conn.last_insert_rowid()
+    }
+
+    #[test]
+    fn test_reconcile_marks_dead_runs_interrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let conn = crate::commands::agents::init_database_in(dir.path()).unwrap();
+        conn.execute(
+            "INSERT INTO agents (id, name, icon, system_prompt) VALUES (1, 'agent', 'bot', '')",
+            [],
+        )
+        .unwrap();
+
+        let mut exited = std::process::Command::new("true").spawn().unwrap();
+        let dead_pid = exited.id();
+        exited.wait().unwrap();
+        let now = chrono::Utc::now().to_rfc3339();
+        let dead = insert_running_run(&conn, dead_pid, &now);
+        // Our own PID, but recorded as started long before this process was
+        let reused = insert_running_run(&conn, std::process::id(), "2001-01-01T00:00:00Z");
+
+        let settings = RecoverySettings::default();
+        let (report, alive) = reconcile_orphaned_runs(&conn, &settings).unwrap();
+
+        assert!(alive.is_empty());
+        assert_eq!(report.runs.len(), 2);
+        for run_id in [dead, reused] {
+            let status: String = conn
+                .query_row(
+                    "SELECT status FROM agent_runs WHERE id = ?1",
+                    params![run_id],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(status, "interrupted");
+        }
+    }
+
+    #[test]
+    fn test_adopted_run_outcome() {
+        let line = StreamLine::parse(
+            r#"{"type":"result","subtype":"success","is_error":false,"result":"Done"}"#,
+        )
+        .unwrap();
+        let outcome = adopted_run_outcome(line.result(), None);
+        assert_eq!(outcome.status, "completed");
+        assert_eq!(outcome.result_text.as_deref(), Some("Done"));
+
+        let line =
+            StreamLine::parse(r#"{"type":"result","subtype":"error_max_turns","is_error":true}"#)
+                .unwrap();
+        let outcome = adopted_run_outcome(line.result(), None);
+        assert_eq!(outcome.status, "failed");
+        assert_eq!(outcome.error_subtype.as_deref(), Some("error_max_turns"));
+
+        let outcome = adopted_run_outcome(None, Some("Halfway".to_string()));
+        assert_eq!(outcome.status, "interrupted");
+        assert_eq!(outcome.result_text.as_deref(), Some("Halfway"));
+    }
+}
@@ -0,0 +1,739 @@
+#![allow(dead_code)]
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::commands::agents::AgentDb;
+use crate::commands::claude::{decode_project_path, Project, Session};
+use crate::commands::usage::estimate_usage_cost;
+use crate::stream_event::{StreamEvent, StreamLine};
+
+/// Order of the projects returned by `list_projects`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectSort {
+    /// Most recent session activity first
+    #[default]
+    LastActive,
+    /// Most recently created first
+    Newest,
+    /// By project path
+    Path,
+    /// Most sessions first
+    Sessions,
+}
+
+/// Order of the sessions returned by `get_project_sessions`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionSort {
+    /// Most recently created first
+    #[default]
+    Newest,
+    Oldest,
+    /// Most recently written to first
+    LastActive,
+    /// Most messages first
+    Messages,
+    /// Most expensive first
+    Cost,
+}
+
+impl SessionSort {
+    fn order_by(self) -> &'static str {
+        match self {
+            Self::Newest => "created_at DESC, session_id",
+            Self::Oldest => "created_at ASC, session_id",
+            Self::LastActive => "file_mtime_ms DESC, session_id",
+            Self::Messages => "message_count DESC, session_id",
+            Self::Cost => "cost_usd DESC, session_id",
+        }
+    }
+}
+
+/// Pagination and sorting of a catalog listing
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct CatalogQuery<S> {
+    #[serde(default)]
+    pub offset: Option<usize>,
+    /// All remaining entries when not set
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub sort: Option<S>,
+}
+
+/// Create the table indexing the session files in `~/.claude/projects`
+pub fn init_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS session_catalog (
+            project_id TEXT NOT NULL,
+            session_id TEXT NOT NULL,
+            file_size INTEGER NOT NULL,
+            file_mtime_ms INTEGER NOT NULL,
+            indexed_bytes INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            cwd TEXT,
+            first_message TEXT,
+            message_timestamp TEXT,
+            last_timestamp TEXT,
+            message_count INTEGER NOT NULL DEFAULT 0,
+            model TEXT,
+            cost_usd REAL NOT NULL DEFAULT 0,
+            last_message_key TEXT,
+            PRIMARY KEY (project_id, session_id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// What the catalog knows about a session file
+#[derive(Debug, Clone, Default, PartialEq)]
+struct IndexedSession {
+    file_size: u64,
+    file_mtime_ms: i64,
+    /// End of the last complete line read; an incomplete line is read again
+    indexed_bytes: u64,
+    created_at: u64,
+    cwd: Option<String>,
+    first_message: Option<String>,
+    message_timestamp: Option<String>,
+    last_timestamp: Option<String>,
+    message_count: u64,
+    model: Option<String>,
+    cost_usd: f64,
+    /// Usage of an assistant message is repeated on each of its lines
+    last_message_key: Option<String>,
+}
+
+impl IndexedSession {
+    fn record(&mut self, line: &StreamLine) {
+        if self.cwd.is_none() {
+            self.cwd = line.cwd.clone().filter(|cwd| !cwd.is_empty());
+        }
+        if line.timestamp.is_some() {
+            self.last_timestamp = line.timestamp.clone();
+        }
+
+        if line.kind == "user" {
+            // Tool results are sent back as user messages
+            if !line
+                .events
+                .iter()
+                .all(|event| matches!(event, StreamEvent::ToolResult { .. }))
+            {
+                self.message_count += 1;
+            }
+            if self.first_message.is_none() {
+                let text = line.events.iter().find_map(|event| match event {
+                    StreamEvent::UserText { text } if is_prompt(text) => Some(text),
+                    _ => None,
+                });
+                if let Some(text) = text {
+                    self.first_message = Some(text.clone());
+                    self.message_timestamp = line.timestamp.clone();
+                }
+            }
+        }
+
+        if line.kind == "assistant" {
+            let (message_id, model, usage) = match line.message_usage() {
+                Some(usage) => usage,
+                None => {
+                    self.message_count += 1;
+                    return;
+                }
+            };
+            let key = format!(
+                "{}:{}",
+                message_id.unwrap_or_default(),
+                line.request_id.as_deref().unwrap_or_default()
+            );
+            if message_id.is_some() && self.last_message_key.as_deref() == Some(key.as_str()) {
+                return;
+            }
+            self.message_count += 1;
+            self.last_message_key = Some(key);
+            if let Some(model) = model.filter(|model| *model != "<synthetic>") {
+                self.model = Some(model.to_string());
+            }
+            self.cost_usd += line
+                .cost_usd
+                .unwrap_or_else(|| model.map_or(0.0, |model| estimate_usage_cost(model, usage)));
+        }
+    }
+}
+
+/// Whether a user message was typed by the user rather than recorded for a
+/// local command
+fn is_prompt(text: &str) -> bool {
+    !text.contains(
+        "Caveat: The messages below were generated by the user while running local commands",
+    ) && !text.starts_with("<command-name>")
+        && !text.starts_with("<local-command-stdout>")
+}
+
+/// Read the lines of `path` from `entry.indexed_bytes` on into `entry`
+fn index_file(path: &Path, entry: &mut IndexedSession) -> std::io::Result<()> {
+    let mut file = fs::File::open(path)?;
+    file.seek(SeekFrom::Start(entry.indexed_bytes))?;
+    let mut appended = Vec::new();
+    file.read_to_end(&mut appended)?;
+
+    let complete = match appended.iter().rposition(|byte| *byte == b'\n') {
+        Some(end) => &appended[..=end],
+        None => return Ok(()),
+    };
+    for line in String::from_utf8_lossy(complete).lines() {
+        if let Some(line) = StreamLine::parse(line) {
+            entry.record(&line);
+        }
+    }
+    entry.indexed_bytes += complete.len() as u64;
+    Ok(())
+}
+
+fn unix_secs(time: std::io::Result<SystemTime>) -> u64 {
+    time.ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// A project directory and its session files
+struct ProjectDir {
+    id: String,
+    created_at: u64,
+    sessions: Vec<(String, PathBuf, fs::Metadata)>,
+}
+
+fn scan_project_dir(path: &Path) -> Option<ProjectDir> {
+    let id = path.file_name()?.to_str()?.to_string();
+    let metadata = fs::metadata(path).ok()?;
+    let mut sessions = Vec::new();
+    for entry in fs::read_dir(path).ok()?.flatten() {
+        let session_path = entry.path();
+        if session_path.extension().and_then(|s| s.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let Some(session_id) = session_path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if let Ok(metadata) = entry.metadata() {
+            if metadata.is_file() {
+                sessions.push((session_id.to_string(), session_path, metadata));
+            }
+        }
+    }
+    Some(ProjectDir {
+        id,
+        created_at: unix_secs(metadata.created().or_else(|_| metadata.modified())),
+        sessions,
+    })
+}
+
+fn load_indexed(
+    conn: &Connection,
+    project_id: Option<&str>,
+) -> rusqlite::Result<HashMap<(String, String), IndexedSession>> {
+    let mut stmt = conn.prepare(
+        "SELECT project_id, session_id, file_size, file_mtime_ms, indexed_bytes, created_at, cwd,
+                first_message, message_timestamp, last_timestamp, message_count, model, cost_usd,
+                last_message_key
+         FROM session_catalog WHERE ?1 IS NULL OR project_id = ?1",
+    )?;
+    let rows = stmt.query_map(params![project_id], |row| {
+        Ok((
+            (row.get(0)?, row.get(1)?),
+            IndexedSession {
+                file_size: row.get::<_, i64>(2)? as u64,
+                file_mtime_ms: row.get(3)?,
+                indexed_bytes: row.get::<_, i64>(4)? as u64,
+                created_at: row.get::<_, i64>(5)? as u64,
+                cwd: row.get(6)?,
+                first_message: row.get(7)?,
+                message_timestamp: row.get(8)?,
+                last_timestamp: row.get(9)?,
+                message_count: row.get::<_, i64>(10)? as u64,
+                model: row.get(11)?,
+                cost_usd: row.get(12)?,
+                last_message_key: row.get(13)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+/// Bring the catalog up to date with the session files of one or all projects
+///
+/// Only files whose size or modification time changed are read, and files
+/// that grew are read from where the last refresh stopped. The database is
+/// not locked while files are read.
+fn refresh(
+    db: &AgentDb,
+    projects_dir: &Path,
+    project_id: Option<&str>,
+) -> Result<Vec<ProjectDir>, String> {
+    let dirs: Vec<ProjectDir> = match project_id {
+        Some(project_id) => scan_project_dir(&projects_dir.join(project_id))
+            .into_iter()
+            .collect(),
+        None => fs::read_dir(projects_dir)
+            .map_err(|e| format!("Failed to read projects directory: {}", e))?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .filter_map(|path| scan_project_dir(&path))
+            .collect(),
+    };
+
+    let indexed = {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        load_indexed(&conn, project_id).map_err(|e| e.to_string())?
+    };
+
+    let mut seen = HashSet::new();
+    let mut changed = Vec::new();
+    for dir in &dirs {
+        for (session_id, path, metadata) in &dir.sessions {
+            let key = (dir.id.clone(), session_id.clone());
+            let file_size = metadata.len();
+            let file_mtime_ms = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_millis() as i64);
+
+            let mut entry = match indexed.get(&key) {
+                Some(entry)
+                    if entry.file_size == file_size && entry.file_mtime_ms == file_mtime_ms =>
+                {
+                    seen.insert(key);
+                    continue;
+                }
+                // Session files are only appended to
+                Some(entry) if file_size >= entry.indexed_bytes => entry.clone(),
+                _ => IndexedSession {
+                    created_at: unix_secs(metadata.created().or_else(|_| metadata.modified())),
+                    ..Default::default()
+                },
+            };
+            if let Err(e) = index_file(path, &mut entry) {
+                log::warn!("Failed to index session {}: {}", path.display(), e);
+                continue;
+            }
+            entry.file_size = file_size;
+            entry.file_mtime_ms = file_mtime_ms;
+            seen.insert(key.clone());
+            changed.push((key, entry));
+        }
+    }
+
+    let mut conn = db.0.lock().map_err(|e| e.to_string())?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    for ((project_id, session_id), entry) in &changed {
+        tx.execute(
+            "INSERT OR REPLACE INTO session_catalog (project_id, session_id, file_size, file_mtime_ms,
+                indexed_bytes, created_at, cwd, first_message, message_timestamp, last_timestamp,
+                message_count, model, cost_usd, last_message_key)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            params![
+                project_id,
+                session_id,
+                entry.file_size as i64,
+                entry.file_mtime_ms,
+                entry.indexed_bytes as i64,
+                entry.created_at as i64,
+                entry.cwd,
+                entry.first_message,
+                entry.message_timestamp,
+                entry.last_timestamp,
+                entry.message_count as i64,
+                entry.model,
+                entry.cost_usd,
+                entry.last_message_key,
+            ],
+        )
+        .map_err(|e| format!("Failed to update session catalog: {}", e))?;
+    }
+    for (project_id, session_id) in indexed.keys().filter(|key| !seen.contains(*key)) {
+        tx.execute(
+            "DELETE FROM session_catalog WHERE project_id = ?1 AND session_id = ?2",
+            params![project_id, session_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    if !changed.is_empty() {
+        log::info!("Indexed {} changed session files", changed.len());
+    }
+    Ok(dirs)
+}
+
+/// The sessions of a project as recorded in the catalog
+#[derive(Default)]
+struct CatalogedProject {
+    sessions: Vec<String>,
+    most_recent_session: Option<u64>,
+    /// Working directory of its oldest session
+    path: Option<String>,
+}
+
+fn page<T>(items: Vec<T>, offset: Option<usize>, limit: Option<usize>) -> Vec<T> {
+    items
+        .into_iter()
+        .skip(offset.unwrap_or(0))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// The projects in `projects_dir`, read from the catalog after refreshing it
+pub fn list_projects(
+    db: &AgentDb,
+    projects_dir: &Path,
+    query: CatalogQuery<ProjectSort>,
+) -> Result<Vec<Project>, String> {
+    if !projects_dir.exists() {
+        log::warn!("Projects directory does not exist: {:?}", projects_dir);
+        return Ok(Vec::new());
+    }
+    let dirs = refresh(db, projects_dir, None)?;
+
+    let mut catalog: HashMap<String, CatalogedProject> = HashMap::new();
+    {
+        let conn = db.0.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT project_id, session_id, file_mtime_ms, cwd FROM session_catalog
+                 ORDER BY created_at",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        for row in rows {
+            let (project_id, session_id, mtime_ms, cwd) = row.map_err(|e| e.to_string())?;
+            let project = catalog.entry(project_id).or_default();
+            project.sessions.push(session_id);
+            let modified = (mtime_ms / 1000) as u64;
+            project.most_recent_session = Some(
+                project
+                    .most_recent_session
+                    .map_or(modified, |current| current.max(modified)),
+            );
+            if project.path.is_none() {
+                project.path = cwd;
+            }
+        }
+    }
+
+    let mut projects: Vec<Project> = dirs
+        .into_iter()
+        .map(|dir| {
+            let project = catalog.remove(&dir.id).unwrap_or_default();
+            Project {
+                path: project.path.unwrap_or_else(|| decode_project_path(&dir.id)),
+                id: dir.id,
+                sessions: project.sessions,
+                created_at: dir.created_at,
+                most_recent_session: project.most_recent_session,
+            }
+        })
+        .collect();
+
+    match query.sort.unwrap_or_default() {
+        ProjectSort::LastActive => {
+            projects.sort_by(
+                |a, b| match (a.most_recent_session, b.most_recent_session) {
+                    (Some(a_time), Some(b_time)) => b_time.cmp(&a_time),
+                    (Some(_), None) => std::cmp::Ordering::Less,
+                    (None, Some(_)) => std::cmp::Ordering::Greater,
+                    (None, None) => b.created_at.cmp(&a.created_at),
+                },
+            )
+        }
+        ProjectSort::Newest => projects.sort_by_key(|p| std::cmp::Reverse(p.created_at)),
+        ProjectSort::Path => projects.sort_by(|a, b| a.path.cmp(&b.path)),
+        ProjectSort::Sessions => projects.sort_by_key(|p| std::cmp::Reverse(p.sessions.len())),
+    }
+
+    log::info!("Found {} projects", projects.len());
+    Ok(page(projects, query.offset, query.limit))
+}
+
+/// The sessions of a project, read from the catalog after refreshing it
+pub fn project_sessions(
+    db: &AgentDb,
+    projects_dir: &Path,
+    todos_dir: &Path,
+    project_id: &str,
+    query: CatalogQuery<SessionSort>,
+) -> Result<Vec<Session>, String> {
+    if !projects_dir.join(project_id).exists() {
+        return Err(format!("Project directory not found: {}", project_id));
+    }
+    refresh(db, projects_dir, Some(project_id))?;
+
+    let conn = db.0.lock().map_err(|e| e.to_string())?;
+    let project_path = conn
+        .query_row(
+            "SELECT cwd FROM session_catalog WHERE project_id = ?1 AND cwd IS NOT NULL
+             ORDER BY created_at LIMIT 1",
+            params![project_id],
+            |row| row.get::<_, String>(0),
+        )
+        .unwrap_or_else(|_| decode_project_path(project_id));
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT session_id, created_at, file_mtime_ms, first_message, message_timestamp,
+                    last_timestamp, message_count, model, cost_usd
+             FROM session_catalog WHERE project_id = ?1
+             ORDER BY {} LIMIT ?2 OFFSET ?3",
+            query.sort.unwrap_or_default().order_by()
+        ))
+        .map_err(|e| e.to_string())?;
+    let limit = query.limit.map_or(-1, |limit| limit as i64);
+    let offset = query.offset.unwrap_or(0) as i64;
+    let sessions = stmt
+        .query_map(params![project_id, limit, offset], |row| {
+            let session_id: String = row.get(0)?;
+            // Todo lists are only read for the page being returned
+            let todo_data = fs::read_to_string(todos_dir.join(format!("{}.json", session_id)))
+                .ok()
+                .and_then(|content| serde_json::from_str(&content).ok());
+            Ok(Session {
+                id: session_id,
+                project_id: project_id.to_string(),
+                project_path: project_path.clone(),
+                todo_data,
+                created_at: row.get::<_, i64>(1)? as u64,
+                last_modified: (row.get::<_, i64>(2)? / 1000) as u64,
+                first_message: row.get(3)?,
+                message_timestamp: row.get(4)?,
+                last_message_timestamp: row.get(5)?,
+                message_count: row.get::<_, i64>(6)? as u64,
+                model: row.get(7)?,
+                cost_usd: row.get(8)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    log::info!(
+        "Found {} sessions for project {}",
+        sessions.len(),
+        project_id
+    );
+    Ok(sessions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::Mutex;
+
+    fn catalog_db() -> AgentDb {
+        let conn = Connection::open_in_memory().unwrap();
+        init_table(&conn).unwrap();
+        AgentDb(Mutex::new(conn))
+    }
+
+    fn recorded_cwd(lines: &[&str]) -> Option<String> {
+        let mut entry = IndexedSession::default();
+        for line in lines {
+            entry.record(&StreamLine::parse(line).unwrap());
+        }
+        entry.cwd
+    }
+
+    #[test]
+    fn test_record_keeps_first_cwd() {
+        assert_eq!(
+            recorded_cwd(&[r#"{"type":"system","cwd":"/Users/test/data-discovery"}"#]).as_deref(),
+            Some("/Users/test/data-discovery")
+        );
+        // Null or empty cwds on the first lines are skipped
+        assert_eq!(
+            recorded_cwd(&[
+                r#"{"type":"system","cwd":null}"#,
+                r#"{"type":"other"}"#,
+                r#"{"type":"system","cwd":""}"#,
+                r#"{"type":"message"}"#,
+                r#"{"type":"system","cwd":"/Users/test/project"}"#,
+                r#"{"type":"system","cwd":"/Users/test/project/sub"}"#,
+            ])
+            .as_deref(),
+            Some("/Users/test/project")
+        );
+        assert_eq!(
+            recorded_cwd(&[r#"{"type":"system"}"#, r#"{"type":"message"}"#]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_project_path_resolution() {
+        let dir = tempfile::tempdir().unwrap();
+        let projects_dir = dir.path().join("projects");
+        let write_session = |project: &str, session: &str, content: &str| {
+            let project_dir = projects_dir.join(project);
+            fs::create_dir_all(&project_dir).unwrap();
+            fs::write(project_dir.join(session), content).unwrap();
+        };
+        // Hyphens in the path can't be told apart from separators when decoding
+        write_session(
+            "-Users-test-data-discovery",
+            "s1.jsonl",
+            "{\"type\":\"system\",\"cwd\":\"/Users/test/data-discovery\"}\n",
+        );
+        write_session(
+            "-Users-test-no-cwd",
+            "s1.jsonl",
+            "{\"type\":\"system\"}\n{\"type\":\"message\"}\n",
+        );
+        write_session(
+            "-Users-test-multi",
+            "s1.jsonl",
+            "{\"type\":\"system\",\"cwd\":\"/path1\"}\n",
+        );
+        write_session(
+            "-Users-test-multi",
+            "s2.jsonl",
+            "{\"type\":\"system\",\"cwd\":\"/path2\"}\n",
+        );
+        write_session("-Users-test-notes", "readme.txt", "Some text");
+        fs::create_dir_all(projects_dir.join("-Users-test-empty")).unwrap();
+
+        let db = catalog_db();
+        let projects = list_projects(
+            &db,
+            &projects_dir,
+            CatalogQuery {
+                offset: None,
+                limit: None,
+                sort: Some(ProjectSort::Path),
+            },
+        )
+        .unwrap();
+        let project = |id: &str| projects.iter().find(|p| p.id == id).unwrap();
+
+        assert_eq!(projects.len(), 5);
+        assert_eq!(
+            project("-Users-test-data-discovery").path,
+            "/Users/test/data-discovery"
+        );
+        // Without a recorded cwd the directory name is decoded
+        for id in [
+            "-Users-test-no-cwd",
+            "-Users-test-empty",
+            "-Users-test-notes",
+        ] {
+            assert_eq!(project(id).path, decode_project_path(id));
+        }
+        assert!(project("-Users-test-empty").sessions.is_empty());
+        assert!(project("-Users-test-notes").sessions.is_empty());
+        let multi = project("-Users-test-multi");
+        assert_eq!(multi.sessions.len(), 2);
+        assert!(multi.path == "/path1" || multi.path == "/path2");
+
+        let query = CatalogQuery::<SessionSort>::default();
+        let sessions =
+            project_sessions(&db, &projects_dir, dir.path(), "-Users-test-no-cwd", query).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(
+            sessions[0].project_path,
+            decode_project_path("-Users-test-no-cwd")
+        );
+        let sessions =
+            project_sessions(&db, &projects_dir, dir.path(), "-Users-test-multi", query).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions
+            .iter()
+            .all(|s| s.project_path == "/path1" || s.project_path == "/path2"));
+        assert!(
+            project_sessions(&db, &projects_dir, dir.path(), "-Users-test-gone", query).is_err()
+        );
+    }
+
+    #[test]
+    fn test_catalog_indexes_appended_lines_incrementally() {
+        let dir = tempfile::tempdir().unwrap();
+        let projects_dir = dir.path().join("projects");
+        let project_dir = projects_dir.join("-tmp-app");
+        fs::create_dir_all(&project_dir).unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        init_table(&conn).unwrap();
+        let db = AgentDb(Mutex::new(conn));
+
+        let session_path = project_dir.join("s1.jsonl");
+        fs::write(
+            &session_path,
+            concat!(
+                r#"{"type":"user","cwd":"/tmp/app","timestamp":"2025-01-01T00:00:00Z","message":{"role":"user","content":"<command-name>/clear</command-name>"}}"#,
+                "\n",
+                r#"{"type":"user","cwd":"/tmp/app","timestamp":"2025-01-01T00:00:01Z","message":{"role":"user","content":"Fix the bug"}}"#,
+                "\n",
+                r#"{"type":"assistant","requestId":"r1","costUSD":0.25,"timestamp":"2025-01-01T00:00:02Z","message":{"id":"m1","model":"claude-sonnet-4","role":"assistant","content":[{"type":"text","text":"On it"}],"usage":{"input_tokens":10,"output_tokens":5}}}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let query = CatalogQuery::<SessionSort>::default();
+        let sessions = project_sessions(&db, &projects_dir, dir.path(), "-tmp-app", query).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].project_path, "/tmp/app");
+        assert_eq!(sessions[0].first_message.as_deref(), Some("Fix the bug"));
+        assert_eq!(sessions[0].message_count, 3);
+        assert_eq!(sessions[0].model.as_deref(), Some("claude-sonnet-4"));
+
+        // The second block of the same message carries the same usage again
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(&session_path)
+            .unwrap();
+        writeln!(
+            file,
+            r#"{{"type":"assistant","requestId":"r1","costUSD":0.25,"timestamp":"2025-01-01T00:00:03Z","message":{{"id":"m1","model":"claude-sonnet-4","role":"assistant","content":[{{"type":"tool_use","id":"t1","name":"Bash","input":{{}}}}],"usage":{{"input_tokens":10,"output_tokens":5}}}}}}"#
+        )
+        .unwrap();
+        write!(file, r#"{{"type":"user","#).unwrap();
+        drop(file);
+
+        let sessions = project_sessions(&db, &projects_dir, dir.path(), "-tmp-app", query).unwrap();
+        assert_eq!(sessions[0].message_count, 3);
+        assert!((sessions[0].cost_usd - 0.25).abs() < 1e-9);
+        assert_eq!(
+            sessions[0].last_message_timestamp.as_deref(),
+            Some("2025-01-01T00:00:03Z")
+        );
+
+        let projects = list_projects(
+            &db,
+            &projects_dir,
+            CatalogQuery {
+                offset: None,
+                limit: Some(1),
+                sort: Some(ProjectSort::Sessions),
+            },
+        )
+        .unwrap();
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].sessions, vec!["s1".to_string()]);
+    }
+}
@@ -0,0 +1,296 @@
+#![allow(dead_code)]
+use log::{debug, error, info, warn};
+use notify::{EventKind, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// A session without new lines for this long is reported as finished
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often idle sessions are looked for
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Receives the events of the session watcher, e.g. `session-created`
+pub type SessionEventEmitter = Arc<dyn Fn(&str, JsonValue) + Send + Sync>;
+
+/// Reads the lines appended to a JSONL file since the last read
+#[derive(Debug, Default)]
+pub struct JsonlTail {
+    offset: u64,
+    /// Bytes of the incomplete last line, which may end inside a character
+    partial: Vec<u8>,
+}
+
+impl JsonlTail {
+    /// Tail that skips the first `offset` bytes, e.g. the lines already in a
+    /// file when watching starts
+    pub fn starting_at(offset: u64) -> Self {
+        Self {
+            offset,
+            partial: Vec::new(),
+        }
+    }
+
+    /// The complete lines appended since the last read
+    ///
+    /// An incomplete last line is kept until the rest of it is written. A file
+    /// that shrank was rewritten and is read again from the start.
+    pub async fn read_new_lines(&mut self, path: &Path) -> Vec<String> {
+        let mut appended = Vec::new();
+        let read = async {
+            let mut file = tokio::fs::File::open(path).await?;
+            if file.metadata().await?.len() < self.offset {
+                self.offset = 0;
+                self.partial.clear();
+            }
+            file.seek(std::io::SeekFrom::Start(self.offset)).await?;
+            file.read_to_end(&mut appended).await
+        };
+        if let Err(e) = read.await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to read {}: {}", path.display(), e);
+            }
+            return Vec::new();
+        }
+        self.offset += appended.len() as u64;
+        self.partial.extend_from_slice(&appended);
+
+        let complete = match self.partial.iter().rposition(|byte| *byte == b'\n') {
+            Some(end) => self.partial.drain(..=end).collect::<Vec<u8>>(),
+            None => return Vec::new(),
+        };
+        String::from_utf8_lossy(&complete)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// Activity in a session file under `~/.claude/projects`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionActivity {
+    pub project_id: String,
+    pub session_id: String,
+    /// Number of lines appended, for `session-appended`
+    pub new_lines: usize,
+}
+
+/// The project and session ID of a session file, i.e.
+/// `<projects_dir>/<project_id>/<session_id>.jsonl`
+fn session_ids(projects_dir: &Path, path: &Path) -> Option<(String, String)> {
+    let relative = path.strip_prefix(projects_dir).ok()?;
+    let mut components = relative.components();
+    let project_id = components.next()?.as_os_str().to_str()?.to_string();
+    let file = Path::new(components.next()?.as_os_str());
+    if components.next().is_some() || file.extension()? != "jsonl" {
+        return None;
+    }
+    let session_id = file.file_stem()?.to_str()?.to_string();
+    Some((project_id, session_id))
+}
+
+struct WatchedSession {
+    project_id: String,
+    session_id: String,
+    tail: JsonlTail,
+    last_append: Instant,
+    finished: bool,
+}
+
+/// Watches the session files of all projects, including sessions started
+/// outside opcode with the plain `claude` CLI
+///
+/// Emits `session-created` when a session file appears, `session-appended`
+/// when lines are added to it, and `session-finished` once it has been quiet
+/// for a while; each with a `SessionActivity`. The appended lines themselves
+/// are emitted one by one as `session-output:{session_id}`, so a session can
+/// be live-tailed.
+pub struct SessionWatcher {
+    // Dropping the watcher stops the events
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl SessionWatcher {
+    /// Start watching `projects_dir`
+    pub fn start(projects_dir: PathBuf, emitter: SessionEventEmitter) -> Result<Self, String> {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })
+        .map_err(|e| format!("Failed to create session watcher: {}", e))?;
+        watcher
+            .watch(&projects_dir, RecursiveMode::Recursive)
+            .map_err(|e| format!("Failed to watch {}: {}", projects_dir.display(), e))?;
+
+        // Sessions that exist already are only reported once they change
+        let mut sessions: HashMap<PathBuf, WatchedSession> = HashMap::new();
+        for entry in walkdir::WalkDir::new(&projects_dir)
+            .min_depth(2)
+            .max_depth(2)
+            .into_iter()
+            .filter_map(Result::ok)
+        {
+            let path = entry.into_path();
+            if let Some((project_id, session_id)) = session_ids(&projects_dir, &path) {
+                let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+                sessions.insert(
+                    path,
+                    WatchedSession {
+                        project_id,
+                        session_id,
+                        tail: JsonlTail::starting_at(size),
+                        last_append: Instant::now(),
+                        finished: true,
+                    },
+                );
+            }
+        }
+        info!(
+            "Watching {} for session changes ({} existing sessions)",
+            projects_dir.display(),
+            sessions.len()
+        );
+
+        tauri::async_runtime::spawn(async move {
+            let emit = |event: &str, activity: &WatchedSession, new_lines: usize| {
+                let activity = SessionActivity {
+                    project_id: activity.project_id.clone(),
+                    session_id: activity.session_id.clone(),
+                    new_lines,
+                };
+                if let Ok(payload) = serde_json::to_value(activity) {
+                    emitter(event, payload);
+                }
+            };
+            let mut idle_check = tokio::time::interval(IDLE_CHECK_INTERVAL);
+
+            loop {
+                tokio::select! {
+                    event = rx.recv() => {
+                        let event: notify::Event = match event {
+                            Some(Ok(event)) => event,
+                            Some(Err(e)) => {
+                                error!("Session watcher error: {}", e);
+                                continue;
+                            }
+                            // The watcher was dropped
+                            None => break,
+                        };
+
+                        for path in event.paths {
+                            let Some((project_id, session_id)) = session_ids(&projects_dir, &path)
+                            else {
+                                continue;
+                            };
+                            if matches!(event.kind, EventKind::Remove(_)) {
+                                if let Some(session) = sessions.remove(&path) {
+                                    if !session.finished {
+                                        emit("session-finished", &session, 0);
+                                    }
+                                }
+                                continue;
+                            }
+                            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                                continue;
+                            }
+
+                            let session = sessions.entry(path.clone()).or_insert_with(|| {
+                                debug!("Session {} created in {}", session_id, project_id);
+                                let session = WatchedSession {
+                                    project_id,
+                                    session_id,
+                                    tail: JsonlTail::default(),
+                                    last_append: Instant::now(),
+                                    finished: false,
+                                };
+                                emit("session-created", &session, 0);
+                                session
+                            });
+                            let lines = session.tail.read_new_lines(&path).await;
+                            if lines.is_empty() {
+                                continue;
+                            }
+                            session.last_append = Instant::now();
+                            session.finished = false;
+                            emit("session-appended", session, lines.len());
+                            let output = format!("session-output:{}", session.session_id);
+                            for line in lines {
+                                emitter(&output, JsonValue::String(line));
+                            }
+                        }
+                    }
+                    _ = idle_check.tick() => {
+                        for session in sessions.values_mut() {
+                            if !session.finished
+                                && session.last_append.elapsed() >= SESSION_IDLE_TIMEOUT
+                            {
+                                session.finished = true;
+                                emit("session-finished", session, 0);
+                            }
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+/// The session watcher of the app, kept alive for as long as the app runs
+#[derive(Default)]
+pub struct SessionWatcherState(pub Mutex<Option<SessionWatcher>>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_session_ids_of_session_files_only() {
+        let projects = Path::new("/home/me/.claude/projects");
+        assert_eq!(
+            session_ids(projects, &projects.join("-home-me-app").join("abc.jsonl")),
+            Some(("-home-me-app".to_string(), "abc".to_string()))
+        );
+        assert_eq!(session_ids(projects, &projects.join("-home-me-app")), None);
+        assert_eq!(
+            session_ids(projects, &projects.join("-home-me-app").join("notes.txt")),
+            None
+        );
+        assert_eq!(
+            session_ids(projects, &projects.join("a").join("b").join("c.jsonl")),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_tail_keeps_incomplete_lines() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let mut tail = JsonlTail::default();
+
+        write!(file, "{{\"a\":1}}\n{{\"b\":").unwrap();
+        assert_eq!(tail.read_new_lines(file.path()).await, vec!["{\"a\":1}"]);
+
+        writeln!(file, "2}}").unwrap();
+        assert_eq!(tail.read_new_lines(file.path()).await, vec!["{\"b\":2}"]);
+        assert!(tail.read_new_lines(file.path()).await.is_empty());
+
+        // A character split across two writes
+        let line = "{\"c\":\"é\"}\n".as_bytes();
+        let split = line.iter().position(|byte| *byte >= 0x80).unwrap() + 1;
+        file.write_all(&line[..split]).unwrap();
+        assert!(tail.read_new_lines(file.path()).await.is_empty());
+        file.write_all(&line[split..]).unwrap();
+        assert_eq!(
+            tail.read_new_lines(file.path()).await,
+            vec!["{\"c\":\"é\"}"]
+        );
+    }
+}
@@ -361,40 +361,65 @@ pub async fn slash_command_save(
             return Err("Project path required for project scope".to_string());
         }
     } else {
-        dirs::home_dir()
-            .ok_or_else(|| "Could not find home directory".to_string())?
-            .join(".claude")
-            .join("commands")
+        user_commands_dir()?
     };
 
-    // Build file path
-    let mut file_path = base_dir.clone();
-    if let Some(ns) = &namespace {
+    // Build file path and create directories if needed
+    let file_path = command_file_path(&base_dir, namespace.as_deref(), &name);
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directories: {}", e))?;
+    }
+
+    // Build content with frontmatter
+    let full_content = build_command_file(&content, description.as_deref(), &allowed_tools);
+
+    // Write file
+    fs::write(&file_path, &full_content)
+        .map_err(|e| format!("Failed to write command file: {}", e))?;
+
+    // Load and return the saved command
+    load_command_from_file(&file_path, &base_dir, &scope)
+        .map_err(|e| format!("Failed to load saved command: {}", e))
+}
+
+/// Directory holding user-scope commands (`~/.claude/commands`)
+pub fn user_commands_dir() -> Result<PathBuf, String> {
+    Ok(dirs::home_dir()
+        .ok_or_else(|| "Could not find home directory".to_string())?
+        .join(".claude")
+        .join("commands"))
+}
+
+/// Path of a command's markdown file; namespace components become subdirectories
+pub fn command_file_path(base_dir: &Path, namespace: Option<&str>, name: &str) -> PathBuf {
+    let mut file_path = base_dir.to_path_buf();
+    if let Some(ns) = namespace {
         for component in ns.split(':') {
             file_path = file_path.join(component);
         }
     }
+    file_path.join(format!("{}.md", name))
+}
 
-    // Create directories if needed
-    fs::create_dir_all(&file_path).map_err(|e| format!("Failed to create directories: {}", e))?;
-
-    // Add filename
-    file_path = file_path.join(format!("{}.md", name));
-
-    // Build content with frontmatter
+/// Build the contents of a command file, with frontmatter when there is metadata
+pub fn build_command_file(
+    content: &str,
+    description: Option<&str>,
+    allowed_tools: &[String],
+) -> String {
     let mut full_content = String::new();
 
     // Add frontmatter if we have metadata
     if description.is_some() || !allowed_tools.is_empty() {
         full_content.push_str("---\n");
 
-        if let Some(desc) = &description {
+        if let Some(desc) = description {
             full_content.push_str(&format!("description: {}\n", desc));
         }
 
         if !allowed_tools.is_empty() {
             full_content.push_str("allowed-tools:\n");
-            for tool in &allowed_tools {
+            for tool in allowed_tools {
                 full_content.push_str(&format!("  - {}\n", tool));
             }
         }
@@ -402,15 +427,8 @@ pub async fn slash_command_save(
         full_content.push_str("---\n\n");
     }
 
-    full_content.push_str(&content);
-
-    // Write file
-    fs::write(&file_path, &full_content)
-        .map_err(|e| format!("Failed to write command file: {}", e))?;
-
-    // Load and return the saved command
-    load_command_from_file(&file_path, &base_dir, &scope)
-        .map_err(|e| format!("Failed to load saved command: {}", e))
+    full_content.push_str(content);
+    full_content
 }
 
 /// Delete a slash command
@@ -0,0 +1,244 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, HashMap};
+
+/// Type of an agent task parameter
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentParameterType {
+    String,
+    Path,
+    Enum,
+    Number,
+}
+
+/// A named parameter an agent's task template can refer to as `{{name}}`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AgentParameter {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub param_type: AgentParameterType,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Value used when none is given; parameters without a default are required
+    #[serde(default)]
+    pub default: Option<JsonValue>,
+    /// Allowed values of an `enum` parameter
+    #[serde(default)]
+    pub options: Vec<String>,
+}
+
+fn placeholder_regex() -> Regex {
+    Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").expect("valid placeholder regex")
+}
+
+/// Parse the JSON parameter definitions stored on an agent
+pub fn parse_parameters(parameters_json: Option<&str>) -> Result<Vec<AgentParameter>, String> {
+    match parameters_json {
+        Some(json) if !json.trim().is_empty() => {
+            serde_json::from_str(json).map_err(|e| format!("Invalid agent parameters: {}", e))
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Check that parameter definitions and a template are consistent
+///
+/// Used when saving an agent, so mistakes surface before the agent is run.
+pub fn validate_definitions(
+    parameters: &[AgentParameter],
+    template: Option<&str>,
+) -> Result<(), String> {
+    let name_regex = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").expect("valid name regex");
+
+    for (i, parameter) in parameters.iter().enumerate() {
+        if !name_regex.is_match(&parameter.name) {
+            return Err(format!("Invalid parameter name: '{}'", parameter.name));
+        }
+        if parameters[..i].iter().any(|p| p.name == parameter.name) {
+            return Err(format!("Duplicate parameter: '{}'", parameter.name));
+        }
+        if parameter.param_type == AgentParameterType::Enum && parameter.options.is_empty() {
+            return Err(format!(
+                "Enum parameter '{}' needs at least one option",
+                parameter.name
+            ));
+        }
+        if let Some(default) = &parameter.default {
+            coerce(parameter, default)?;
+        }
+    }
+
+    if let Some(template) = template {
+        for captures in placeholder_regex().captures_iter(template) {
+            let name = &captures[1];
+            if !parameters.iter().any(|p| p.name == name) {
+                return Err(format!(
+                    "Task template uses undeclared parameter '{}'",
+                    name
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate a value against its parameter definition, returning its canonical form
+fn coerce(parameter: &AgentParameter, value: &JsonValue) -> Result<JsonValue, String> {
+    let invalid = |expected: &str| {
+        format!(
+            "Parameter '{}' must be {}, got {}",
+            parameter.name, expected, value
+        )
+    };
+
+    match parameter.param_type {
+        AgentParameterType::String => value
+            .as_str()
+            .map(|s| JsonValue::String(s.to_string()))
+            .ok_or_else(|| invalid("a string")),
+        AgentParameterType::Path => value
+            .as_str()
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| JsonValue::String(s.to_string()))
+            .ok_or_else(|| invalid("a non-empty path")),
+        AgentParameterType::Number => match value {
+            JsonValue::Number(_) => Ok(value.clone()),
+            JsonValue::String(s) => {
+                let s = s.trim();
+                s.parse::<i64>()
+                    .map(JsonValue::from)
+                    .ok()
+                    .or_else(|| {
+                        s.parse::<f64>()
+                            .ok()
+                            .and_then(serde_json::Number::from_f64)
+                            .map(JsonValue::Number)
+                    })
+                    .ok_or_else(|| invalid("a number"))
+            }
+            _ => Err(invalid("a number")),
+        },
+        AgentParameterType::Enum => value
+            .as_str()
+            .filter(|s| parameter.options.iter().any(|o| o == s))
+            .map(|s| JsonValue::String(s.to_string()))
+            .ok_or_else(|| invalid(&format!("one of {}", parameter.options.join(", ")))),
+    }
+}
+
+/// Validate the given values, filling in defaults
+///
+/// Returns the complete set of values the task is rendered with.
+pub fn resolve_values(
+    parameters: &[AgentParameter],
+    values: &HashMap<String, JsonValue>,
+) -> Result<BTreeMap<String, JsonValue>, String> {
+    if let Some(unknown) = values
+        .keys()
+        .find(|name| !parameters.iter().any(|p| &p.name == *name))
+    {
+        return Err(format!("Unknown parameter: '{}'", unknown));
+    }
+
+    let mut resolved = BTreeMap::new();
+    for parameter in parameters {
+        let value = values
+            .get(&parameter.name)
+            .filter(|v| !v.is_null())
+            .or(parameter.default.as_ref())
+            .ok_or_else(|| format!("Missing required parameter: '{}'", parameter.name))?;
+        resolved.insert(parameter.name.clone(), coerce(parameter, value)?);
+    }
+
+    Ok(resolved)
+}
+
+/// Render a task template, replacing each `{{name}}` with its value
+pub fn render(template: &str, values: &BTreeMap<String, JsonValue>) -> Result<String, String> {
+    let mut missing = None;
+    let rendered = placeholder_regex().replace_all(template, |captures: &regex::Captures| {
+        match values.get(&captures[1]) {
+            Some(JsonValue::String(s)) => s.clone(),
+            Some(value) => value.to_string(),
+            None => {
+                missing.get_or_insert_with(|| captures[1].to_string());
+                String::new()
+            }
+        }
+    });
+
+    match missing {
+        Some(name) => Err(format!("No value for template parameter '{}'", name)),
+        None => Ok(rendered.into_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parameters() -> Vec<AgentParameter> {
+        parse_parameters(Some(
+            r#"[
+                {"name": "target", "type": "path"},
+                {"name": "level", "type": "enum", "options": ["quick", "thorough"], "default": "quick"},
+                {"name": "max_issues", "type": "number", "default": 10}
+            ]"#,
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_resolve_and_render() {
+        let parameters = parameters();
+        let template = "Review {{ target }} ({{level}}), report at most {{max_issues}} issues";
+        validate_definitions(&parameters, Some(template)).unwrap();
+
+        let values = HashMap::from([
+            ("target".to_string(), json!("src/lib.rs")),
+            ("max_issues".to_string(), json!("5")),
+        ]);
+        let resolved = resolve_values(&parameters, &values).unwrap();
+        assert_eq!(
+            render(template, &resolved).unwrap(),
+            "Review src/lib.rs (quick), report at most 5 issues"
+        );
+    }
+
+    #[test]
+    fn test_resolve_rejects_invalid_values() {
+        let parameters = parameters();
+
+        let err = resolve_values(&parameters, &HashMap::new()).unwrap_err();
+        assert!(err.contains("Missing required parameter: 'target'"));
+
+        let values = HashMap::from([
+            ("target".to_string(), json!("src")),
+            ("level".to_string(), json!("exhaustive")),
+        ]);
+        let err = resolve_values(&parameters, &values).unwrap_err();
+        assert!(err.contains("one of quick, thorough"));
+
+        let values = HashMap::from([
+            ("target".to_string(), json!("src")),
+            ("other".to_string(), json!("x")),
+        ]);
+        let err = resolve_values(&parameters, &values).unwrap_err();
+        assert!(err.contains("Unknown parameter: 'other'"));
+    }
+
+    #[test]
+    fn test_validate_definitions() {
+        let parameters = parameters();
+        let err = validate_definitions(&parameters, Some("Fix {{bug}}")).unwrap_err();
+        assert!(err.contains("undeclared parameter 'bug'"));
+
+        let mut duplicated = parameters.clone();
+        duplicated.push(parameters[0].clone());
+        assert!(validate_definitions(&duplicated, None).is_err());
+    }
+}
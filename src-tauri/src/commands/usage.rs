@@ -8,6 +8,8 @@ use std::fs;
 use std::path::PathBuf;
 use tauri::command;
 
+use crate::stream_event::{parse_jsonl, TokenUsage};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UsageEntry {
     timestamp: String,
@@ -76,38 +78,11 @@ const SONNET_4_OUTPUT_PRICE: f64 = 15.0;
 const SONNET_4_CACHE_WRITE_PRICE: f64 = 3.75;
 const SONNET_4_CACHE_READ_PRICE: f64 = 0.30;
 
-#[derive(Debug, Deserialize)]
-struct JsonlEntry {
-    timestamp: String,
-    message: Option<MessageData>,
-    #[serde(rename = "sessionId")]
-    session_id: Option<String>,
-    #[serde(rename = "requestId")]
-    request_id: Option<String>,
-    #[serde(rename = "costUSD")]
-    cost_usd: Option<f64>,
-}
-
-#[derive(Debug, Deserialize)]
-struct MessageData {
-    id: Option<String>,
-    model: Option<String>,
-    usage: Option<UsageData>,
-}
-
-#[derive(Debug, Deserialize)]
-struct UsageData {
-    input_tokens: Option<u64>,
-    output_tokens: Option<u64>,
-    cache_creation_input_tokens: Option<u64>,
-    cache_read_input_tokens: Option<u64>,
-}
-
-fn calculate_cost(model: &str, usage: &UsageData) -> f64 {
-    let input_tokens = usage.input_tokens.unwrap_or(0) as f64;
-    let output_tokens = usage.output_tokens.unwrap_or(0) as f64;
-    let cache_creation_tokens = usage.cache_creation_input_tokens.unwrap_or(0) as f64;
-    let cache_read_tokens = usage.cache_read_input_tokens.unwrap_or(0) as f64;
+fn calculate_cost(model: &str, usage: &TokenUsage) -> f64 {
+    let input_tokens = usage.input_tokens as f64;
+    let output_tokens = usage.output_tokens as f64;
+    let cache_creation_tokens = usage.cache_creation_input_tokens as f64;
+    let cache_read_tokens = usage.cache_read_input_tokens as f64;
 
     // Calculate cost based on model
     let (input_price, output_price, cache_write_price, cache_read_price) =
@@ -137,6 +112,11 @@ fn calculate_cost(model: &str, usage: &UsageData) -> f64 {
         + (cache_read_tokens * cache_read_price / 1_000_000.0)
 }
 
+/// Estimate the cost in USD of a single `usage` object reported by Claude Code
+pub fn estimate_usage_cost(model: &str, usage: &TokenUsage) -> f64 {
+    calculate_cost(model, usage)
+}
+
 fn parse_jsonl_file(
     path: &std::path::Path,
     encoded_project_name: &str,
@@ -154,74 +134,56 @@ fn parse_jsonl_file(
             .unwrap_or("unknown")
             .to_string();
 
-        for line in content.lines() {
-            if line.trim().is_empty() {
-                continue;
+        for line in parse_jsonl(&content) {
+            // Extract the actual project path from cwd if we haven't already
+            if actual_project_path.is_none() {
+                actual_project_path = line.cwd.clone();
             }
 
-            if let Ok(json_value) = serde_json::from_str::<serde_json::Value>(line) {
-                // Extract the actual project path from cwd if we haven't already
-                if actual_project_path.is_none() {
-                    if let Some(cwd) = json_value.get("cwd").and_then(|v| v.as_str()) {
-                        actual_project_path = Some(cwd.to_string());
-                    }
-                }
-
-                // Try to parse as JsonlEntry for usage data
-                if let Ok(entry) = serde_json::from_value::<JsonlEntry>(json_value) {
-                    if let Some(message) = &entry.message {
-                        // Deduplication based on message ID and request ID
-                        if let (Some(msg_id), Some(req_id)) = (&message.id, &entry.request_id) {
-                            let unique_hash = format!("{}:{}", msg_id, req_id);
-                            if processed_hashes.contains(&unique_hash) {
-                                continue; // Skip duplicate entry
-                            }
-                            processed_hashes.insert(unique_hash);
-                        }
+            let Some(timestamp) = line.timestamp.clone() else {
+                continue;
+            };
+            let Some((message_id, model, usage)) = line.message_usage() else {
+                continue;
+            };
 
-                        if let Some(usage) = &message.usage {
-                            // Skip entries without meaningful token usage
-                            if usage.input_tokens.unwrap_or(0) == 0
-                                && usage.output_tokens.unwrap_or(0) == 0
-                                && usage.cache_creation_input_tokens.unwrap_or(0) == 0
-                                && usage.cache_read_input_tokens.unwrap_or(0) == 0
-                            {
-                                continue;
-                            }
-
-                            let cost = entry.cost_usd.unwrap_or_else(|| {
-                                if let Some(model_str) = &message.model {
-                                    calculate_cost(model_str, usage)
-                                } else {
-                                    0.0
-                                }
-                            });
-
-                            // Use actual project path if found, otherwise use encoded name
-                            let project_path = actual_project_path
-                                .clone()
-                                .unwrap_or_else(|| encoded_project_name.to_string());
-
-                            entries.push(UsageEntry {
-                                timestamp: entry.timestamp,
-                                model: message
-                                    .model
-                                    .clone()
-                                    .unwrap_or_else(|| "unknown".to_string()),
-                                input_tokens: usage.input_tokens.unwrap_or(0),
-                                output_tokens: usage.output_tokens.unwrap_or(0),
-                                cache_creation_tokens: usage
-                                    .cache_creation_input_tokens
-                                    .unwrap_or(0),
-                                cache_read_tokens: usage.cache_read_input_tokens.unwrap_or(0),
-                                cost,
-                                session_id: entry.session_id.unwrap_or_else(|| session_id.clone()),
-                                project_path,
-                            });
-                        }
-                    }
+            // Deduplication based on message ID and request ID
+            if let (Some(msg_id), Some(req_id)) = (message_id, &line.request_id) {
+                let unique_hash = format!("{}:{}", msg_id, req_id);
+                if processed_hashes.contains(&unique_hash) {
+                    continue; // Skip duplicate entry
                 }
+                processed_hashes.insert(unique_hash);
+            }
+
+            // Skip entries without meaningful token usage
+            if usage.is_empty() {
+                continue;
             }
+
+            let cost = line
+                .cost_usd
+                .unwrap_or_else(|| model.map_or(0.0, |model| calculate_cost(model, usage)));
+
+            // Use actual project path if found, otherwise use encoded name
+            let project_path = actual_project_path
+                .clone()
+                .unwrap_or_else(|| encoded_project_name.to_string());
+
+            entries.push(UsageEntry {
+                timestamp,
+                model: model.unwrap_or("unknown").to_string(),
+                input_tokens: usage.input_tokens,
+                output_tokens: usage.output_tokens,
+                cache_creation_tokens: usage.cache_creation_input_tokens,
+                cache_read_tokens: usage.cache_read_input_tokens,
+                cost,
+                session_id: line
+                    .session_id
+                    .clone()
+                    .unwrap_or_else(|| session_id.clone()),
+                project_path,
+            });
         }
     }
 
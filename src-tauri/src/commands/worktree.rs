@@ -0,0 +1,300 @@
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A git worktree an agent run executes in, isolated from the project checkout
+///
+/// The worktree is checked out on its own branch starting at the project's
+/// `HEAD`, so uncommitted changes in the project are not visible to the agent.
+/// Whatever the agent changes is committed to that branch when the run ends,
+/// which keeps the result reviewable after the worktree itself is removed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AgentWorktree {
+    /// Directory the agent runs in (the project's location inside the worktree)
+    pub path: String,
+    /// Branch checked out in the worktree
+    pub branch: String,
+    /// Commit the branch was created from
+    pub base_commit: String,
+}
+
+/// Run git in `dir` and return its trimmed stdout
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Location of `project` inside the repository rooted at `repo_root`
+fn relative_to_root(project: &Path, repo_root: &str) -> PathBuf {
+    project
+        .canonicalize()
+        .ok()
+        .and_then(|p| {
+            let root = Path::new(repo_root).canonicalize().ok()?;
+            p.strip_prefix(root).ok().map(Path::to_path_buf)
+        })
+        .unwrap_or_default()
+}
+
+/// Create a worktree for an agent run under `worktrees_dir`
+pub fn create(
+    project_path: &str,
+    worktrees_dir: &Path,
+    run_id: i64,
+) -> Result<AgentWorktree, String> {
+    let project = Path::new(project_path);
+    let repo_root = git(project, &["rev-parse", "--show-toplevel"])
+        .map_err(|_| format!("Isolated runs require a git repository: {}", project_path))?;
+    let base_commit = git(project, &["rev-parse", "HEAD"])
+        .map_err(|_| "Isolated runs require a repository with at least one commit".to_string())?;
+
+    // Agents may be run in a subdirectory of the repository
+    let relative = relative_to_root(project, &repo_root);
+
+    std::fs::create_dir_all(worktrees_dir)
+        .map_err(|e| format!("Failed to create worktrees directory: {}", e))?;
+    let root = worktrees_dir.join(format!("run-{}", run_id));
+    let branch = format!("opcode/agent-run-{}", run_id);
+
+    git(
+        Path::new(&repo_root),
+        &[
+            "worktree",
+            "add",
+            "-b",
+            &branch,
+            &root.to_string_lossy(),
+            &base_commit,
+        ],
+    )?;
+    info!("Created worktree for agent run {} at {:?}", run_id, root);
+
+    Ok(AgentWorktree {
+        path: root.join(relative).to_string_lossy().to_string(),
+        branch,
+        base_commit,
+    })
+}
+
+/// Commit everything the agent changed to the worktree's branch
+///
+/// `exclude` lists paths (relative to the worktree path) created by opcode for
+/// the run itself, which are left out of the commit. Returns whether a commit
+/// was made.
+pub fn commit_changes(
+    worktree_path: &str,
+    message: &str,
+    exclude: &[&str],
+) -> Result<bool, String> {
+    let dir = Path::new(worktree_path);
+    let root = PathBuf::from(git(dir, &["rev-parse", "--show-toplevel"])?);
+
+    git(&root, &["add", "-A"])?;
+    for path in exclude {
+        let _ = git(dir, &["reset", "-q", "--", path]);
+    }
+    if git(&root, &["diff", "--cached", "--quiet"]).is_ok() {
+        return Ok(false);
+    }
+
+    // Fall back to a local identity when the user has none configured
+    let mut args = Vec::new();
+    if git(&root, &["config", "user.email"]).is_err() {
+        args.extend([
+            "-c",
+            "user.name=opcode",
+            "-c",
+            "user.email=opcode@localhost",
+        ]);
+    }
+    args.extend(["commit", "-q", "--no-verify", "-m", message]);
+    git(&root, &args)?;
+
+    Ok(true)
+}
+
+/// Diff of an agent run's changes against the commit its worktree started from
+///
+/// While the worktree exists this includes uncommitted changes to tracked
+/// files; afterwards the run's branch is diffed from the project.
+pub fn diff(project_path: &str, worktree: &AgentWorktree) -> Result<String, String> {
+    let worktree_dir = Path::new(&worktree.path);
+    let diff = if worktree_dir.exists() {
+        git(worktree_dir, &["diff", &worktree.base_commit])?
+    } else {
+        git(
+            Path::new(project_path),
+            &["diff", &worktree.base_commit, &worktree.branch],
+        )?
+    };
+
+    // `git` trims its output, but a patch must end with a newline to apply
+    Ok(if diff.is_empty() { diff } else { diff + "\n" })
+}
+
+/// Diff from the changes of one agent run to those of another, using the branches
+/// their changes were committed to
+pub fn diff_between(
+    project_path: &str,
+    from: &AgentWorktree,
+    to: &AgentWorktree,
+) -> Result<String, String> {
+    let diff = git(Path::new(project_path), &["diff", &from.branch, &to.branch])?;
+    Ok(if diff.is_empty() { diff } else { diff + "\n" })
+}
+
+/// Check out a removed worktree again from its branch, at the same path
+///
+/// Follow-ups of an isolated run need its worktree back: Claude keeps the
+/// session under the directory it ran in.
+pub fn restore(project_path: &str, worktree: &AgentWorktree) -> Result<(), String> {
+    let project = Path::new(project_path);
+    let repo_root = git(project, &["rev-parse", "--show-toplevel"])?;
+    let relative = relative_to_root(project, &repo_root);
+    let root = Path::new(&worktree.path)
+        .ancestors()
+        .nth(relative.components().count())
+        .ok_or_else(|| format!("Invalid worktree path: {}", worktree.path))?;
+
+    // Forget worktrees whose directory was deleted, so the branch can be checked out
+    git(Path::new(&repo_root), &["worktree", "prune"])?;
+    git(
+        Path::new(&repo_root),
+        &["worktree", "add", &root.to_string_lossy(), &worktree.branch],
+    )?;
+    info!("Restored worktree {:?} from {}", root, worktree.branch);
+
+    Ok(())
+}
+
+/// Remove a run's worktree, keeping its branch. Returns whether anything was removed.
+pub fn remove(project_path: &str, worktree_path: &str) -> Result<bool, String> {
+    let dir = Path::new(worktree_path);
+    if !dir.exists() {
+        return Ok(false);
+    }
+
+    let root = git(dir, &["rev-parse", "--show-toplevel"])?;
+    if let Err(e) = git(
+        Path::new(project_path),
+        &["worktree", "remove", "--force", &root],
+    ) {
+        // The project may have moved; drop the directory and let git prune it later
+        warn!("{}, removing worktree directory directly", e);
+        std::fs::remove_dir_all(&root)
+            .map_err(|e| format!("Failed to remove worktree {}: {}", root, e))?;
+    }
+    info!("Removed worktree {}", root);
+
+    Ok(true)
+}
+
+/// Remove the worktree and branch of a run that never started
+pub fn discard(project_path: &str, worktree: &AgentWorktree) -> Result<(), String> {
+    remove(project_path, &worktree.path)?;
+    git(Path::new(project_path), &["branch", "-D", &worktree.branch])?;
+    info!("Deleted branch {}", worktree.branch);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn init_repo(dir: &Path) {
+        for args in [
+            &["init", "-q"][..],
+            &["config", "user.name", "Test"],
+            &["config", "user.email", "test@example.com"],
+        ] {
+            git(dir, args).unwrap();
+        }
+        std::fs::write(dir.join("README.md"), "hello\n").unwrap();
+        git(dir, &["add", "-A"]).unwrap();
+        git(dir, &["commit", "-q", "-m", "init"]).unwrap();
+    }
+
+    #[test]
+    fn test_worktree_lifecycle() {
+        let repo = TempDir::new().unwrap();
+        let worktrees = TempDir::new().unwrap();
+        init_repo(repo.path());
+        let project_path = repo.path().to_string_lossy().to_string();
+
+        let worktree = create(&project_path, worktrees.path(), 7).unwrap();
+        assert_eq!(worktree.branch, "opcode/agent-run-7");
+
+        std::fs::write(Path::new(&worktree.path).join("README.md"), "changed\n").unwrap();
+        std::fs::create_dir_all(Path::new(&worktree.path).join(".claude")).unwrap();
+        std::fs::write(
+            Path::new(&worktree.path).join(".claude/settings.json"),
+            "{}",
+        )
+        .unwrap();
+
+        assert!(commit_changes(&worktree.path, "Agent run 7", &[".claude/settings.json"]).unwrap());
+        assert!(
+            !commit_changes(&worktree.path, "Agent run 7", &[".claude/settings.json"]).unwrap()
+        );
+
+        assert!(remove(&project_path, &worktree.path).unwrap());
+        assert!(!Path::new(&worktree.path).exists());
+
+        // The result stays reviewable on the branch
+        let diff = diff(&project_path, &worktree).unwrap();
+        assert!(diff.contains("+changed"));
+        assert!(!diff.contains("settings.json"));
+        // The project checkout is untouched
+        assert_eq!(
+            std::fs::read_to_string(repo.path().join("README.md")).unwrap(),
+            "hello\n"
+        );
+
+        // Follow-ups get the worktree back with the run's changes
+        restore(&project_path, &worktree).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(Path::new(&worktree.path).join("README.md")).unwrap(),
+            "changed\n"
+        );
+    }
+
+    #[test]
+    fn test_create_requires_git_repository() {
+        let dir = TempDir::new().unwrap();
+        let worktrees = TempDir::new().unwrap();
+
+        let err = create(&dir.path().to_string_lossy(), worktrees.path(), 1).unwrap_err();
+        assert!(err.contains("git repository"));
+    }
+
+    #[test]
+    fn test_discard_removes_branch() {
+        let repo = TempDir::new().unwrap();
+        let worktrees = TempDir::new().unwrap();
+        init_repo(repo.path());
+        let project_path = repo.path().to_string_lossy().to_string();
+
+        let worktree = create(&project_path, worktrees.path(), 3).unwrap();
+        discard(&project_path, &worktree).unwrap();
+
+        assert!(!Path::new(&worktree.path).exists());
+        assert!(git(repo.path(), &["rev-parse", "--verify", &worktree.branch]).is_err());
+    }
+}
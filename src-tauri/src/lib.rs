@@ -5,6 +5,7 @@ pub mod checkpoint;
 pub mod claude_binary;
 pub mod commands;
 pub mod process;
+pub mod stream_event;
 pub mod web_server;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -5,38 +5,63 @@ mod checkpoint;
 mod claude_binary;
 mod commands;
 mod process;
+mod stream_event;
 
 use checkpoint::state::CheckpointState;
+use commands::agent_batch::{execute_agent_batch, get_agent_batch, list_agent_batches};
+use commands::agent_compare::compare_agent_runs;
+use commands::agent_output::{get_agent_output_retention, save_agent_output_retention};
+use commands::agent_registry::{
+    fetch_github_agent_content, fetch_github_agents, get_agent_sources, save_agent_sources,
+};
+use commands::agent_trust::{
+    get_agent_trust_settings, preview_agent_import_from_file, save_agent_trust_settings,
+};
 use commands::agents::{
-    cleanup_finished_processes, create_agent, delete_agent, execute_agent, export_agent,
-    export_agent_to_file, fetch_github_agent_content, fetch_github_agents, get_agent,
-    get_agent_run, get_agent_run_with_real_time_metrics, get_claude_binary_path,
-    get_live_session_output, get_session_output, get_session_status, import_agent,
-    import_agent_from_file, import_agent_from_github, init_database, kill_agent_session,
-    list_agent_runs, list_agent_runs_with_metrics, list_agents, list_claude_installations,
-    list_running_sessions, load_agent_session_history, set_claude_binary_path,
-    stream_session_output, update_agent, AgentDb,
+    apply_agent_update, check_agent_updates, cleanup_finished_processes, continue_agent_run,
+    create_agent, delete_agent, diff_agent_versions, execute_agent, export_agent,
+    export_agent_to_file, get_agent, get_agent_run, get_agent_run_diff, get_agent_run_limits,
+    get_agent_run_with_real_time_metrics, get_claude_binary_path, get_live_session_output,
+    get_session_output, get_session_status, import_agent, import_agent_from_file,
+    import_agent_from_github, init_database, kill_agent_session, list_agent_runs,
+    list_agent_runs_with_metrics, list_agent_versions, list_agents, list_claude_installations,
+    list_running_sessions, load_agent_session_history, rollback_agent_version,
+    save_agent_run_limits, set_claude_binary_path, stream_session_output, update_agent, AgentDb,
+    AgentRunHost,
+};
+use commands::cancellation::{
+    get_cancellation_settings, load_cancellation_settings, save_cancellation_settings,
 };
 use commands::claude::{
     cancel_claude_execution, check_auto_checkpoint, check_claude_version, cleanup_old_checkpoints,
-    clear_checkpoint_manager, continue_claude_code, create_checkpoint, create_project,
-    execute_claude_code, find_claude_md_files, fork_from_checkpoint, get_checkpoint_diff,
-    get_checkpoint_settings, get_checkpoint_state_stats, get_claude_session_output,
-    get_claude_settings, get_home_directory, get_hooks_config, get_project_sessions,
-    get_recently_modified_files, get_session_timeline, get_system_prompt, list_checkpoints,
-    list_directory_contents, list_projects, list_running_claude_sessions, load_session_history,
-    open_new_session, read_claude_md_file, restore_checkpoint, resume_claude_code,
-    save_claude_md_file, save_claude_settings, save_system_prompt, search_files,
-    track_checkpoint_message, track_session_messages, update_checkpoint_settings,
-    update_hooks_config, validate_hook_command, ClaudeProcessState,
+    clear_checkpoint_manager, close_claude_session, continue_claude_code, create_checkpoint,
+    create_project, execute_claude_code, find_claude_md_files, fork_from_checkpoint,
+    get_checkpoint_diff, get_checkpoint_settings, get_checkpoint_state_stats,
+    get_claude_session_output, get_claude_settings, get_home_directory, get_hooks_config,
+    get_project_sessions, get_recently_modified_files, get_session_timeline, get_system_prompt,
+    interrupt_session, list_checkpoints, list_directory_contents, list_projects,
+    list_running_claude_sessions, load_session_history, open_new_session, read_claude_md_file,
+    restore_checkpoint, resume_claude_code, save_claude_md_file, save_claude_settings,
+    save_system_prompt, search_files, send_session_message, start_claude_session,
+    subscribe_claude_run_output, subscribe_claude_session_output, track_checkpoint_message,
+    track_session_messages, update_checkpoint_settings, update_hooks_config, validate_hook_command,
 };
 use commands::mcp::{
     mcp_add, mcp_add_from_claude_desktop, mcp_add_json, mcp_get, mcp_get_server_status, mcp_list,
     mcp_read_project_config, mcp_remove, mcp_reset_project_choices, mcp_save_project_config,
     mcp_serve, mcp_test_connection,
 };
+use commands::permissions::{
+    get_permission_settings, list_permission_requests, respond_permission_request,
+    save_permission_settings, PermissionBroker, PermissionBrokerState,
+};
 
 use commands::proxy::{apply_proxy_settings, get_proxy_settings, save_proxy_settings};
+use commands::recovery::{
+    get_recovery_report, get_recovery_settings, recover_agent_runs, save_recovery_settings,
+    RecoveryState,
+};
+use commands::session_watcher::{SessionEventEmitter, SessionWatcher, SessionWatcherState};
 use commands::storage::{
     storage_delete_row, storage_execute_sql, storage_insert_row, storage_list_tables,
     storage_read_table, storage_reset_database, storage_update_row,
@@ -45,8 +70,8 @@ use commands::usage::{
     get_session_stats, get_usage_by_date_range, get_usage_details, get_usage_stats,
 };
 use process::ProcessRegistryState;
-use std::sync::Mutex;
-use tauri::Manager;
+use std::sync::{Arc, Mutex};
+use tauri::{Emitter, Manager};
 
 #[cfg(target_os = "macos")]
 use window_vibrancy::{apply_vibrancy, NSVisualEffectMaterial};
@@ -118,6 +143,7 @@ fn main() {
 
             // Re-open the connection for the app to manage
             let conn = init_database(app.handle()).expect("Failed to initialize agents database");
+            let cancellation_settings = load_cancellation_settings(&conn);
             app.manage(AgentDb(Mutex::new(conn)));
 
             // Initialize checkpoint state
@@ -142,10 +168,57 @@ fn main() {
             app.manage(checkpoint_state);
 
             // Initialize process registry
-            app.manage(ProcessRegistryState::default());
+            let registry = ProcessRegistryState::default();
+            registry
+                .0
+                .set_cancellation_settings(cancellation_settings)?;
+            let registry_for_recovery = registry.0.clone();
+            app.manage(registry);
+
+            // Initialize the broker answering Claude's permission prompts
+            let host = AgentRunHost::from_app(app.handle())?;
+            app.manage(PermissionBrokerState(PermissionBroker::new(
+                &host.data_dir,
+                host.emitter,
+            )));
+
+            // Reconcile agent runs left running when the app last quit
+            app.manage(RecoveryState::default());
+            let recovery_host = AgentRunHost::from_app(app.handle())?;
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                match recover_agent_runs(&recovery_host, registry_for_recovery) {
+                    Ok(report) => {
+                        let _ = app_handle.emit("agent-runs-recovered", &report);
+                        if let Ok(mut state) = app_handle.state::<RecoveryState>().0.lock() {
+                            *state = Some(report);
+                        }
+                    }
+                    Err(e) => log::error!("Failed to recover agent runs: {}", e),
+                }
+            });
 
-            // Initialize Claude process state
-            app.manage(ClaudeProcessState::default());
+            // Watch the session files of all projects, including external sessions.
+            // The directory is created so sessions started later are seen too.
+            let projects_dir = dirs::home_dir()
+                .map(|home| home.join(".claude").join("projects"))
+                .filter(|dir| match std::fs::create_dir_all(dir) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        log::warn!("Failed to create {}: {}", dir.display(), e);
+                        false
+                    }
+                });
+            let session_watcher = projects_dir.and_then(|projects_dir| {
+                let app_handle = app.handle().clone();
+                let emitter: SessionEventEmitter = Arc::new(move |event, payload| {
+                    let _ = app_handle.emit(event, payload);
+                });
+                SessionWatcher::start(projects_dir, emitter)
+                    .map_err(|e| log::warn!("Session watcher not started: {}", e))
+                    .ok()
+            });
+            app.manage(SessionWatcherState(Mutex::new(session_watcher)));
 
             // Apply window vibrancy with rounded corners on macOS
             #[cfg(target_os = "macos")]
@@ -202,9 +275,21 @@ fn main() {
             execute_claude_code,
             continue_claude_code,
             resume_claude_code,
+            start_claude_session,
+            send_session_message,
+            interrupt_session,
+            close_claude_session,
+            get_permission_settings,
+            save_permission_settings,
+            list_permission_requests,
+            respond_permission_request,
             cancel_claude_execution,
+            get_cancellation_settings,
+            save_cancellation_settings,
             list_running_claude_sessions,
             get_claude_session_output,
+            subscribe_claude_session_output,
+            subscribe_claude_run_output,
             list_directory_contents,
             search_files,
             get_recently_modified_files,
@@ -241,12 +326,26 @@ fn main() {
             kill_agent_session,
             get_session_status,
             cleanup_finished_processes,
+            get_recovery_report,
+            get_recovery_settings,
+            save_recovery_settings,
             get_session_output,
             get_live_session_output,
             stream_session_output,
             load_agent_session_history,
             get_claude_binary_path,
             set_claude_binary_path,
+            get_agent_run_limits,
+            save_agent_run_limits,
+            get_agent_run_diff,
+            continue_agent_run,
+            compare_agent_runs,
+            execute_agent_batch,
+            get_agent_batch,
+            list_agent_batches,
+            list_agent_versions,
+            diff_agent_versions,
+            rollback_agent_version,
             list_claude_installations,
             export_agent,
             export_agent_to_file,
@@ -255,6 +354,15 @@ fn main() {
             fetch_github_agents,
             fetch_github_agent_content,
             import_agent_from_github,
+            get_agent_sources,
+            save_agent_sources,
+            check_agent_updates,
+            apply_agent_update,
+            get_agent_trust_settings,
+            save_agent_trust_settings,
+            preview_agent_import_from_file,
+            get_agent_output_retention,
+            save_agent_output_retention,
             // Usage & Analytics
             get_usage_stats,
             get_usage_by_date_range,
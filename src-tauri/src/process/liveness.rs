@@ -0,0 +1,99 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+
+/// How far the start time reported by the OS may be from the one recorded at
+/// spawn for both to describe the same process
+const START_TIME_TOLERANCE_SECS: i64 = 5;
+
+/// Whether a process with this PID still exists and is not a zombie
+pub fn pid_is_running(pid: u32) -> bool {
+    #[cfg(unix)]
+    {
+        // SAFETY: kill with signal 0 only checks that the process exists
+        if unsafe { libc::kill(pid as libc::pid_t, 0) } != 0 {
+            return false;
+        }
+        // A zombie that is not our child is never reaped by us
+        std::fs::read_to_string(format!("/proc/{}/stat", pid))
+            .map(|stat| {
+                !stat
+                    .rsplit(')')
+                    .next()
+                    .unwrap_or("")
+                    .trim_start()
+                    .starts_with('Z')
+            })
+            .unwrap_or(true)
+    }
+    #[cfg(not(unix))]
+    {
+        std::process::Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV"])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).lines().count() > 1)
+            .unwrap_or(false)
+    }
+}
+
+/// When the process with this PID was started, as reported by `ps`
+///
+/// Returns `None` if the process does not exist or the platform offers no
+/// start time.
+pub fn process_start_time(pid: u32) -> Option<DateTime<Utc>> {
+    if cfg!(target_os = "windows") {
+        return None;
+    }
+    let output = std::process::Command::new("ps")
+        .args(["-o", "lstart=", "-p", &pid.to_string()])
+        .env("LC_ALL", "C")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_lstart(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Parse the `lstart` column of `ps`, e.g. `Mon Oct  5 04:31:49 2026`, which
+/// is in local time
+fn parse_lstart(lstart: &str) -> Option<DateTime<Utc>> {
+    let lstart = lstart.split_whitespace().collect::<Vec<_>>().join(" ");
+    let naive = NaiveDateTime::parse_from_str(&lstart, "%a %b %d %H:%M:%S %Y").ok()?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Whether `pid` is still the process that was spawned at `started_at`
+///
+/// A PID whose start time differs from the recorded one has been reused by
+/// another process. When either start time is unknown, only the PID is checked.
+pub fn is_same_process(pid: u32, started_at: Option<&str>) -> bool {
+    if !pid_is_running(pid) {
+        return false;
+    }
+    let recorded = started_at.and_then(|time| DateTime::parse_from_rfc3339(time).ok());
+    match (recorded, process_start_time(pid)) {
+        (Some(recorded), Some(actual)) => {
+            (actual - recorded.with_timezone(&Utc)).num_seconds().abs() <= START_TIME_TOLERANCE_SECS
+        }
+        _ => true,
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_same_process_detects_reused_pid() {
+        let pid = std::process::id();
+        let started_at = process_start_time(pid).unwrap();
+
+        assert!(is_same_process(pid, Some(&started_at.to_rfc3339())));
+        assert!(is_same_process(pid, None));
+
+        let earlier = started_at - chrono::Duration::hours(1);
+        assert!(!is_same_process(pid, Some(&earlier.to_rfc3339())));
+    }
+}
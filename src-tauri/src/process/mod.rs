@@ -1,3 +1,9 @@
+pub mod liveness;
+pub mod output_buffer;
 pub mod registry;
+pub mod termination;
 
+pub use liveness::*;
+pub use output_buffer::*;
 pub use registry::*;
+pub use termination::*;
@@ -0,0 +1,260 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Bytes of output kept per process before the oldest lines are dropped
+pub const DEFAULT_OUTPUT_BUFFER_BYTES: usize = 8 * 1024 * 1024;
+
+/// Lines a follower may fall behind before it catches up from the buffer
+const LIVE_CAPACITY: usize = 1024;
+
+/// A line of process output with its position in the stream
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OutputLine {
+    pub seq: u64,
+    pub line: String,
+    /// Whether the process succeeded, set only on the empty line that ends the
+    /// stream of a process whose exit status was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success: Option<bool>,
+}
+
+/// Bounded buffer of the most recent output lines of a process
+///
+/// Every line gets the next sequence number, so a reader that reconnects can
+/// replay from the last line it saw and then follow new lines as they arrive.
+pub struct OutputBuffer {
+    lines: VecDeque<OutputLine>,
+    bytes: usize,
+    max_bytes: usize,
+    next_seq: u64,
+    /// Dropped once the process is gone, which ends all followers
+    live: Option<broadcast::Sender<OutputLine>>,
+    /// How the process ended, sent to followers once the buffer is closed
+    success: Option<bool>,
+}
+
+impl OutputBuffer {
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            bytes: 0,
+            max_bytes,
+            next_seq: 0,
+            live: Some(broadcast::channel(LIVE_CAPACITY).0),
+            success: None,
+        }
+    }
+
+    /// Append a line and return its sequence number, dropping the oldest lines
+    /// once the buffer is full
+    pub fn push(&mut self, line: String) -> u64 {
+        let line = OutputLine {
+            seq: self.next_seq,
+            line,
+            success: None,
+        };
+        self.next_seq += 1;
+
+        self.bytes += line.line.len();
+        while self.bytes > self.max_bytes {
+            match self.lines.pop_front() {
+                Some(old) => self.bytes -= old.line.len(),
+                None => break,
+            }
+        }
+        if let Some(live) = &self.live {
+            let _ = live.send(line.clone());
+        }
+        let seq = line.seq;
+        self.lines.push_back(line);
+        seq
+    }
+
+    /// Buffered lines with a sequence number of at least `seq`
+    pub fn since(&self, seq: u64) -> Vec<OutputLine> {
+        self.lines
+            .iter()
+            .skip_while(|line| line.seq < seq)
+            .cloned()
+            .collect()
+    }
+
+    /// Sequence number of the oldest buffered line
+    pub fn first_seq(&self) -> u64 {
+        self.lines.front().map_or(self.next_seq, |line| line.seq)
+    }
+
+    /// The buffered output as newline-terminated lines
+    pub fn text(&self) -> String {
+        let mut text = String::with_capacity(self.bytes + self.lines.len());
+        for line in &self.lines {
+            text.push_str(&line.line);
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Stop accepting followers and end the existing ones
+    pub fn close(&mut self) {
+        self.live = None;
+    }
+
+    /// Record whether the process succeeded, so followers that reach the end
+    /// of its output learn how it ended
+    pub fn set_success(&mut self, success: bool) {
+        self.success = Some(success);
+    }
+
+    /// The line that ends the stream once the buffer is closed, if the exit
+    /// status of the process was recorded
+    fn end_line(&self) -> Option<OutputLine> {
+        match (&self.live, self.success) {
+            (None, Some(success)) => Some(OutputLine {
+                seq: self.next_seq,
+                line: String::new(),
+                success: Some(success),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Default for OutputBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_OUTPUT_BUFFER_BYTES)
+    }
+}
+
+/// Send the buffered lines from `from_seq` on, then new lines as they arrive
+///
+/// Returns once the buffer is closed or `send` returns false, ending with a
+/// line that carries the exit status if one was recorded. A follower that
+/// falls behind catches up from the buffer; lines already dropped from it are
+/// skipped.
+pub async fn follow_output<F>(buffer: Arc<Mutex<OutputBuffer>>, from_seq: u64, mut send: F)
+where
+    F: FnMut(OutputLine) -> bool,
+{
+    let mut next = from_seq;
+    loop {
+        // Subscribe under the same lock as the replay so no line is missed
+        let (replay, live, end) = match buffer.lock() {
+            Ok(buffer) => (
+                buffer.since(next),
+                buffer.live.as_ref().map(|live| live.subscribe()),
+                buffer.end_line(),
+            ),
+            Err(_) => return,
+        };
+        for line in replay {
+            next = line.seq + 1;
+            if !send(line) {
+                return;
+            }
+        }
+        let Some(mut live) = live else {
+            if let Some(end) = end {
+                send(end);
+            }
+            return;
+        };
+
+        loop {
+            match live.recv().await {
+                Ok(line) if line.seq < next => {}
+                Ok(line) => {
+                    next = line.seq + 1;
+                    if !send(line) {
+                        return;
+                    }
+                }
+                // Lagged or closed: catch up from the buffer, which also ends
+                // the stream once it is closed
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_buffer_drops_oldest_lines() {
+        let mut buffer = OutputBuffer::new(10);
+        for line in ["aaaa", "bbbb", "cccc"] {
+            buffer.push(line.to_string());
+        }
+
+        assert_eq!(buffer.first_seq(), 1);
+        assert_eq!(buffer.text(), "bbbb\ncccc\n");
+        assert_eq!(
+            buffer.since(2),
+            vec![OutputLine {
+                seq: 2,
+                line: "cccc".to_string(),
+                success: None,
+            }]
+        );
+        assert_eq!(buffer.since(0).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_follow_output_replays_then_follows() {
+        let buffer = Arc::new(Mutex::new(OutputBuffer::default()));
+        buffer.lock().unwrap().push("first".to_string());
+        buffer.lock().unwrap().push("second".to_string());
+
+        let follower = {
+            let buffer = buffer.clone();
+            tokio::spawn(async move {
+                let mut seen = Vec::new();
+                follow_output(buffer, 1, |line| {
+                    seen.push(line.seq);
+                    true
+                })
+                .await;
+                seen
+            })
+        };
+        tokio::task::yield_now().await;
+        buffer.lock().unwrap().push("third".to_string());
+        buffer.lock().unwrap().close();
+
+        assert_eq!(follower.await.unwrap(), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_follow_output_ends_with_exit_status() {
+        let buffer = Arc::new(Mutex::new(OutputBuffer::default()));
+        buffer.lock().unwrap().push("first".to_string());
+
+        let follow = |buffer: Arc<Mutex<OutputBuffer>>| {
+            tokio::spawn(async move {
+                let mut seen = Vec::new();
+                follow_output(buffer, 0, |line| {
+                    seen.push((line.seq, line.success));
+                    true
+                })
+                .await;
+                seen
+            })
+        };
+        let follower = follow(buffer.clone());
+        tokio::task::yield_now().await;
+        {
+            let mut buffer = buffer.lock().unwrap();
+            buffer.push("second".to_string());
+            buffer.set_success(true);
+            buffer.close();
+        }
+
+        let expected = vec![(0, None), (1, None), (2, Some(true))];
+        assert_eq!(follower.await.unwrap(), expected);
+        // Subscribers that attach after the exit still get the whole stream
+        assert_eq!(follow(buffer).await.unwrap(), expected);
+    }
+}
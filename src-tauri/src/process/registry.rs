@@ -2,8 +2,13 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::process::ExitStatus;
 use std::sync::{Arc, Mutex};
-use tokio::process::Child;
+use tokio::process::{Child, ChildStdin};
+
+use super::liveness::pid_is_running;
+use super::output_buffer::OutputBuffer;
+use super::termination::{terminate, CancellationSettings, TerminationStage};
 
 /// Type of process being tracked
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,13 +34,28 @@ pub struct ProcessInfo {
 pub struct ProcessHandle {
     pub info: ProcessInfo,
     pub child: Arc<Mutex<Option<Child>>>,
-    pub live_output: Arc<Mutex<String>>,
+    /// The most recent output lines, for readers that attach mid-run
+    pub live_output: Arc<Mutex<OutputBuffer>>,
+    /// Input of sessions that read stream-json messages from stdin
+    pub stdin: Arc<tokio::sync::Mutex<Option<ChildStdin>>>,
+}
+
+impl Drop for ProcessHandle {
+    fn drop(&mut self) {
+        // End the readers following the output of the process
+        if let Ok(mut live_output) = self.live_output.lock() {
+            live_output.close();
+        }
+    }
 }
 
 /// Registry for tracking active agent processes
 pub struct ProcessRegistry {
     processes: Arc<Mutex<HashMap<i64, ProcessHandle>>>, // run_id -> ProcessHandle
     next_id: Arc<Mutex<i64>>, // Auto-incrementing ID for non-agent processes
+    cancellation: Mutex<CancellationSettings>,
+    /// Output of processes that have exited, for subscribers that attach late
+    finished_output: Mutex<HashMap<i64, Arc<Mutex<OutputBuffer>>>>,
 }
 
 impl ProcessRegistry {
@@ -43,9 +63,24 @@ impl ProcessRegistry {
         Self {
             processes: Arc::new(Mutex::new(HashMap::new())),
             next_id: Arc::new(Mutex::new(1000000)), // Start at high number to avoid conflicts
+            cancellation: Mutex::new(CancellationSettings::default()),
+            finished_output: Mutex::new(HashMap::new()),
         }
     }
 
+    /// Grace periods used when cancelling a process
+    pub fn cancellation_settings(&self) -> CancellationSettings {
+        self.cancellation
+            .lock()
+            .map(|settings| *settings)
+            .unwrap_or_default()
+    }
+
+    pub fn set_cancellation_settings(&self, settings: CancellationSettings) -> Result<(), String> {
+        *self.cancellation.lock().map_err(|e| e.to_string())? = settings;
+        Ok(())
+    }
+
     /// Generate a unique ID for non-agent processes
     pub fn generate_id(&self) -> Result<i64, String> {
         let mut next_id = self.next_id.lock().map_err(|e| e.to_string())?;
@@ -114,27 +149,33 @@ impl ProcessRegistry {
         let process_handle = ProcessHandle {
             info: process_info,
             child: Arc::new(Mutex::new(None)), // No tokio::process::Child handle for sidecar
-            live_output: Arc::new(Mutex::new(String::new())),
+            live_output: Arc::new(Mutex::new(OutputBuffer::default())),
+            stdin: Arc::new(tokio::sync::Mutex::new(None)),
         };
 
         processes.insert(run_id, process_handle);
         Ok(())
     }
 
-    /// Register a new Claude session (without child process - handled separately)
+    /// Register a new interactive Claude session and return its run ID
+    ///
+    /// The session ID stays empty until Claude reports it in its init message;
+    /// see `set_claude_session_id`.
     pub fn register_claude_session(
         &self,
-        session_id: String,
         pid: u32,
         project_path: String,
         task: String,
         model: String,
+        child: Child,
     ) -> Result<i64, String> {
         let run_id = self.generate_id()?;
 
         let process_info = ProcessInfo {
             run_id,
-            process_type: ProcessType::ClaudeSession { session_id },
+            process_type: ProcessType::ClaudeSession {
+                session_id: String::new(),
+            },
             pid,
             started_at: Utc::now(),
             project_path,
@@ -142,19 +183,21 @@ impl ProcessRegistry {
             model,
         };
 
-        // Register without child - Claude sessions use ClaudeProcessState for process management
-        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
-
-        let process_handle = ProcessHandle {
-            info: process_info,
-            child: Arc::new(Mutex::new(None)), // No child handle for Claude sessions
-            live_output: Arc::new(Mutex::new(String::new())),
-        };
-
-        processes.insert(run_id, process_handle);
+        self.register_process_internal(run_id, process_info, child)?;
         Ok(run_id)
     }
 
+    /// Record the session ID Claude reported for a registered session
+    pub fn set_claude_session_id(&self, run_id: i64, session_id: &str) -> Result<(), String> {
+        let mut processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get_mut(&run_id) {
+            if let ProcessType::ClaudeSession { session_id: sid } = &mut handle.info.process_type {
+                *sid = session_id.to_string();
+            }
+        }
+        Ok(())
+    }
+
     /// Internal method to register any process
     fn register_process_internal(
         &self,
@@ -167,7 +210,8 @@ impl ProcessRegistry {
         let process_handle = ProcessHandle {
             info: process_info,
             child: Arc::new(Mutex::new(Some(child))),
-            live_output: Arc::new(Mutex::new(String::new())),
+            live_output: Arc::new(Mutex::new(OutputBuffer::default())),
+            stdin: Arc::new(tokio::sync::Mutex::new(None)),
         };
 
         processes.insert(run_id, process_handle);
@@ -240,200 +284,67 @@ impl ProcessRegistry {
 
     /// Kill a running process with proper cleanup
     pub async fn kill_process(&self, run_id: i64) -> Result<bool, String> {
-        use log::{error, info, warn};
+        Ok(self.terminate_process(run_id).await?.is_some())
+    }
+
+    /// Stop a running process and its process group, escalating from SIGINT to
+    /// SIGKILL, and return the stage that ended it
+    ///
+    /// Returns `None` if the process is not in the registry.
+    pub async fn terminate_process(&self, run_id: i64) -> Result<Option<TerminationStage>, String> {
+        use log::{info, warn};
 
-        // First check if the process exists and get its PID
         let (pid, child_arc) = {
             let processes = self.processes.lock().map_err(|e| e.to_string())?;
             if let Some(handle) = processes.get(&run_id) {
                 (handle.info.pid, handle.child.clone())
             } else {
                 warn!("Process {} not found in registry", run_id);
-                return Ok(false); // Process not found
+                return Ok(None);
             }
         };
 
-        info!(
-            "Attempting graceful shutdown of process {} (PID: {})",
-            run_id, pid
-        );
+        info!("Cancelling process {} (PID: {})", run_id, pid);
 
-        // Send kill signal to the process
-        let kill_sent = {
-            let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
-            if let Some(child) = child_guard.as_mut() {
-                match child.start_kill() {
-                    Ok(_) => {
-                        info!("Successfully sent kill signal to process {}", run_id);
-                        true
-                    }
-                    Err(e) => {
-                        error!("Failed to send kill signal to process {}: {}", run_id, e);
-                        // Don't return error here, try fallback method
-                        false
-                    }
-                }
-            } else {
-                warn!(
-                    "No child handle available for process {} (PID: {}), attempting system kill",
-                    run_id, pid
-                );
-                false // Process handle not available, try fallback
-            }
-        };
+        // Processes registered without a child handle, e.g. runs adopted after a
+        // restart, can only be watched by their PID
+        let owns_child = child_arc.lock().map_err(|e| e.to_string())?.is_some();
 
-        // If direct kill didn't work, try system command as fallback
-        if !kill_sent {
-            info!(
-                "Attempting fallback kill for process {} (PID: {})",
-                run_id, pid
-            );
-            match self.kill_process_by_pid(run_id, pid) {
-                Ok(true) => return Ok(true),
-                Ok(false) => warn!(
-                    "Fallback kill also failed for process {} (PID: {})",
-                    run_id, pid
-                ),
-                Err(e) => error!("Error during fallback kill: {}", e),
-            }
-            // Continue with the rest of the cleanup even if fallback failed
-        }
-
-        // Wait for the process to exit (with timeout)
-        let wait_result = tokio::time::timeout(tokio::time::Duration::from_secs(5), async {
-            loop {
-                // Check if process has exited
-                let status = {
-                    let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
-                    if let Some(child) = child_guard.as_mut() {
-                        match child.try_wait() {
-                            Ok(Some(status)) => {
-                                info!("Process {} exited with status: {:?}", run_id, status);
-                                *child_guard = None; // Clear the child handle
-                                Some(Ok::<(), String>(()))
-                            }
-                            Ok(None) => {
-                                // Still running
-                                None
-                            }
-                            Err(e) => {
-                                error!("Error checking process status: {}", e);
-                                Some(Err(e.to_string()))
-                            }
-                        }
-                    } else {
-                        // Process already gone
-                        Some(Ok(()))
-                    }
-                };
-
-                match status {
-                    Some(result) => return result,
-                    None => {
-                        // Still running, wait a bit
-                        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                    }
-                }
-            }
-        })
-        .await;
-
-        match wait_result {
-            Ok(Ok(_)) => {
-                info!("Process {} exited gracefully", run_id);
-            }
-            Ok(Err(e)) => {
-                error!("Error waiting for process {}: {}", run_id, e);
-            }
-            Err(_) => {
-                warn!("Process {} didn't exit within 5 seconds after kill", run_id);
-                // Force clear the handle
-                if let Ok(mut child_guard) = child_arc.lock() {
+        // The monitor task may reap the child while we wait, clearing the handle
+        let has_exited = || match child_arc.lock() {
+            Ok(mut child_guard) => match child_guard.as_mut().map(|child| child.try_wait()) {
+                Some(Ok(None)) => false,
+                Some(_) => {
                     *child_guard = None;
+                    true
                 }
-                // One more attempt with system kill
-                let _ = self.kill_process_by_pid(run_id, pid);
-            }
-        }
+                None => owns_child || !pid_is_running(pid),
+            },
+            Err(_) => true,
+        };
+        let stage = terminate(pid, &self.cancellation_settings(), has_exited).await;
+        info!("Process {} ended at stage {:?}", run_id, stage);
 
         // Remove from registry after killing
         self.unregister_process(run_id)?;
 
-        Ok(true)
+        Ok(Some(stage))
     }
 
-    /// Kill a process by PID using system commands (fallback method)
-    pub fn kill_process_by_pid(&self, run_id: i64, pid: u32) -> Result<bool, String> {
-        use log::{error, info, warn};
-
-        info!("Attempting to kill process {} by PID {}", run_id, pid);
+    /// Stop a process by PID when the registry holds no child handle for it
+    /// (fallback method)
+    pub async fn kill_process_by_pid(
+        &self,
+        run_id: i64,
+        pid: u32,
+    ) -> Result<TerminationStage, String> {
+        log::info!("Attempting to kill process {} by PID {}", run_id, pid);
 
-        let kill_result = if cfg!(target_os = "windows") {
-            std::process::Command::new("taskkill")
-                .args(["/F", "/PID", &pid.to_string()])
-                .output()
-        } else {
-            // First try SIGTERM
-            let term_result = std::process::Command::new("kill")
-                .args(["-TERM", &pid.to_string()])
-                .output();
-
-            match &term_result {
-                Ok(output) if output.status.success() => {
-                    info!("Sent SIGTERM to PID {}", pid);
-                    // Give it 2 seconds to exit gracefully
-                    std::thread::sleep(std::time::Duration::from_secs(2));
-
-                    // Check if still running
-                    let check_result = std::process::Command::new("kill")
-                        .args(["-0", &pid.to_string()])
-                        .output();
-
-                    if let Ok(output) = check_result {
-                        if output.status.success() {
-                            // Still running, send SIGKILL
-                            warn!(
-                                "Process {} still running after SIGTERM, sending SIGKILL",
-                                pid
-                            );
-                            std::process::Command::new("kill")
-                                .args(["-KILL", &pid.to_string()])
-                                .output()
-                        } else {
-                            term_result
-                        }
-                    } else {
-                        term_result
-                    }
-                }
-                _ => {
-                    // SIGTERM failed, try SIGKILL directly
-                    warn!("SIGTERM failed for PID {}, trying SIGKILL", pid);
-                    std::process::Command::new("kill")
-                        .args(["-KILL", &pid.to_string()])
-                        .output()
-                }
-            }
-        };
+        let stage = terminate(pid, &self.cancellation_settings(), || !pid_is_running(pid)).await;
+        log::info!("PID {} ended at stage {:?}", pid, stage);
 
-        match kill_result {
-            Ok(output) => {
-                if output.status.success() {
-                    info!("Successfully killed process with PID {}", pid);
-                    // Remove from registry
-                    self.unregister_process(run_id)?;
-                    Ok(true)
-                } else {
-                    let error_msg = String::from_utf8_lossy(&output.stderr);
-                    warn!("Failed to kill PID {}: {}", pid, error_msg);
-                    Ok(false)
-                }
-            }
-            Err(e) => {
-                error!("Failed to execute kill command for PID {}: {}", pid, e);
-                Err(format!("Failed to execute kill command: {}", e))
-            }
-        }
+        self.unregister_process(run_id)?;
+        Ok(stage)
     }
 
     /// Check if a process is still running by trying to get its status
@@ -471,13 +382,82 @@ impl ProcessRegistry {
         }
     }
 
+    /// Check whether the registry still holds the child handle for a process,
+    /// i.e. it has not been reaped or killed yet
+    pub fn has_child_handle(&self, run_id: i64) -> Result<bool, String> {
+        let processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get(&run_id) {
+            let child_guard = handle.child.lock().map_err(|e| e.to_string())?;
+            Ok(child_guard.is_some())
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Wait for a registered process to exit and return its exit status
+    ///
+    /// Returns `None` if the process was killed or unregistered before it could be reaped.
+    pub async fn wait_for_exit(&self, run_id: i64) -> Result<Option<ExitStatus>, String> {
+        loop {
+            let child_arc = {
+                let processes = self.processes.lock().map_err(|e| e.to_string())?;
+                match processes.get(&run_id) {
+                    Some(handle) => handle.child.clone(),
+                    None => return Ok(None),
+                }
+            };
+
+            {
+                let mut child_guard = child_arc.lock().map_err(|e| e.to_string())?;
+                match child_guard.as_mut() {
+                    Some(child) => match child.try_wait() {
+                        Ok(Some(status)) => {
+                            *child_guard = None;
+                            return Ok(Some(status));
+                        }
+                        Ok(None) => {}
+                        Err(e) => {
+                            *child_guard = None;
+                            return Err(e.to_string());
+                        }
+                    },
+                    None => return Ok(None),
+                }
+            }
+
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Keep the stdin of a process open for `get_stdin`
+    pub async fn set_stdin(&self, run_id: i64, stdin: ChildStdin) -> Result<(), String> {
+        let stdin_arc = {
+            let processes = self.processes.lock().map_err(|e| e.to_string())?;
+            match processes.get(&run_id) {
+                Some(handle) => handle.stdin.clone(),
+                None => return Err(format!("Process {} not found", run_id)),
+            }
+        };
+        *stdin_arc.lock().await = Some(stdin);
+        Ok(())
+    }
+
+    /// The stdin of a process, if it was kept open with `set_stdin`; closing it
+    /// is done by taking it out of the returned slot
+    pub fn get_stdin(
+        &self,
+        run_id: i64,
+    ) -> Result<Option<Arc<tokio::sync::Mutex<Option<ChildStdin>>>>, String> {
+        let processes = self.processes.lock().map_err(|e| e.to_string())?;
+        Ok(processes.get(&run_id).map(|handle| handle.stdin.clone()))
+    }
+
     /// Append to live output for a process
     pub fn append_live_output(&self, run_id: i64, output: &str) -> Result<(), String> {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
         if let Some(handle) = processes.get(&run_id) {
             let mut live_output = handle.live_output.lock().map_err(|e| e.to_string())?;
-            live_output.push_str(output);
-            live_output.push('\n');
+            live_output.push(output.to_string());
         }
         Ok(())
     }
@@ -487,12 +467,59 @@ impl ProcessRegistry {
         let processes = self.processes.lock().map_err(|e| e.to_string())?;
         if let Some(handle) = processes.get(&run_id) {
             let live_output = handle.live_output.lock().map_err(|e| e.to_string())?;
-            Ok(live_output.clone())
+            Ok(live_output.text())
         } else {
             Ok(String::new())
         }
     }
 
+    /// Get the output buffer of a process, to replay and follow its output
+    ///
+    /// Buffers kept by `finish_output` stay available after the process is
+    /// unregistered, until `forget_output`.
+    pub fn get_output_buffer(
+        &self,
+        run_id: i64,
+    ) -> Result<Option<Arc<Mutex<OutputBuffer>>>, String> {
+        let processes = self.processes.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = processes.get(&run_id) {
+            return Ok(Some(handle.live_output.clone()));
+        }
+        let finished_output = self.finished_output.lock().map_err(|e| e.to_string())?;
+        Ok(finished_output.get(&run_id).cloned())
+    }
+
+    /// Record whether a process succeeded and keep its output once it is
+    /// unregistered, so subscribers that attach late still replay it and learn
+    /// how it ended
+    pub fn finish_output(&self, run_id: i64, success: bool) -> Result<(), String> {
+        let buffer = {
+            let processes = self.processes.lock().map_err(|e| e.to_string())?;
+            match processes.get(&run_id) {
+                Some(handle) => handle.live_output.clone(),
+                None => return Ok(()),
+            }
+        };
+        buffer
+            .lock()
+            .map_err(|e| e.to_string())?
+            .set_success(success);
+        self.finished_output
+            .lock()
+            .map_err(|e| e.to_string())?
+            .insert(run_id, buffer);
+        Ok(())
+    }
+
+    /// Drop the output kept by `finish_output`
+    pub fn forget_output(&self, run_id: i64) -> Result<(), String> {
+        self.finished_output
+            .lock()
+            .map_err(|e| e.to_string())?
+            .remove(&run_id);
+        Ok(())
+    }
+
     /// Cleanup finished processes
     #[allow(dead_code)]
     pub async fn cleanup_finished_processes(&self) -> Result<Vec<i64>, String> {
@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How long a cancelled process gets at each stage before the next signal
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CancellationSettings {
+    /// Time after SIGINT for the CLI to write its final result and exit
+    pub interrupt_grace_ms: u64,
+    /// Time after SIGTERM before the process group is killed
+    pub terminate_grace_ms: u64,
+}
+
+impl Default for CancellationSettings {
+    fn default() -> Self {
+        Self {
+            interrupt_grace_ms: 5000,
+            terminate_grace_ms: 2000,
+        }
+    }
+}
+
+/// The signal that ended a cancelled process
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminationStage {
+    /// The process was gone before any signal was sent
+    AlreadyExited,
+    /// It exited after SIGINT, having had the chance to flush its output
+    Interrupt,
+    /// It exited after SIGTERM
+    Terminate,
+    /// It had to be killed
+    Kill,
+}
+
+/// How often a signalled process is checked for exit
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Start the command in a process group of its own
+///
+/// Cancelling then reaches the tools and MCP servers it spawned as well, and
+/// a Ctrl-C in the terminal running the app is not forwarded to it.
+pub fn isolate_process_group(cmd: &mut tokio::process::Command) {
+    #[cfg(unix)]
+    cmd.process_group(0);
+    #[cfg(not(unix))]
+    let _ = cmd;
+}
+
+/// Send `signal` to the group led by `pid`, or to `pid` alone if it does not
+/// lead a group
+#[cfg(unix)]
+fn send_signal(pid: u32, signal: libc::c_int, group: bool) -> bool {
+    let target = if group {
+        -(pid as libc::pid_t)
+    } else {
+        pid as libc::pid_t
+    };
+    // SAFETY: kill has no memory safety requirements
+    unsafe { libc::kill(target, signal) == 0 }
+}
+
+/// Whether `pid` leads its own process group
+#[cfg(unix)]
+fn leads_process_group(pid: u32) -> bool {
+    // SAFETY: getpgid has no memory safety requirements
+    unsafe { libc::getpgid(pid as libc::pid_t) == pid as libc::pid_t }
+}
+
+/// Poll `has_exited` until it returns true or `grace` has passed
+async fn wait_for<F>(grace: Duration, has_exited: &mut F) -> bool
+where
+    F: FnMut() -> bool,
+{
+    let deadline = tokio::time::Instant::now() + grace;
+    loop {
+        if has_exited() {
+            return true;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+    }
+}
+
+/// Stop the process `pid` and everything in its process group
+///
+/// Sends SIGINT so the Claude CLI can write its final result, then SIGTERM and
+/// SIGKILL once the grace periods in `settings` have passed. Whatever is left
+/// in the group after the process exits is killed. `has_exited` reports
+/// whether the process is gone.
+pub async fn terminate<F>(
+    pid: u32,
+    settings: &CancellationSettings,
+    mut has_exited: F,
+) -> TerminationStage
+where
+    F: FnMut() -> bool,
+{
+    if has_exited() {
+        return TerminationStage::AlreadyExited;
+    }
+
+    #[cfg(unix)]
+    {
+        let group = leads_process_group(pid);
+        let stages = [
+            (
+                libc::SIGINT,
+                TerminationStage::Interrupt,
+                settings.interrupt_grace_ms,
+            ),
+            (
+                libc::SIGTERM,
+                TerminationStage::Terminate,
+                settings.terminate_grace_ms,
+            ),
+        ];
+
+        let mut stage = TerminationStage::Kill;
+        for (signal, signal_stage, grace_ms) in stages {
+            log::info!("Sending signal {} to PID {}", signal, pid);
+            if !send_signal(pid, signal, group) {
+                // Nothing left to signal
+                stage = signal_stage;
+                break;
+            }
+            if wait_for(Duration::from_millis(grace_ms), &mut has_exited).await {
+                stage = signal_stage;
+                break;
+            }
+        }
+
+        if stage == TerminationStage::Kill {
+            log::warn!("PID {} is still running, killing it", pid);
+            send_signal(pid, libc::SIGKILL, group);
+            wait_for(
+                Duration::from_millis(settings.terminate_grace_ms),
+                &mut has_exited,
+            )
+            .await;
+        }
+        if group {
+            // Reap the tools and servers the process left behind
+            send_signal(pid, libc::SIGKILL, true);
+        }
+        stage
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = std::process::Command::new("taskkill")
+            .args(["/F", "/T", "/PID", &pid.to_string()])
+            .output();
+        wait_for(
+            Duration::from_millis(settings.terminate_grace_ms),
+            &mut has_exited,
+        )
+        .await;
+        TerminationStage::Kill
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_terminate_interrupts_process_group() {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.args(["-c", "sleep 30 & wait"]);
+        isolate_process_group(&mut cmd);
+        let mut child = cmd.spawn().unwrap();
+        let pid = child.id().unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let stage = terminate(pid, &CancellationSettings::default(), || {
+            matches!(child.try_wait(), Ok(Some(_)))
+        })
+        .await;
+
+        assert_eq!(stage, TerminationStage::Interrupt);
+    }
+}
@@ -0,0 +1,438 @@
+//! Typed model of the stream-json output of the Claude CLI
+//!
+//! The same message format is written to the session JSONL files under
+//! `~/.claude/projects`, so both are parsed here. Parsing is tolerant: lines
+//! that aren't JSON are skipped, unknown message types and content blocks are
+//! ignored, and missing or null fields fall back to defaults.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+/// Token counts of one API response, or of a whole run in a `result` message
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+}
+
+impl TokenUsage {
+    fn from_value(usage: &JsonValue) -> Option<Self> {
+        let usage = usage.as_object()?;
+        let tokens = |key: &str| usage.get(key).and_then(|t| t.as_u64()).unwrap_or(0);
+        Some(Self {
+            input_tokens: tokens("input_tokens"),
+            output_tokens: tokens("output_tokens"),
+            cache_creation_input_tokens: tokens("cache_creation_input_tokens"),
+            cache_read_input_tokens: tokens("cache_read_input_tokens"),
+        })
+    }
+
+    /// Input and output tokens, without cache tokens
+    pub fn input_output(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+
+    /// All tokens, including cache writes and reads
+    pub fn total(&self) -> u64 {
+        self.input_output() + self.cache_creation_input_tokens + self.cache_read_input_tokens
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+}
+
+/// The final `result` message of a run or turn
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RunResult {
+    /// `success`, or an error such as `error_max_turns`
+    pub subtype: String,
+    pub is_error: bool,
+    pub result: Option<String>,
+    pub cost_usd: Option<f64>,
+    pub duration_ms: Option<i64>,
+    pub num_turns: Option<i64>,
+    /// Usage of the whole run
+    pub usage: Option<TokenUsage>,
+}
+
+/// Something that happened in a Claude session
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    /// The session started
+    SystemInit {
+        session_id: String,
+        model: Option<String>,
+        cwd: Option<String>,
+        tools: Vec<String>,
+    },
+    /// Text of a prompt
+    UserText { text: String },
+    /// Text written by the assistant
+    AssistantText { text: String },
+    /// A tool call made by the assistant
+    ToolUse {
+        id: String,
+        name: String,
+        input: JsonValue,
+    },
+    /// The output of a tool call, reported back in a user message
+    ToolResult {
+        tool_use_id: String,
+        content: String,
+        is_error: bool,
+    },
+    /// Token usage of an assistant message; repeated for every content block
+    /// of the same message
+    Usage {
+        message_id: Option<String>,
+        model: Option<String>,
+        usage: TokenUsage,
+    },
+    /// The run or turn finished
+    Result(RunResult),
+}
+
+/// One line of stream-json output or of a session file
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamLine {
+    /// The message `type`, e.g. `system`, `assistant`, `user` or `result`
+    pub kind: String,
+    pub subtype: Option<String>,
+    pub session_id: Option<String>,
+    /// Raw timestamp, only present in session files
+    pub timestamp: Option<String>,
+    pub cwd: Option<String>,
+    pub request_id: Option<String>,
+    /// Cost recorded by older CLI versions in session files
+    pub cost_usd: Option<f64>,
+    pub events: Vec<StreamEvent>,
+}
+
+fn string(value: &JsonValue, key: &str) -> Option<String> {
+    value.get(key).and_then(|v| v.as_str()).map(str::to_string)
+}
+
+/// Text of a tool result, which is either a string or a list of text blocks
+fn tool_result_text(content: &JsonValue) -> String {
+    match content {
+        JsonValue::String(text) => text.clone(),
+        JsonValue::Array(blocks) => blocks
+            .iter()
+            .filter_map(|block| block.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        _ => String::new(),
+    }
+}
+
+impl StreamLine {
+    /// Parse a line; `None` for blank lines and lines that aren't JSON objects
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+        serde_json::from_str::<JsonValue>(line)
+            .ok()
+            .and_then(|value| Self::from_value(&value))
+    }
+
+    /// Interpret an already parsed message; `None` if it isn't a JSON object
+    pub fn from_value(value: &JsonValue) -> Option<Self> {
+        value.as_object()?;
+
+        let kind = string(value, "type").unwrap_or_default();
+        let subtype = string(value, "subtype");
+        // stream-json uses "session_id", session files "sessionId"
+        let session_id = string(value, "session_id").or_else(|| string(value, "sessionId"));
+        let message = value.get("message").unwrap_or(&JsonValue::Null);
+
+        let mut events = Vec::new();
+        match kind.as_str() {
+            "system" if subtype.as_deref() == Some("init") => {
+                if let Some(session_id) = &session_id {
+                    events.push(StreamEvent::SystemInit {
+                        session_id: session_id.clone(),
+                        model: string(value, "model"),
+                        cwd: string(value, "cwd"),
+                        tools: value
+                            .get("tools")
+                            .and_then(|t| t.as_array())
+                            .map(|tools| {
+                                tools
+                                    .iter()
+                                    .filter_map(|t| t.as_str().map(str::to_string))
+                                    .collect()
+                            })
+                            .unwrap_or_default(),
+                    });
+                }
+            }
+            "user" | "assistant" => {
+                let content = message.get("content").unwrap_or(&JsonValue::Null);
+                if let Some(text) = content.as_str() {
+                    if kind == "user" {
+                        events.push(StreamEvent::UserText {
+                            text: text.to_string(),
+                        });
+                    }
+                }
+                for block in content.as_array().into_iter().flatten() {
+                    match block.get("type").and_then(|t| t.as_str()) {
+                        Some("text") => {
+                            let text = string(block, "text").unwrap_or_default();
+                            events.push(if kind == "user" {
+                                StreamEvent::UserText { text }
+                            } else {
+                                StreamEvent::AssistantText { text }
+                            });
+                        }
+                        Some("tool_use") => events.push(StreamEvent::ToolUse {
+                            id: string(block, "id").unwrap_or_default(),
+                            name: string(block, "name").unwrap_or_default(),
+                            input: block.get("input").cloned().unwrap_or(JsonValue::Null),
+                        }),
+                        Some("tool_result") => events.push(StreamEvent::ToolResult {
+                            tool_use_id: string(block, "tool_use_id").unwrap_or_default(),
+                            content: block
+                                .get("content")
+                                .map(tool_result_text)
+                                .unwrap_or_default(),
+                            is_error: block
+                                .get("is_error")
+                                .and_then(|e| e.as_bool())
+                                .unwrap_or(false),
+                        }),
+                        _ => {}
+                    }
+                }
+                if let Some(usage) = message.get("usage").and_then(TokenUsage::from_value) {
+                    events.push(StreamEvent::Usage {
+                        message_id: string(message, "id"),
+                        model: string(message, "model"),
+                        usage,
+                    });
+                }
+            }
+            "result" => events.push(StreamEvent::Result(RunResult {
+                subtype: subtype.clone().unwrap_or_else(|| "success".to_string()),
+                is_error: value
+                    .get("is_error")
+                    .and_then(|e| e.as_bool())
+                    .unwrap_or(false),
+                result: string(value, "result"),
+                cost_usd: value
+                    .get("total_cost_usd")
+                    .or_else(|| value.get("cost_usd"))
+                    .and_then(|c| c.as_f64()),
+                duration_ms: value.get("duration_ms").and_then(|d| d.as_i64()),
+                num_turns: value.get("num_turns").and_then(|n| n.as_i64()),
+                usage: value.get("usage").and_then(TokenUsage::from_value),
+            })),
+            _ => {}
+        }
+
+        Some(Self {
+            kind,
+            subtype,
+            session_id,
+            timestamp: string(value, "timestamp"),
+            cwd: string(value, "cwd"),
+            request_id: string(value, "requestId"),
+            cost_usd: value.get("costUSD").and_then(|c| c.as_f64()),
+            events,
+        })
+    }
+
+    /// The session ID announced by a `system` `init` message
+    pub fn init_session_id(&self) -> Option<&str> {
+        self.events.iter().find_map(|event| match event {
+            StreamEvent::SystemInit { session_id, .. } => Some(session_id.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn result(&self) -> Option<&RunResult> {
+        self.events.iter().find_map(|event| match event {
+            StreamEvent::Result(result) => Some(result),
+            _ => None,
+        })
+    }
+
+    /// Name and input of each tool call
+    pub fn tool_uses(&self) -> impl Iterator<Item = (&str, &JsonValue)> {
+        self.events.iter().filter_map(|event| match event {
+            StreamEvent::ToolUse { name, input, .. } => Some((name.as_str(), input)),
+            _ => None,
+        })
+    }
+
+    /// The usage event of an assistant message
+    pub fn message_usage(&self) -> Option<(Option<&str>, Option<&str>, &TokenUsage)> {
+        self.events.iter().find_map(|event| match event {
+            StreamEvent::Usage {
+                message_id,
+                model,
+                usage,
+            } => Some((message_id.as_deref(), model.as_deref(), usage)),
+            _ => None,
+        })
+    }
+
+    /// Usage reported by the line: of its assistant message, or of the whole run
+    /// for a `result` message
+    pub fn usage(&self) -> Option<&TokenUsage> {
+        self.message_usage()
+            .map(|(_, _, usage)| usage)
+            .or_else(|| self.result().and_then(|result| result.usage.as_ref()))
+    }
+
+    pub fn timestamp_utc(&self) -> Option<DateTime<Utc>> {
+        DateTime::parse_from_rfc3339(self.timestamp.as_deref()?)
+            .ok()
+            .map(|timestamp| timestamp.with_timezone(&Utc))
+    }
+}
+
+/// Parse JSONL content, skipping lines that aren't JSON objects
+pub fn parse_jsonl(jsonl: &str) -> impl Iterator<Item = StreamLine> + '_ {
+    jsonl.lines().filter_map(StreamLine::parse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRINT_RUN: &str = include_str!("../tests/fixtures/stream_json/print_run.jsonl");
+    const SESSION_FILE: &str = include_str!("../tests/fixtures/stream_json/session_file.jsonl");
+
+    #[test]
+    fn test_parse_print_run() {
+        let lines: Vec<_> = parse_jsonl(PRINT_RUN).collect();
+        assert_eq!(lines.len(), 8);
+
+        assert_eq!(
+            lines[0].init_session_id(),
+            Some("4f1c2a9e-7d3b-4c8e-9a51-0b6e2f8d1c47")
+        );
+        assert!(matches!(
+            &lines[0].events[0],
+            StreamEvent::SystemInit { model: Some(model), tools, .. }
+                if model == "claude-sonnet-4-20250514" && tools.len() == 7
+        ));
+
+        assert_eq!(
+            lines[1].events[0],
+            StreamEvent::AssistantText {
+                text: "I'll look at the manifest first.".to_string()
+            }
+        );
+        let (message_id, model, usage) = lines[1].message_usage().unwrap();
+        assert_eq!(message_id, Some("msg_01AbCdEf"));
+        assert_eq!(model, Some("claude-sonnet-4-20250514"));
+        assert_eq!(usage.input_output(), 7);
+        assert_eq!(usage.total(), 14727);
+
+        let tools: Vec<_> = lines
+            .iter()
+            .flat_map(|line| line.tool_uses().map(|(name, _)| name))
+            .collect();
+        assert_eq!(tools, vec!["Read", "Edit"]);
+
+        assert_eq!(
+            lines[3].events,
+            vec![StreamEvent::ToolResult {
+                tool_use_id: "toolu_01Read".to_string(),
+                content: "     1→[package]\n     2→name = \"demo\"".to_string(),
+                is_error: false,
+            }]
+        );
+        assert!(matches!(
+            &lines[5].events[0],
+            StreamEvent::ToolResult { is_error: true, content, .. }
+                if content == "File has been modified since read."
+        ));
+
+        let result = lines[7].result().unwrap();
+        assert_eq!(result.subtype, "success");
+        assert!(!result.is_error);
+        assert_eq!(
+            result.result.as_deref(),
+            Some("Renamed the package to demo-app.")
+        );
+        assert_eq!(result.cost_usd, Some(0.02134));
+        assert_eq!(result.duration_ms, Some(8123));
+        assert_eq!(result.num_turns, Some(5));
+        assert_eq!(lines[7].usage().unwrap().output_tokens, 180);
+    }
+
+    #[test]
+    fn test_parse_session_file() {
+        let lines: Vec<_> = parse_jsonl(SESSION_FILE).collect();
+        assert_eq!(lines.len(), 5);
+
+        assert_eq!(lines[0].kind, "summary");
+        assert!(lines[0].events.is_empty());
+
+        assert_eq!(
+            lines[1].events,
+            vec![StreamEvent::UserText {
+                text: "Rename the package to demo-app".to_string()
+            }]
+        );
+        assert_eq!(
+            lines[1].session_id.as_deref(),
+            Some("4f1c2a9e-7d3b-4c8e-9a51-0b6e2f8d1c47")
+        );
+        assert_eq!(lines[1].cwd.as_deref(), Some("/home/dev/demo"));
+        assert_eq!(
+            lines[1].timestamp_utc().map(|t| t.timestamp()),
+            Some(1751364000)
+        );
+
+        assert_eq!(lines[2].request_id.as_deref(), Some("req_011CQg1"));
+        assert_eq!(lines[4].cost_usd, Some(0.0118));
+        assert!(lines[4].result().is_none());
+    }
+
+    #[test]
+    fn test_parse_is_tolerant() {
+        assert_eq!(StreamLine::parse(""), None);
+        assert_eq!(StreamLine::parse("Error: not logged in"), None);
+        assert_eq!(StreamLine::parse("[1, 2]"), None);
+
+        let unknown = StreamLine::parse(r#"{"type":"stream_event","event":{}}"#).unwrap();
+        assert_eq!(unknown.kind, "stream_event");
+        assert!(unknown.events.is_empty());
+
+        let sparse = StreamLine::parse(
+            r#"{"type":"assistant","message":{"content":[{"type":"thinking"},{"type":"tool_use"}],"usage":{"input_tokens":null,"output_tokens":5}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            sparse.events,
+            vec![
+                StreamEvent::ToolUse {
+                    id: String::new(),
+                    name: String::new(),
+                    input: JsonValue::Null,
+                },
+                StreamEvent::Usage {
+                    message_id: None,
+                    model: None,
+                    usage: TokenUsage {
+                        output_tokens: 5,
+                        ..Default::default()
+                    },
+                },
+            ]
+        );
+
+        let result = StreamLine::parse(r#"{"type":"result"}"#).unwrap();
+        assert_eq!(result.result().unwrap().subtype, "success");
+    }
+}
@@ -2,7 +2,7 @@
 use axum::extract::ws::{Message, WebSocket};
 use axum::http::Method;
 use axum::{
-    extract::{Path, State as AxumState, WebSocketUpgrade},
+    extract::{Path, Query, State as AxumState, WebSocketUpgrade},
     response::{Html, Json, Response},
     routing::get,
     Router,
@@ -18,6 +18,10 @@ use tower_http::cors::{Any, CorsLayer};
 use tower_http::services::ServeDir;
 
 use crate::commands;
+use crate::commands::agents::AgentDb;
+use crate::commands::claude_options::{session_option_args, ClaudeSessionOptions};
+use crate::commands::session_catalog::{self, CatalogQuery, ProjectSort, SessionSort};
+use crate::process::{follow_output, OutputBuffer};
 
 // Find Claude binary for web mode - use bundled binary first
 fn find_claude_binary_web() -> Result<String, String> {
@@ -63,6 +67,10 @@ pub struct AppState {
     // Track active WebSocket sessions for Claude execution
     pub active_sessions:
         Arc<Mutex<std::collections::HashMap<String, tokio::sync::mpsc::Sender<String>>>>,
+    // Recent messages of each execution, kept while it runs so clients can reattach
+    pub outputs: Arc<Mutex<std::collections::HashMap<String, Arc<std::sync::Mutex<OutputBuffer>>>>>,
+    // Database holding the session catalog behind the project and session lists
+    pub db: Arc<AgentDb>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -72,6 +80,14 @@ pub struct ClaudeExecutionRequest {
     pub model: Option<String>,
     pub session_id: Option<String>,
     pub command_type: String, // "execute", "continue", or "resume"
+    #[serde(default)]
+    pub options: Option<ClaudeSessionOptions>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AttachParams {
+    #[serde(default)]
+    pub from_seq: Option<u64>,
 }
 
 #[derive(Deserialize)]
@@ -111,20 +127,41 @@ async fn serve_frontend() -> Html<&'static str> {
 }
 
 /// API endpoint to get projects (equivalent to Tauri command)
-async fn get_projects() -> Json<ApiResponse<Vec<commands::claude::Project>>> {
-    match commands::claude::list_projects().await {
+async fn get_projects(
+    AxumState(state): AxumState<AppState>,
+    Query(query): Query<CatalogQuery<ProjectSort>>,
+) -> Json<ApiResponse<Vec<commands::claude::Project>>> {
+    let result = commands::claude::get_claude_dir()
+        .map_err(|e| e.to_string())
+        .and_then(|claude_dir| {
+            session_catalog::list_projects(&state.db, &claude_dir.join("projects"), query)
+        });
+    match result {
         Ok(projects) => Json(ApiResponse::success(projects)),
-        Err(e) => Json(ApiResponse::error(e.to_string())),
+        Err(e) => Json(ApiResponse::error(e)),
     }
 }
 
 /// API endpoint to get sessions for a project
 async fn get_sessions(
+    AxumState(state): AxumState<AppState>,
     Path(project_id): Path<String>,
+    Query(query): Query<CatalogQuery<SessionSort>>,
 ) -> Json<ApiResponse<Vec<commands::claude::Session>>> {
-    match commands::claude::get_project_sessions(project_id).await {
+    let result = commands::claude::get_claude_dir()
+        .map_err(|e| e.to_string())
+        .and_then(|claude_dir| {
+            session_catalog::project_sessions(
+                &state.db,
+                &claude_dir.join("projects"),
+                &claude_dir.join("todos"),
+                &project_id,
+                query,
+            )
+        });
+    match result {
         Ok(sessions) => Json(ApiResponse::success(sessions)),
-        Err(e) => Json(ApiResponse::error(e.to_string())),
+        Err(e) => Json(ApiResponse::error(e)),
     }
 }
 
@@ -323,6 +360,20 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
                         );
                         tokio::spawn(async move {
                             println!("[TRACE] Task started for command execution");
+
+                            // Tell the client which stream to reattach to if the
+                            // connection drops
+                            send_to_session(
+                                &state_clone,
+                                &session_id_clone,
+                                json!({
+                                    "type": "stream",
+                                    "stream_id": session_id_clone
+                                })
+                                .to_string(),
+                            )
+                            .await;
+
                             let result = match request.command_type.as_str() {
                                 "execute" => {
                                     println!("[TRACE] Calling execute_claude_command");
@@ -330,6 +381,7 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
                                         request.project_path,
                                         request.prompt,
                                         request.model.unwrap_or_default(),
+                                        request.options,
                                         session_id_clone.clone(),
                                         state_clone.clone(),
                                     )
@@ -341,6 +393,7 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
                                         request.project_path,
                                         request.prompt,
                                         request.model.unwrap_or_default(),
+                                        request.options,
                                         session_id_clone.clone(),
                                         state_clone.clone(),
                                     )
@@ -353,6 +406,7 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
                                         request.session_id.unwrap_or_default(),
                                         request.prompt,
                                         request.model.unwrap_or_default(),
+                                        request.options,
                                         session_id_clone.clone(),
                                         state_clone.clone(),
                                     )
@@ -373,27 +427,32 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
                             );
 
                             // Send completion message
-                            if let Some(sender) = state_clone
-                                .active_sessions
-                                .lock()
-                                .await
-                                .get(&session_id_clone)
+                            let completion_msg = match result {
+                                Ok(_) => json!({
+                                    "type": "completion",
+                                    "status": "success"
+                                }),
+                                Err(e) => json!({
+                                    "type": "completion",
+                                    "status": "error",
+                                    "error": e
+                                }),
+                            };
+                            println!("[TRACE] Sending completion message: {}", completion_msg);
+                            send_to_session(
+                                &state_clone,
+                                &session_id_clone,
+                                completion_msg.to_string(),
+                            )
+                            .await;
+
+                            // End reattached clients and drop the buffered output
+                            if let Some(buffer) =
+                                state_clone.outputs.lock().await.remove(&session_id_clone)
                             {
-                                let completion_msg = match result {
-                                    Ok(_) => json!({
-                                        "type": "completion",
-                                        "status": "success"
-                                    }),
-                                    Err(e) => json!({
-                                        "type": "completion",
-                                        "status": "error",
-                                        "error": e
-                                    }),
-                                };
-                                println!("[TRACE] Sending completion message: {}", completion_msg);
-                                let _ = sender.send(completion_msg.to_string()).await;
-                            } else {
-                                println!("[TRACE] Session not found in active sessions when sending completion");
+                                if let Ok(mut buffer) = buffer.lock() {
+                                    buffer.close();
+                                }
                             }
                         });
                     }
@@ -440,11 +499,25 @@ async fn claude_websocket_handler(socket: WebSocket, state: AppState) {
     println!("[TRACE] WebSocket handler ended for session {}", session_id);
 }
 
+/// Session option arguments for web mode, which has no permission prompts and so
+/// skips permissions unless the options set a permission mode
+fn web_session_args(
+    claude_path: &str,
+    options: Option<&ClaudeSessionOptions>,
+) -> Result<Vec<String>, String> {
+    let mut args = session_option_args(claude_path, options)?;
+    if !args.iter().any(|arg| arg == "--permission-mode") {
+        args.splice(0..0, crate::commands::permissions::skip_permissions_args());
+    }
+    Ok(args)
+}
+
 // Claude command execution functions for WebSocket streaming
 async fn execute_claude_command(
     project_path: String,
     prompt: String,
     model: String,
+    options: Option<ClaudeSessionOptions>,
     session_id: String,
     state: AppState,
 ) -> Result<(), String> {
@@ -490,9 +563,9 @@ async fn execute_claude_command(
         "--output-format",
         "stream-json",
         "--verbose",
-        "--dangerously-skip-permissions",
     ];
     cmd.args(args);
+    cmd.args(web_session_args(&claude_path, options.as_ref())?);
     cmd.current_dir(&project_path);
     cmd.stdout(std::process::Stdio::piped());
     cmd.stderr(std::process::Stdio::piped());
@@ -571,6 +644,7 @@ async fn continue_claude_command(
     project_path: String,
     prompt: String,
     model: String,
+    options: Option<ClaudeSessionOptions>,
     session_id: String,
     state: AppState,
 ) -> Result<(), String> {
@@ -603,8 +677,8 @@ async fn continue_claude_command(
         "--output-format",
         "stream-json",
         "--verbose",
-        "--dangerously-skip-permissions",
     ]);
+    cmd.args(web_session_args(&claude_path, options.as_ref())?);
     cmd.current_dir(&project_path);
     cmd.stdout(std::process::Stdio::piped());
     cmd.stderr(std::process::Stdio::piped());
@@ -649,6 +723,7 @@ async fn resume_claude_command(
     claude_session_id: String,
     prompt: String,
     model: String,
+    options: Option<ClaudeSessionOptions>,
     session_id: String,
     state: AppState,
 ) -> Result<(), String> {
@@ -691,9 +766,9 @@ async fn resume_claude_command(
         "--output-format",
         "stream-json",
         "--verbose",
-        "--dangerously-skip-permissions",
     ];
     cmd.args(args);
+    cmd.args(web_session_args(&claude_path, options.as_ref())?);
     cmd.current_dir(&project_path);
     cmd.stdout(std::process::Stdio::piped());
     cmd.stderr(std::process::Stdio::piped());
@@ -742,10 +817,37 @@ async fn resume_claude_command(
     Ok(())
 }
 
+/// Add the sequence number of a buffered message to its JSON
+fn with_seq(message: &str, seq: u64) -> String {
+    match serde_json::from_str::<serde_json::Value>(message) {
+        Ok(serde_json::Value::Object(mut object)) => {
+            object.insert("seq".to_string(), json!(seq));
+            serde_json::Value::Object(object).to_string()
+        }
+        _ => message.to_string(),
+    }
+}
+
 async fn send_to_session(state: &AppState, session_id: &str, message: String) {
     println!("[TRACE] send_to_session called for session: {}", session_id);
     println!("[TRACE] Message: {}", message);
 
+    // Buffer the message so a client can reattach to the stream later
+    let message = {
+        let buffer = state
+            .outputs
+            .lock()
+            .await
+            .entry(session_id.to_string())
+            .or_default()
+            .clone();
+        let seq = match buffer.lock() {
+            Ok(mut buffer) => buffer.push(message.clone()),
+            Err(_) => return,
+        };
+        with_seq(&message, seq)
+    };
+
     let sessions = state.active_sessions.lock().await;
     if let Some(sender) = sessions.get(session_id) {
         println!("[TRACE] Found session in active sessions, sending message...");
@@ -765,10 +867,62 @@ async fn send_to_session(state: &AppState, session_id: &str, message: String) {
     }
 }
 
+/// WebSocket endpoint replaying the messages of an execution from `from_seq`,
+/// then following it until it completes
+async fn attach_websocket(
+    ws: WebSocketUpgrade,
+    Path(stream_id): Path<String>,
+    Query(params): Query<AttachParams>,
+    AxumState(state): AxumState<AppState>,
+) -> Response {
+    ws.on_upgrade(move |socket| attach_websocket_handler(socket, state, stream_id, params))
+}
+
+async fn attach_websocket_handler(
+    socket: WebSocket,
+    state: AppState,
+    stream_id: String,
+    params: AttachParams,
+) {
+    let (mut sender, _) = socket.split();
+
+    let Some(buffer) = state.outputs.lock().await.get(&stream_id).cloned() else {
+        let error_msg = json!({
+            "type": "error",
+            "message": format!("No running execution {}", stream_id)
+        });
+        let _ = sender
+            .send(Message::Text(error_msg.to_string().into()))
+            .await;
+        return;
+    };
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let forward_task = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if sender.send(Message::Text(message.into())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    follow_output(buffer, params.from_seq.unwrap_or(0), move |line| {
+        tx.send(with_seq(&line.line, line.seq)).is_ok()
+    })
+    .await;
+    let _ = forward_task.await;
+}
+
 /// Create the web server
 pub async fn create_web_server(port: u16) -> Result<(), Box<dyn std::error::Error>> {
+    let data_dir = dirs::data_dir()
+        .ok_or("Could not determine the data directory")?
+        .join("opcode.asterisk.so");
+    let conn = commands::agents::init_database_in(&data_dir)?;
     let state = AppState {
         active_sessions: Arc::new(Mutex::new(std::collections::HashMap::new())),
+        outputs: Arc::new(Mutex::new(std::collections::HashMap::new())),
+        db: Arc::new(AgentDb(std::sync::Mutex::new(conn))),
     };
 
     // CORS layer to allow requests from phone browsers
@@ -821,6 +975,7 @@ pub async fn create_web_server(port: u16) -> Result<(), Box<dyn std::error::Erro
         )
         // WebSocket endpoint for real-time Claude execution
         .route("/ws/claude", get(claude_websocket))
+        .route("/ws/claude/{stream_id}/attach", get(attach_websocket))
         // Serve static assets
         .nest_service("/assets", ServeDir::new("../dist/assets"))
         .nest_service("/vite.svg", ServeDir::new("../dist/vite.svg"))
@@ -19,6 +19,7 @@ import { UsageDashboard } from "@/components/UsageDashboard";
 import { MCPManager } from "@/components/MCPManager";
 import { NFOCredits } from "@/components/NFOCredits";
 import { ClaudeBinaryDialog } from "@/components/ClaudeBinaryDialog";
+import { PermissionPrompt } from "@/components/PermissionPrompt";
 import { Toast, ToastContainer } from "@/components/ui/toast";
 import { ProjectSettings } from '@/components/ProjectSettings';
 import { TabManager } from "@/components/TabManager";
@@ -420,6 +421,9 @@ function AppContent() {
         onError={(message) => setToast({ message, type: "error" })}
       />
 
+      {/* Tool permission requests of Claude processes */}
+      <PermissionPrompt />
+
       {/* File picker modal for selecting project directory */}
       {showProjectPicker && (
         <div className="fixed inset-0 z-50 flex items-center justify-center bg-background/80 backdrop-blur-sm">
@@ -109,7 +109,7 @@ export function AgentRunOutputViewer({
         const agentRun = await api.getAgentRun(parseInt(agentRunId));
         setRun(agentRun);
         updateTabTitle(tabId, `Agent: ${agentRun.agent_name || 'Unknown'}`);
-        updateTabStatus(tabId, agentRun.status === 'running' ? 'running' : agentRun.status === 'failed' ? 'error' : 'complete');
+        updateTabStatus(tabId, agentRun.status === 'running' ? 'running' : agentRun.status === 'failed' || agentRun.status === 'budget_exceeded' ? 'error' : 'complete');
       } catch (error) {
         console.error('Failed to load agent run:', error);
         updateTabStatus(tabId, 'error');
@@ -13,6 +13,7 @@ import { Badge } from '@/components/ui/badge';
 import { Card } from '@/components/ui/card';
 import { Toast } from '@/components/ui/toast';
 import { api, type Agent, type AgentRunWithMetrics } from '@/lib/api';
+import { importAgentFileWithPreview } from '@/lib/agentImport';
 import { open as openDialog, save } from '@tauri-apps/plugin-dialog';
 import { invoke } from '@tauri-apps/api/core';
 import { GitHubAgentBrowser } from '@/components/GitHubAgentBrowser';
@@ -132,7 +133,8 @@ export const Agents: React.FC = () => {
       });
 
       if (selected) {
-        await api.importAgentFromFile(selected as string);
+        const imported = await importAgentFileWithPreview(selected as string);
+        if (!imported) return;
         setToast({ message: t('messages.agent_imported'), type: 'success' });
         loadAgents();
       }
@@ -168,6 +170,7 @@ export const Agents: React.FC = () => {
       case 'completed':
         return <CheckCircle className="w-4 h-4 text-green-500" />;
       case 'failed':
+      case 'budget_exceeded':
         return <XCircle className="w-4 h-4 text-red-500" />;
       default:
         return <Clock className="w-4 h-4 text-muted-foreground" />;
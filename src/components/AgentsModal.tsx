@@ -20,6 +20,7 @@ import { Badge } from '@/components/ui/badge';
 import { ScrollArea } from '@/components/ui/scroll-area';
 import { Toast } from '@/components/ui/toast';
 import { api, type Agent, type AgentRunWithMetrics } from '@/lib/api';
+import { importAgentFileWithPreview } from '@/lib/agentImport';
 import { useTabState } from '@/hooks/useTabState';
 import { formatISOTimestamp } from '@/lib/date-utils';
 import { open as openDialog, save } from '@tauri-apps/plugin-dialog';
@@ -167,7 +168,8 @@ export const AgentsModal: React.FC<AgentsModalProps> = ({ open, onOpenChange })
       });
       
       if (filePath) {
-        const agent = await api.importAgentFromFile(filePath as string);
+        const agent = await importAgentFileWithPreview(filePath as string);
+        if (!agent) return;
         loadAgents(); // Refresh list
         setToast({ message: t('messages.agent_imported', { name: agent.name }), type: "success" });
       }
@@ -209,6 +211,7 @@ export const AgentsModal: React.FC<AgentsModalProps> = ({ open, onOpenChange })
       case 'completed':
         return <CheckCircle className="w-4 h-4 text-green-500" />;
       case 'failed':
+      case 'budget_exceeded':
         return <XCircle className="w-4 h-4 text-red-500" />;
       default:
         return <Clock className="w-4 h-4 text-muted-foreground" />;
@@ -31,6 +31,7 @@ import {
   DialogTitle,
 } from "@/components/ui/dialog";
 import { api, type Agent, type AgentRunWithMetrics } from "@/lib/api";
+import { importAgentFileWithPreview } from "@/lib/agentImport";
 import { save, open } from "@tauri-apps/plugin-dialog";
 import { invoke } from "@tauri-apps/api/core";
 import { cn } from "@/lib/utils";
@@ -235,7 +236,8 @@ export const CCAgents: React.FC<CCAgentsProps> = ({ onBack, className }) => {
       }
 
       // Import the agent from the selected file
-      await api.importAgentFromFile(filePath as string);
+      const imported = await importAgentFileWithPreview(filePath as string);
+      if (!imported) return;
 
       setToast({ message: t('messages.agent_imported'), type: "success" });
       await loadAgents();
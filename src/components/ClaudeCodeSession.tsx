@@ -146,6 +146,8 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
   const queuedPromptsRef = useRef<Array<{ id: string; prompt: string; model: "sonnet" | "opus" }>>([]);
   const isMountedRef = useRef(true);
   const isListeningRef = useRef(false);
+  // Run ID of the Claude process started for the current prompt, if any
+  const runIdRef = useRef<number | null>(null);
   const sessionStartTime = useRef<number>(Date.now());
   const isIMEComposingRef = useRef(false);
   
@@ -441,6 +443,7 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
     
     // IMPORTANT: Set the session ID before setting up listeners
     setClaudeSessionId(sessionId);
+    runIdRef.current = null;
     
     // Mark as listening
     isListeningRef.current = true;
@@ -530,53 +533,44 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
         // --------------------------------------------------------------------
         // 1️⃣  Event Listener Setup Strategy
         // --------------------------------------------------------------------
-        // Claude Code may emit a *new* session_id even when we pass --resume. If
-        // we listen only on the old session-scoped channel we will miss the
-        // stream until the user navigates away & back. To avoid this we:
-        //   • Always start with GENERIC listeners (no suffix) so we catch the
-        //     very first "system:init" message regardless of the session id.
-        //   • Once that init message provides the *actual* session_id, we
-        //     dynamically switch to session-scoped listeners and stop the
-        //     generic ones to prevent duplicate handling.
+        // Claude Code may emit a *new* session_id even when we pass --resume, so
+        // we can't listen on the old session-scoped channel. Instead:
+        //   • In the desktop app, execute/resume return the run ID of the new
+        //     process. Its output is replayed from the start of the run and
+        //     ends with the exit status, so completion can't be missed even if
+        //     the run finishes before we subscribe. Errors are scoped to the
+        //     run until the init message provides the *actual* session_id, then
+        //     to the session. Other tabs' sessions never reach these listeners.
+        //   • In web mode, each execution has its own WebSocket, which
+        //     dispatches GENERIC events (no suffix) for this tab only.
         // --------------------------------------------------------------------
 
-        console.log('[ClaudeCodeSession] Setting up generic event listeners first');
-
         let currentSessionId: string | null = claudeSessionId || effectiveSession?.id || null;
+        let completed = false;
+        let sessionListenersAttached = false;
 
-        // Helper to attach session-specific listeners **once we are sure**
+        // Helper to switch error listeners to the session once it is known
         const attachSessionSpecificListeners = async (sid: string) => {
           console.log('[ClaudeCodeSession] Attaching session-specific listeners for', sid);
-
-          const specificOutputUnlisten = await listen(`claude-output:${sid}`, (evt: any) => {
-            handleStreamMessage(evt.payload);
-          });
+          sessionListenersAttached = true;
 
           const specificErrorUnlisten = await listen(`claude-error:${sid}`, (evt: any) => {
             console.error('Claude error (scoped):', evt.payload);
             setError(evt.payload);
           });
 
-          const specificCompleteUnlisten = await listen(`claude-complete:${sid}`, (evt: any) => {
-            console.log('[ClaudeCodeSession] Received claude-complete (scoped):', evt.payload);
-            processComplete(evt.payload);
-          });
-
           // Replace existing unlisten refs with these new ones (after cleaning up)
           unlistenRefs.current.forEach((u) => u());
-          unlistenRefs.current = [specificOutputUnlisten, specificErrorUnlisten, specificCompleteUnlisten];
+          unlistenRefs.current = [specificErrorUnlisten];
         };
 
-        // Generic listeners (catch-all)
-        const genericOutputUnlisten = await listen('claude-output', async (event: any) => {
-          handleStreamMessage(event.payload);
-
-          // Attempt to extract session_id on the fly (for the very first init)
+        // Helper to pick up the session_id from the init message
+        const detectSessionId = async (payload: string | ClaudeStreamMessage) => {
           try {
-            const msg = JSON.parse(event.payload) as ClaudeStreamMessage;
+            const msg = (typeof payload === 'string' ? JSON.parse(payload) : payload) as ClaudeStreamMessage;
             if (msg.type === 'system' && msg.subtype === 'init' && msg.session_id) {
               if (!currentSessionId || currentSessionId !== msg.session_id) {
-                console.log('[ClaudeCodeSession] Detected new session_id from generic listener:', msg.session_id);
+                console.log('[ClaudeCodeSession] Detected new session_id:', msg.session_id);
                 currentSessionId = msg.session_id;
                 setClaudeSessionId(msg.session_id);
 
@@ -593,15 +587,49 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
                     messages.length
                   );
                 }
+              }
 
-                // Switch to session-specific listeners
+              // The backend scopes events to the session from the init message on,
+              // also when a resume reports the session ID we already had. Web mode
+              // only has the generic events of this tab's WebSocket.
+              if (tauriListen && !sessionListenersAttached) {
                 await attachSessionSpecificListeners(msg.session_id);
               }
             }
           } catch {
             /* ignore parse errors */
           }
-        });
+        };
+
+        // Helper to listen to a run until its session_id is known
+        const attachRunListeners = async (runId: number) => {
+          console.log('[ClaudeCodeSession] Attaching run-scoped listeners for run', runId);
+          runIdRef.current = runId;
+
+          const runErrorUnlisten = await listen(`claude-run-error:${runId}`, (evt: any) => {
+            console.error('Claude error (run):', evt.payload);
+            setError(evt.payload);
+          });
+
+          unlistenRefs.current = [runErrorUnlisten];
+
+          try {
+            // The stream ends with a line carrying the exit status, also for runs
+            // that finished before we subscribed
+            await api.subscribeClaudeRunOutput(runId, (line) => {
+              if (line.success !== undefined) {
+                processComplete(line.success);
+                return;
+              }
+              handleStreamMessage(line.line);
+              detectSessionId(line.line);
+            });
+          } catch (err) {
+            console.error('Failed to follow the run output:', err);
+            setError("Failed to follow the session output");
+            processComplete(false);
+          }
+        };
 
         // Helper to process any JSONL stream message string or object
         function handleStreamMessage(payload: string | ClaudeStreamMessage) {
@@ -702,6 +730,8 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
 
         // Helper to handle completion events (both generic and scoped)
         const processComplete = async (success: boolean) => {
+          if (completed) return;
+          completed = true;
           setIsLoading(false);
           hasActiveSessionRef.current = false;
           isListeningRef.current = false; // Reset listening state
@@ -799,18 +829,26 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
           }
         };
 
-        const genericErrorUnlisten = await listen('claude-error', (evt: any) => {
-          console.error('Claude error:', evt.payload);
-          setError(evt.payload);
-        });
+        if (!tauriListen) {
+          console.log('[ClaudeCodeSession] Setting up generic event listeners (web mode)');
 
-        const genericCompleteUnlisten = await listen('claude-complete', (evt: any) => {
-          console.log('[ClaudeCodeSession] Received claude-complete (generic):', evt.payload);
-          processComplete(evt.payload);
-        });
+          const genericOutputUnlisten = await listen('claude-output', (event: any) => {
+            handleStreamMessage(event.payload);
+            detectSessionId(event.payload);
+          });
 
-        // Store the generic unlisteners for now; they may be replaced later.
-        unlistenRefs.current = [genericOutputUnlisten, genericErrorUnlisten, genericCompleteUnlisten];
+          const genericErrorUnlisten = await listen('claude-error', (evt: any) => {
+            console.error('Claude error:', evt.payload);
+            setError(evt.payload);
+          });
+
+          const genericCompleteUnlisten = await listen('claude-complete', (evt: any) => {
+            console.log('[ClaudeCodeSession] Received claude-complete (generic):', evt.payload);
+            processComplete(evt.payload);
+          });
+
+          unlistenRefs.current = [genericOutputUnlisten, genericErrorUnlisten, genericCompleteUnlisten];
+        }
 
         // --------------------------------------------------------------------
         // 2️⃣  Auto-checkpoint logic moved after listener setup (unchanged)
@@ -875,13 +913,19 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
           console.log('[ClaudeCodeSession] Resuming session:', effectiveSession.id);
           trackEvent.sessionResumed(effectiveSession.id);
           trackEvent.modelSelected(model);
-          await api.resumeClaudeCode(projectPath, effectiveSession.id, prompt, model);
+          const runId = await api.resumeClaudeCode(projectPath, effectiveSession.id, prompt, model);
+          if (tauriListen) {
+            await attachRunListeners(runId);
+          }
         } else {
           console.log('[ClaudeCodeSession] Starting new session');
           setIsFirstPrompt(false);
           trackEvent.sessionCreated(model, 'prompt_input');
           trackEvent.modelSelected(model);
-          await api.executeClaudeCode(projectPath, prompt, model);
+          const runId = await api.executeClaudeCode(projectPath, prompt, model);
+          if (tauriListen) {
+            await attachRunListeners(runId);
+          }
         }
       }
     } catch (err) {
@@ -983,13 +1027,13 @@ export const ClaudeCodeSession: React.FC<ClaudeCodeSessionProps> = ({
   };
 
   const handleCancelExecution = async () => {
-    if (!claudeSessionId || !isLoading) return;
+    if ((!claudeSessionId && runIdRef.current === null) || !isLoading) return;
     
     try {
       const sessionStartTime = messages.length > 0 ? messages[0].timestamp || Date.now() : Date.now();
       const duration = Date.now() - sessionStartTime;
       
-      await api.cancelClaudeExecution(claudeSessionId);
+      await api.cancelClaudeExecution(claudeSessionId ?? undefined, runIdRef.current ?? undefined);
       
       // Calculate metrics for enhanced analytics
       const metrics = sessionMetrics.current;
@@ -15,11 +15,12 @@ import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
 import { Card, CardContent, CardFooter } from "@/components/ui/card";
 import { Badge } from "@/components/ui/badge";
-import { api, type GitHubAgentFile, type AgentExport, type Agent } from "@/lib/api";
+import { api, type GitHubAgentFile, type AgentImportPreview, type Agent } from "@/lib/api";
 import { type AgentIconName } from "./CCAgents";
 import { ICON_MAP } from "./IconPicker";
 import { open } from "@tauri-apps/plugin-shell";
 import { useTranslation } from "@/hooks/useTranslation";
+import { confirmBundledResources, describeAgentImport } from "@/lib/agentImport";
 
 interface GitHubAgentBrowserProps {
   isOpen: boolean;
@@ -29,7 +30,7 @@ interface GitHubAgentBrowserProps {
 
 interface AgentPreview {
   file: GitHubAgentFile;
-  data: AgentExport | null;
+  data: AgentImportPreview | null;
   loading: boolean;
   error: string | null;
 }
@@ -43,6 +44,7 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
   const [agents, setAgents] = useState<GitHubAgentFile[]>([]);
   const [loading, setLoading] = useState(true);
   const [error, setError] = useState<string | null>(null);
+  const [sourceNotices, setSourceNotices] = useState<string[]>([]);
   const [searchQuery, setSearchQuery] = useState("");
   const [selectedAgent, setSelectedAgent] = useState<AgentPreview | null>(null);
   const [importing, setImporting] = useState(false);
@@ -68,8 +70,25 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
     try {
       setLoading(true);
       setError(null);
-      const agentFiles = await api.fetchGitHubAgents();
-      setAgents(agentFiles);
+      setSourceNotices([]);
+      const listing = await api.fetchGitHubAgents();
+      setAgents(listing.agents);
+      listing.errors.forEach((e) =>
+        console.warn(`Failed to list agent source ${e.source_name}:`, e.message)
+      );
+      const sourceName = (id: string) =>
+        listing.agents.find((agent) => agent.source_id === id)?.source_name ?? id;
+      setSourceNotices([
+        ...listing.errors.map((e) =>
+          t('messages.source_unavailable', { source: e.source_name, error: e.message })
+        ),
+        ...listing.cached_sources.map((id) =>
+          t('messages.source_cached', { source: sourceName(id) })
+        ),
+      ]);
+      if (listing.agents.length === 0 && listing.errors.length > 0) {
+        setError(t('messages.failed_to_fetch_github'));
+      }
     } catch (err) {
       console.error("Failed to fetch GitHub agents:", err);
       setError(t('messages.failed_to_fetch_github'));
@@ -87,7 +106,7 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
     });
 
     try {
-      const agentData = await api.fetchGitHubAgentContent(file.download_url);
+      const agentData = await api.fetchGitHubAgentContent(file.download_url, file.source_id);
       setSelectedAgent({
         file,
         data: agentData,
@@ -117,7 +136,13 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
 
     try {
       setImporting(true);
-      await api.importAgentFromGitHub(selectedAgent.file.download_url);
+      await api.importAgentFromGitHub(
+        selectedAgent.file.download_url,
+        selectedAgent.file.source_id,
+        selectedAgent.file.sha,
+        selectedAgent.data?.verification.sha256,
+        selectedAgent.data ? confirmBundledResources(selectedAgent.data) : false
+      );
 
       // Refresh existing agents list
       await fetchExistingAgents();
@@ -188,6 +213,18 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
             </p>
           </div>
 
+          {/* Sources that could not be listed */}
+          {!loading && !error && sourceNotices.length > 0 && (
+            <div className="mb-4 space-y-1">
+              {sourceNotices.map((notice) => (
+                <p key={notice} className="flex items-center gap-2 text-xs text-muted-foreground">
+                  <AlertCircle className="h-3 w-3 flex-shrink-0 text-yellow-600" />
+                  {notice}
+                </p>
+              ))}
+            </div>
+          )}
+
           {/* Search Bar */}
           <div className="mb-4">
             <div className="relative">
@@ -227,7 +264,7 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
                 <AnimatePresence mode="popLayout">
                   {filteredAgents.map((agent, index) => (
                     <motion.div
-                      key={agent.sha}
+                      key={`${agent.source_id}:${agent.sha}`}
                       initial={{ opacity: 0, scale: 0.9 }}
                       animate={{ opacity: 1, scale: 1 }}
                       exit={{ opacity: 0, scale: 0.9 }}
@@ -257,7 +294,7 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
                             )}
                           </div>
                           <p className="text-xs text-muted-foreground">
-                            {(agent.size / 1024).toFixed(1)} KB
+                            {agent.source_name} · {(agent.size / 1024).toFixed(1)} KB
                           </p>
                         </CardContent>
                         <CardFooter className="p-4 pt-0">
@@ -308,14 +345,14 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
                     {/* Agent Info */}
                     <div className="flex items-start gap-4">
                       <div className="p-3 rounded-lg bg-primary/10 text-primary">
-                        {renderIcon(selectedAgent.data.agent.icon)}
+                        {renderIcon(selectedAgent.data.package.agent.icon)}
                       </div>
                       <div className="flex-1">
                         <h3 className="text-lg font-semibold">
-                          {selectedAgent.data.agent.name}
+                          {selectedAgent.data.package.agent.name}
                         </h3>
                         <div className="flex items-center gap-2 mt-1">
-                          <Badge variant="outline">{selectedAgent.data.agent.model}</Badge>
+                          <Badge variant="outline">{selectedAgent.data.package.agent.model}</Badge>
                         </div>
                       </div>
                     </div>
@@ -325,28 +362,35 @@ export const GitHubAgentBrowser: React.FC<GitHubAgentBrowserProps> = ({
                       <h4 className="text-sm font-medium mb-2">{t('labels.system_prompt_label')}</h4>
                       <div className="bg-muted rounded-lg p-3 max-h-48 overflow-y-auto">
                         <pre className="text-xs whitespace-pre-wrap font-mono">
-                          {selectedAgent.data.agent.system_prompt}
+                          {selectedAgent.data.package.agent.system_prompt}
                         </pre>
                       </div>
                     </div>
 
                     {/* Default Task */}
-                    {selectedAgent.data.agent.default_task && (
+                    {selectedAgent.data.package.agent.default_task && (
                       <div>
                         <h4 className="text-sm font-medium mb-2">{t('labels.default_task_label')}</h4>
                         <div className="bg-muted rounded-lg p-3">
-                          <p className="text-sm">{selectedAgent.data.agent.default_task}</p>
+                          <p className="text-sm">{selectedAgent.data.package.agent.default_task}</p>
                         </div>
                       </div>
                     )}
 
 
 
-                    {/* Metadata */}
+                    {/* Verification and commands the agent runs */}
+                    <div>
+                      <h4 className="text-sm font-medium mb-2">{t('labels.verification')}</h4>
+                      <pre className="text-xs whitespace-pre-wrap font-mono bg-muted rounded-lg p-3 max-h-48 overflow-y-auto">
+                        {describeAgentImport(selectedAgent.data)}
+                      </pre>
+                    </div>
+
                     {/* Metadata */}
                     <div className="text-xs text-muted-foreground">
-                      <p>{t('labels.version')}: {selectedAgent.data.version}</p>
-                      <p>{t('labels.exported_at')}: {new Date(selectedAgent.data.exported_at).toLocaleDateString()}</p>
+                      <p>{t('labels.version')}: {selectedAgent.data.package.version}</p>
+                      <p>{t('labels.exported_at')}: {new Date(selectedAgent.data.package.exported_at).toLocaleDateString()}</p>
                     </div>
                   </div>
                 ) : null}
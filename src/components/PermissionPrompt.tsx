@@ -0,0 +1,114 @@
+import { useState, useEffect } from "react";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+import { api, type PermissionDecision, type PermissionRequest } from "@/lib/api";
+import { Button } from "@/components/ui/button";
+import { Dialog, DialogContent, DialogDescription, DialogFooter, DialogHeader, DialogTitle } from "@/components/ui/dialog";
+import { ShieldQuestion } from "lucide-react";
+import { useTranslation } from "@/hooks/useTranslation";
+
+/**
+ * Asks the user to approve the tool uses of Claude processes run in the `prompt`
+ * permission mode, one `permission-request` at a time
+ */
+export function PermissionPrompt() {
+  const { t } = useTranslation('settings');
+  const [requests, setRequests] = useState<PermissionRequest[]>([]);
+  const [responding, setResponding] = useState(false);
+
+  useEffect(() => {
+    let disposed = false;
+    let unlisten: UnlistenFn | undefined;
+
+    // Requests made before this window was listening
+    api.listPermissionRequests()
+      .then(pending => {
+        if (!disposed) {
+          setRequests(prev => [...prev, ...pending.filter(p => !prev.some(r => r.id === p.id))]);
+        }
+      })
+      .catch(() => {});
+
+    listen<PermissionRequest>('permission-request', event => {
+      setRequests(prev => prev.some(r => r.id === event.payload.id) ? prev : [...prev, event.payload]);
+    })
+      .then(fn => {
+        if (disposed) {
+          fn();
+        } else {
+          unlisten = fn;
+        }
+      })
+      .catch(err => console.error("Failed to listen for permission requests:", err));
+
+    return () => {
+      disposed = true;
+      unlisten?.();
+    };
+  }, []);
+
+  const current = requests[0];
+
+  const respond = async (decision: PermissionDecision) => {
+    if (!current) return;
+    setResponding(true);
+    try {
+      await api.respondPermissionRequest(current.id, decision);
+    } catch {
+      // Already answered, timed out, or its process exited
+    }
+    try {
+      // Drop requests that ended meanwhile along with the answered one
+      const pending = await api.listPermissionRequests();
+      setRequests(prev => prev.filter(r => r.id !== current.id && pending.some(p => p.id === r.id)));
+    } catch {
+      setRequests(prev => prev.filter(r => r.id !== current.id));
+    } finally {
+      setResponding(false);
+    }
+  };
+
+  if (!current) return null;
+
+  return (
+    <Dialog open onOpenChange={open => !open && respond('deny')}>
+      <DialogContent className="sm:max-w-[600px]">
+        <DialogHeader>
+          <DialogTitle className="flex items-center gap-2">
+            <ShieldQuestion className="w-5 h-5" />
+            {t('permission_prompt.title', { tool: current.tool_name })}
+          </DialogTitle>
+          <DialogDescription>
+            {t('permission_prompt.description', { tool: current.tool_name, project: current.project_path })}
+          </DialogDescription>
+        </DialogHeader>
+
+        <div className="space-y-1">
+          <p className="text-sm font-medium">{t('permission_prompt.input')}</p>
+          <pre className="max-h-64 overflow-auto rounded-md bg-muted p-3 text-xs whitespace-pre-wrap break-all">
+            {JSON.stringify(current.input, null, 2)}
+          </pre>
+          {requests.length > 1 && (
+            <p className="text-xs text-muted-foreground">
+              {t('permission_prompt.more_waiting', { count: requests.length - 1 })}
+            </p>
+          )}
+        </div>
+
+        <DialogFooter className="gap-2">
+          <Button variant="outline" onClick={() => respond('deny')} disabled={responding} className="mr-auto">
+            {t('permission_prompt.deny')}
+          </Button>
+          <Button variant="outline" onClick={() => respond('allow_project')} disabled={responding}>
+            {t('permission_prompt.allow_project')}
+          </Button>
+          <Button variant="outline" onClick={() => respond('allow_session')} disabled={responding}>
+            {t('permission_prompt.allow_session')}
+          </Button>
+          <Button onClick={() => respond('allow_once')} disabled={responding}>
+            {t('permission_prompt.allow_once')}
+          </Button>
+        </DialogFooter>
+      </DialogContent>
+    </Dialog>
+  );
+}
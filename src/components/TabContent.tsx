@@ -4,6 +4,7 @@ import { useTabState } from '@/hooks/useTabState';
 import { useScreenTracking } from '@/hooks/useAnalytics';
 import { Tab } from '@/contexts/TabContext';
 import { Loader2, Plus, ArrowLeft } from 'lucide-react';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import { api, type Project, type Session, type ClaudeMdFile } from '@/lib/api';
 import { ProjectList } from '@/components/ProjectList';
 import { SessionList } from '@/components/SessionList';
@@ -48,6 +49,40 @@ const TabPanel: React.FC<TabPanelProps> = ({ tab, isActive }) => {
     }
   }, [isActive, tab.type]);
   
+  // Refresh the lists when sessions are created or finish, including ones
+  // started outside opcode
+  useEffect(() => {
+    if (!isActive || tab.type !== 'projects') return;
+
+    const refresh = () => {
+      api.listProjects().then(setProjects).catch(err => {
+        console.error("Failed to refresh projects:", err);
+      });
+      if (selectedProject) {
+        api.getProjectSessions(selectedProject.id).then(setSessions).catch(err => {
+          console.error("Failed to refresh sessions:", err);
+        });
+      }
+    };
+
+    let disposed = false;
+    const unlisteners: UnlistenFn[] = [];
+    Promise.all([listen('session-created', refresh), listen('session-finished', refresh)])
+      .then(fns => {
+        if (disposed) {
+          fns.forEach(fn => fn());
+        } else {
+          unlisteners.push(...fns);
+        }
+      })
+      .catch(err => console.error("Failed to listen for session changes:", err));
+
+    return () => {
+      disposed = true;
+      unlisteners.forEach(fn => fn());
+    };
+  }, [isActive, tab.type, selectedProject]);
+
   const loadProjects = async () => {
     try {
       setLoading(true);
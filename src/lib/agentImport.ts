@@ -0,0 +1,69 @@
+/**
+ * Import of agent files with a review of what they would run
+ */
+
+import { api, type AgentImportPreview, type AgentImportResult } from './api';
+
+/**
+ * Describes the verification status and the commands an agent would run
+ */
+export function describeAgentImport(preview: AgentImportPreview): string {
+  const { verification, commands } = preview;
+  const lines: string[] = [];
+
+  switch (verification.status) {
+    case 'signed':
+      lines.push(`Signed by ${verification.publisher}.`);
+      break;
+    case 'checksum':
+      lines.push('Matches a pinned SHA-256 checksum, but is not signed.');
+      break;
+    default:
+      lines.push('WARNING: this agent file is not signed and its checksum is not pinned.');
+  }
+
+  if (commands.length > 0) {
+    lines.push('', 'It will run these commands:');
+    for (const command of commands) {
+      lines.push(`  [${command.kind === 'hook' ? 'hook' : 'MCP'} ${command.context}] ${command.command}`);
+    }
+  }
+
+  return lines.join('\n');
+}
+
+/**
+ * Asks whether to install the slash commands and MCP servers bundled with an agent
+ * at user scope, after the user reviewed the import preview
+ */
+export function confirmBundledResources(preview: AgentImportPreview): boolean {
+  const { slash_commands, mcp_servers } = preview.package;
+  const names = [
+    ...slash_commands.map(c => (c.namespace ? `/${c.namespace}:${c.name}` : `/${c.name}`)),
+    ...Object.keys(mcp_servers).map(name => `MCP server ${name}`),
+  ];
+  if (names.length === 0) {
+    return false;
+  }
+  return window.confirm(
+    `This agent bundles resources that would be available to all your sessions:\n\n${names
+      .map(name => `  ${name}`)
+      .join('\n')}\n\nInstall them too?`
+  );
+}
+
+/**
+ * Previews an agent file and imports it once the user accepted the preview
+ * @param filePath - The path to the agent file
+ * @returns Promise resolving to the imported agent, or null if the user declined
+ */
+export async function importAgentFileWithPreview(
+  filePath: string
+): Promise<AgentImportResult | null> {
+  const preview = await api.previewAgentImportFromFile(filePath);
+  const message = `Import agent "${preview.package.agent.name}"?\n\n${describeAgentImport(preview)}`;
+  if (!window.confirm(message)) {
+    return null;
+  }
+  return api.importAgentFromFile(filePath, preview.verification.sha256, confirmBundledResources(preview));
+}
@@ -1,3 +1,4 @@
+import { Channel } from '@tauri-apps/api/core';
 import { apiCall } from './apiAdapter';
 import type { HooksConfiguration } from '@/types/hooks';
 
@@ -51,6 +52,36 @@ export interface Session {
   first_message?: string;
   /** Timestamp of the first user message (if available) */
   message_timestamp?: string;
+  /** Unix timestamp when the session file was last written to */
+  last_modified: number;
+  /** Timestamp of the last message (if available) */
+  last_message_timestamp?: string;
+  /** Number of user and assistant messages, excluding tool results */
+  message_count: number;
+  /** Model of the last assistant message (if any) */
+  model?: string;
+  /** Cost of the session in USD */
+  cost_usd: number;
+}
+
+/**
+ * Order of the projects returned by listProjects; defaults to 'last_active'
+ */
+export type ProjectSort = 'last_active' | 'newest' | 'path' | 'sessions';
+
+/**
+ * Order of the sessions returned by getProjectSessions; defaults to 'newest'
+ */
+export type SessionSort = 'newest' | 'oldest' | 'last_active' | 'messages' | 'cost';
+
+/**
+ * Pagination and sorting of a project or session listing
+ */
+export interface CatalogQuery<S> {
+  offset?: number;
+  /** All remaining entries when not set */
+  limit?: number;
+  sort?: S;
 }
 
 /**
@@ -120,11 +151,52 @@ export interface Agent {
   default_task?: string;
   model: string;
   hooks?: string; // JSON string of HooksConfiguration
+  first_output_timeout_secs?: number | null; // Overrides the global limit when set
+  max_runtime_secs?: number | null;
+  idle_timeout_secs?: number | null;
+  max_cost_usd?: number | null; // Default budget for runs of this agent
+  max_tokens?: number | null;
+  parameters?: string | null; // JSON array of AgentParameter
+  task_template?: string | null; // Task with `{{parameter}}` placeholders
+  source_id?: string | null; // Agent source this agent was imported from
+  source_url?: string | null;
+  source_sha?: string | null;
   created_at: string;
   updated_at: string;
 }
 
-export interface AgentExport {
+/**
+ * Time limits applied to agent runs, in seconds. 0 disables a limit.
+ */
+export interface AgentRunLimits {
+  first_output_timeout_secs: number;
+  max_runtime_secs: number;
+  idle_timeout_secs: number;
+}
+
+/**
+ * A slash command bundled with an agent package
+ */
+export interface PackagedSlashCommand {
+  name: string;
+  namespace?: string | null;
+  description?: string | null;
+  allowed_tools: string[];
+  content: string;
+}
+
+/**
+ * A CLAUDE.md fragment bundled with an agent package
+ */
+export interface ClaudeMdFragment {
+  title?: string | null;
+  content: string;
+}
+
+/**
+ * Agent package format (version 2). Version 1 files are upgraded on import.
+ */
+export interface AgentPackage {
   version: number;
   exported_at: string;
   agent: {
@@ -133,8 +205,186 @@ export interface AgentExport {
     system_prompt: string;
     default_task?: string;
     model: string;
+    enable_file_read: boolean;
+    enable_file_write: boolean;
+    enable_network: boolean;
     hooks?: string;
+    first_output_timeout_secs?: number | null;
+    max_runtime_secs?: number | null;
+    idle_timeout_secs?: number | null;
+    max_cost_usd?: number | null;
+    max_tokens?: number | null;
+    parameters?: string | null;
+    task_template?: string | null;
   };
+  slash_commands: PackagedSlashCommand[];
+  mcp_servers: Record<string, any>;
+  claude_md: ClaudeMdFragment[];
+}
+
+/**
+ * A bundled resource that was not installed because it clashes with an existing one
+ */
+export interface PackageConflict {
+  kind: 'slash_command' | 'mcp_server';
+  name: string;
+  reason: string;
+}
+
+/**
+ * A shell command an imported agent would run, from its hooks or bundled MCP servers
+ */
+export interface PreviewCommand {
+  kind: 'hook' | 'mcp_server';
+  context: string;
+  command: string;
+}
+
+/**
+ * Result of verifying an agent file against its signature and checksum
+ */
+export interface AgentVerification {
+  status: 'signed' | 'checksum' | 'unverified';
+  publisher?: string | null;
+  sha256: string;
+}
+
+/**
+ * What importing an agent file would install, for review before accepting
+ */
+export interface AgentImportPreview {
+  package: AgentPackage;
+  verification: AgentVerification;
+  commands: PreviewCommand[];
+}
+
+/**
+ * Trusted publisher keys and the verification policy for agent imports
+ */
+export interface AgentTrustSettings {
+  require_verification: boolean;
+  trusted_publishers: { name: string; public_key: string }[];
+  /** Agent files trusted by their SHA-256, wherever they are served from */
+  pinned_checksums: { name: string; sha256: string }[];
+}
+
+/**
+ * A line of session output with its position in the session's output stream
+ */
+export interface OutputLine {
+  seq: number;
+  line: string;
+  /** Whether the process succeeded; set only on the empty line that ends the stream */
+  success?: boolean;
+}
+
+/**
+ * Claude CLI options for a session, validated against the installed CLI version
+ */
+export interface ClaudeSessionOptions {
+  append_system_prompt?: string;
+  max_turns?: number;
+  allowed_tools?: string[];
+  disallowed_tools?: string[];
+  /** Directories the session may access besides the project */
+  add_dirs?: string[];
+  /** MCP server config files, or inline JSON configs */
+  mcp_configs?: string[];
+  permission_mode?: 'default' | 'acceptEdits' | 'bypassPermissions' | 'plan';
+  fallback_model?: string;
+}
+
+/**
+ * How Claude processes get permission to use tools, and the tools always allowed per project
+ */
+export interface PermissionSettings {
+  mode: 'skip_permissions' | 'prompt';
+  allowed_tools: Record<string, string[]>;
+}
+
+/**
+ * A tool use waiting for approval, emitted as the `permission-request` event
+ */
+export interface PermissionRequest {
+  id: string;
+  session: string;
+  project_path: string;
+  tool_name: string;
+  input: Record<string, any>;
+}
+
+export type PermissionDecision = 'allow_once' | 'allow_session' | 'allow_project' | 'deny';
+
+/**
+ * How long the stored output of agent runs is kept
+ */
+export interface AgentOutputRetention {
+  max_age_days?: number | null;
+  max_total_mb?: number | null;
+}
+
+/**
+ * Activity in a session file, emitted as `session-created`, `session-appended`
+ * and `session-finished`; appended lines follow as `session-output:{session_id}`
+ */
+export interface SessionActivity {
+  project_id: string;
+  session_id: string;
+  new_lines: number;
+}
+
+/**
+ * Whether agent runs still alive when the app starts are tracked again
+ */
+export interface RecoverySettings {
+  readopt_running: boolean;
+}
+
+/**
+ * What startup recovery did with a run left running by a previous instance
+ */
+export interface RecoveredRun {
+  run_id: number;
+  agent_name: string;
+  pid?: number | null;
+  action: 'interrupted' | 'readopted' | 'left_running';
+}
+
+/**
+ * The outcome of reconciling the runs marked running with the live processes
+ */
+export interface RecoveryReport {
+  checked_at: string;
+  runs: RecoveredRun[];
+}
+
+/**
+ * How long a cancelled session gets to exit after each signal
+ */
+export interface CancellationSettings {
+  interrupt_grace_ms: number;
+  terminate_grace_ms: number;
+}
+
+/**
+ * The signal that ended a cancelled process; `interrupt` means it could flush its result
+ */
+export type TerminationStage = 'already_exited' | 'interrupt' | 'terminate' | 'kill';
+
+/**
+ * A Claude process stopped by cancelClaudeExecution
+ */
+export interface CancelledProcess {
+  run_id: number;
+  stage: TerminationStage;
+}
+
+/**
+ * An imported agent with the outcome of installing its bundled resources
+ */
+export interface AgentImportResult extends Agent {
+  installed: string[];
+  conflicts: PackageConflict[];
 }
 
 export interface GitHubAgentFile {
@@ -143,6 +393,34 @@ export interface GitHubAgentFile {
   download_url: string;
   size: number;
   sha: string;
+  source_id: string;
+  source_name: string;
+}
+
+/**
+ * A configured agent registry: a GitHub repository directory, an HTTP index
+ * JSON or a local directory of `*.opcode.json` files
+ */
+export type AgentSource = {
+  id: string;
+  name: string;
+  /** Stored tokens are returned as '********'; saving that value keeps the stored token */
+  token?: string | null;
+  enabled: boolean;
+} & (
+  | { type: 'github'; repo: string; path: string; branch?: string | null; api_url?: string | null }
+  | { type: 'http'; url: string }
+  | { type: 'local'; path: string }
+);
+
+/**
+ * Agents listed across all enabled sources
+ */
+export interface AgentRegistryListing {
+  agents: GitHubAgentFile[];
+  errors: { source_id: string; source_name: string; message: string }[];
+  /** Sources that could not be reached and were listed from the cache */
+  cached_sources: string[];
 }
 
 export interface AgentRun {
@@ -154,11 +432,99 @@ export interface AgentRun {
   model: string;
   project_path: string;
   session_id: string;
-  status: string; // 'pending', 'running', 'completed', 'failed', 'cancelled'
+  status: string; // 'pending', 'running', 'completed', 'failed', 'cancelled', 'interrupted', 'budget_exceeded'
   pid?: number;
   process_started_at?: string;
   created_at: string;
   completed_at?: string;
+  exit_code?: number;
+  error_subtype?: string; // e.g. 'error_max_turns', 'authentication_failed'
+  stderr_tail?: string;
+  result_text?: string;
+  max_cost_usd?: number; // Budget the run was started with
+  max_tokens?: number;
+  worktree_path?: string; // Set for runs isolated in a git worktree
+  worktree_branch?: string;
+  base_commit?: string;
+  agent_version?: number; // Version of the agent the run was started with
+  parameters?: string; // JSON object of the values the task was rendered with
+  parent_run_id?: number; // Run this one continues, for follow-ups
+}
+
+/**
+ * A named parameter an agent's task template refers to as `{{name}}`.
+ * Parameters without a default are required.
+ */
+export interface AgentParameter {
+  name: string;
+  type: 'string' | 'path' | 'enum' | 'number';
+  description?: string;
+  default?: string | number;
+  options?: string[]; // Allowed values of an enum parameter
+}
+
+/**
+ * A revision of an agent's prompt, model, hooks and permissions
+ */
+export interface AgentVersion {
+  id?: number;
+  agent_id: number;
+  version: number;
+  system_prompt: string;
+  default_task?: string;
+  model: string;
+  enable_file_read: boolean;
+  enable_file_write: boolean;
+  enable_network: boolean;
+  hooks?: string;
+  parameters?: string;
+  task_template?: string;
+  created_at: string;
+}
+
+/**
+ * A field that differs between two agent versions
+ */
+export interface AgentVersionChange {
+  field: string;
+  old_value?: string;
+  new_value?: string;
+}
+
+/**
+ * An available upstream update of an agent imported from an agent source
+ */
+export interface AgentUpdate {
+  agent_id: number;
+  agent_name: string;
+  source_url: string;
+  upstream_sha?: string | null;
+  /** Checksum of the upstream file, which pins applyAgentUpdate to it */
+  upstream_sha256: string;
+  /** Changes applying the update makes to the agent */
+  changes: AgentVersionChange[];
+  /** Fields changed both upstream and locally; applying keeps the local values */
+  preserved_overrides: string[];
+}
+
+/**
+ * Cost and token budget of an agent run. Unset values are unlimited.
+ */
+export interface AgentBudget {
+  max_cost_usd?: number;
+  max_tokens?: number;
+}
+
+/**
+ * Live budget usage, sent with `agent-budget-warning:{runId}` and
+ * `agent-budget-exceeded:{runId}` events
+ */
+export interface AgentBudgetUsage {
+  total_tokens: number;
+  cost_usd: number;
+  max_tokens?: number;
+  max_cost_usd?: number;
+  fraction_used: number;
 }
 
 export interface AgentRunMetrics {
@@ -177,17 +543,89 @@ export interface AgentRunWithMetrics {
   model: string;
   project_path: string;
   session_id: string;
-  status: string; // 'pending', 'running', 'completed', 'failed', 'cancelled'
+  status: string; // 'pending', 'running', 'completed', 'failed', 'cancelled', 'interrupted', 'budget_exceeded'
   pid?: number;
   duration_ms?: number;
   total_tokens?: number;
   process_started_at?: string;
   created_at: string;
   completed_at?: string;
+  exit_code?: number;
+  error_subtype?: string;
+  stderr_tail?: string;
+  result_text?: string;
+  max_cost_usd?: number;
+  max_tokens?: number;
+  worktree_path?: string;
+  worktree_branch?: string;
+  base_commit?: string;
+  agent_version?: number;
+  parameters?: string;
+  parent_run_id?: number; // Run this one continues, for follow-ups
   metrics?: AgentRunMetrics;
   output?: string; // Real-time JSONL content
 }
 
+/**
+ * One side of an agent run comparison
+ */
+export interface RunComparisonSide {
+  run: AgentRun;
+  metrics: AgentRunMetrics;
+  tool_calls: Record<string, number>;
+  files_touched: string[];
+  result_text?: string;
+  diff?: string; // Changes against the base commit, for isolated runs
+  edit_changes?: string; // Changes of its edit tool calls laid out like a diff, for runs without a worktree; not a unified diff
+}
+
+/**
+ * Two agent runs with aligned metrics
+ */
+export interface AgentRunComparison {
+  left: RunComparisonSide;
+  right: RunComparisonSide;
+  files_only_left: string[];
+  files_only_right: string[];
+  diff_between?: string; // Left run's changes to the right run's, when both were isolated
+}
+
+/**
+ * A run of one agent over many projects
+ */
+export interface AgentBatch {
+  id: number;
+  agent_id: number;
+  task: string;
+  model?: string;
+  concurrency: number;
+  status: string; // 'running', 'completed', 'failed', 'interrupted'
+  created_at: string;
+  completed_at?: string;
+}
+
+/**
+ * The run of a batch in one project
+ */
+export interface AgentBatchItem {
+  project_path: string;
+  run_id?: number;
+  status: string; // 'pending', 'running', or the status of the finished run
+  error?: string; // Why the run could not be started
+  cost_usd?: number;
+  result_text?: string;
+}
+
+/**
+ * A batch with the outcome of each of its runs
+ */
+export interface AgentBatchSummary extends AgentBatch {
+  items: AgentBatchItem[];
+  completed: number;
+  failed: number;
+  total_cost_usd: number;
+}
+
 // Usage Dashboard types
 export interface UsageEntry {
   project: string;
@@ -462,12 +900,13 @@ export const api = {
   },
 
   /**
-   * Lists all projects in the ~/.claude/projects directory
+   * Lists the projects in the ~/.claude/projects directory
+   * @param query - Optional pagination and sort order
    * @returns Promise resolving to an array of projects
    */
-  async listProjects(): Promise<Project[]> {
+  async listProjects(query: CatalogQuery<ProjectSort> = {}): Promise<Project[]> {
     try {
-      return await apiCall<Project[]>("list_projects");
+      return await apiCall<Project[]>("list_projects", { ...query });
     } catch (error) {
       console.error("Failed to list projects:", error);
       throw error;
@@ -491,11 +930,15 @@ export const api = {
   /**
    * Retrieves sessions for a specific project
    * @param projectId - The ID of the project to retrieve sessions for
+   * @param query - Optional pagination and sort order
    * @returns Promise resolving to an array of sessions
    */
-  async getProjectSessions(projectId: string): Promise<Session[]> {
+  async getProjectSessions(
+    projectId: string,
+    query: CatalogQuery<SessionSort> = {}
+  ): Promise<Session[]> {
     try {
-      return await apiCall<Session[]>('get_project_sessions', { projectId });
+      return await apiCall<Session[]>('get_project_sessions', { projectId, ...query });
     } catch (error) {
       console.error("Failed to get project sessions:", error);
       throw error;
@@ -503,12 +946,12 @@ export const api = {
   },
 
   /**
-   * Fetch list of agents from GitHub repository
-   * @returns Promise resolving to list of available agents on GitHub
+   * Fetch list of agents from all enabled agent sources
+   * @returns Promise resolving to the agents and the sources that failed
    */
-  async fetchGitHubAgents(): Promise<GitHubAgentFile[]> {
+  async fetchGitHubAgents(): Promise<AgentRegistryListing> {
     try {
-      return await apiCall<GitHubAgentFile[]>('fetch_github_agents');
+      return await apiCall<AgentRegistryListing>('fetch_github_agents');
     } catch (error) {
       console.error("Failed to fetch GitHub agents:", error);
       throw error;
@@ -516,13 +959,14 @@ export const api = {
   },
 
   /**
-   * Fetch and preview a specific agent from GitHub
+   * Fetch and preview a specific agent from an agent source
    * @param downloadUrl - The download URL for the agent file
-   * @returns Promise resolving to the agent export data
+   * @param sourceId - The source the agent was listed by
+   * @returns Promise resolving to the verified agent package and the commands it runs
    */
-  async fetchGitHubAgentContent(downloadUrl: string): Promise<AgentExport> {
+  async fetchGitHubAgentContent(downloadUrl: string, sourceId?: string): Promise<AgentImportPreview> {
     try {
-      return await apiCall<AgentExport>('fetch_github_agent_content', { downloadUrl });
+      return await apiCall<AgentImportPreview>('fetch_github_agent_content', { downloadUrl, sourceId });
     } catch (error) {
       console.error("Failed to fetch GitHub agent content:", error);
       throw error;
@@ -530,19 +974,216 @@ export const api = {
   },
 
   /**
-   * Import an agent directly from GitHub
+   * Import an agent directly from an agent source
    * @param downloadUrl - The download URL for the agent file
+   * @param sourceId - The source the agent was listed by
+   * @param sha - The SHA of the agent file in the listing, used to detect updates
+   * @param expectedSha256 - The SHA-256 of the previewed file, to import exactly that file
+   * @param installResources - Whether to install the bundled slash commands and MCP servers
    * @returns Promise resolving to the imported agent
    */
-  async importAgentFromGitHub(downloadUrl: string): Promise<Agent> {
-    try {
-      return await apiCall<Agent>('import_agent_from_github', { downloadUrl });
+  async importAgentFromGitHub(
+    downloadUrl: string,
+    sourceId?: string,
+    sha?: string,
+    expectedSha256?: string,
+    installResources?: boolean
+  ): Promise<AgentImportResult> {
+    try {
+      return await apiCall<AgentImportResult>('import_agent_from_github', {
+        downloadUrl,
+        sourceId,
+        sha,
+        expectedSha256,
+        installResources,
+      });
     } catch (error) {
       console.error("Failed to import agent from GitHub:", error);
       throw error;
     }
   },
 
+  /**
+   * Checks all agents imported from agent sources for upstream changes
+   * @returns Promise resolving to the available updates
+   */
+  async checkAgentUpdates(): Promise<AgentUpdate[]> {
+    try {
+      return await apiCall<AgentUpdate[]>('check_agent_updates');
+    } catch (error) {
+      console.error("Failed to check agent updates:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Applies the upstream update of an imported agent, keeping local overrides
+   * @param agentId - The agent ID
+   * @param upstreamSha256 - The upstream checksum reported by checkAgentUpdates; fails if the file changed since
+   * @param upstreamSha - The upstream SHA reported by checkAgentUpdates
+   * @param installResources - Whether to update and install the bundled slash commands and MCP servers
+   * @returns Promise resolving to the updated agent
+   */
+  async applyAgentUpdate(
+    agentId: number,
+    upstreamSha256: string,
+    upstreamSha?: string | null,
+    installResources?: boolean
+  ): Promise<AgentImportResult> {
+    try {
+      return await apiCall<AgentImportResult>('apply_agent_update', { agentId, upstreamSha256, upstreamSha, installResources });
+    } catch (error) {
+      console.error("Failed to apply agent update:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Verifies an agent file and lists what importing it would install and run
+   * @param filePath - The path to the agent file
+   * @returns Promise resolving to the import preview
+   */
+  async previewAgentImportFromFile(filePath: string): Promise<AgentImportPreview> {
+    try {
+      return await apiCall<AgentImportPreview>('preview_agent_import_from_file', { filePath });
+    } catch (error) {
+      console.error("Failed to preview agent import:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets the trusted publisher keys and verification policy for agent imports
+   * @returns Promise resolving to the trust settings
+   */
+  async getAgentTrustSettings(): Promise<AgentTrustSettings> {
+    try {
+      return await apiCall<AgentTrustSettings>('get_agent_trust_settings');
+    } catch (error) {
+      console.error("Failed to get agent trust settings:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Saves the trusted publisher keys and verification policy for agent imports
+   * @param settings - The trust settings
+   */
+  async saveAgentTrustSettings(settings: AgentTrustSettings): Promise<void> {
+    try {
+      return await apiCall<void>('save_agent_trust_settings', { settings });
+    } catch (error) {
+      console.error("Failed to save agent trust settings:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets the permission mode and the tools allowed per project
+   * @returns Promise resolving to the permission settings
+   */
+  async getPermissionSettings(): Promise<PermissionSettings> {
+    try {
+      return await apiCall<PermissionSettings>('get_permission_settings');
+    } catch (error) {
+      console.error("Failed to get permission settings:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Saves the permission mode and the tools allowed per project
+   * @param settings - The permission settings
+   */
+  async savePermissionSettings(settings: PermissionSettings): Promise<void> {
+    try {
+      return await apiCall<void>('save_permission_settings', { settings });
+    } catch (error) {
+      console.error("Failed to save permission settings:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Lists the permission requests waiting for an answer
+   * @returns Promise resolving to the pending requests
+   */
+  async listPermissionRequests(): Promise<PermissionRequest[]> {
+    try {
+      return await apiCall<PermissionRequest[]>('list_permission_requests');
+    } catch (error) {
+      console.error("Failed to list permission requests:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Answers a permission request
+   * @param requestId - The request ID
+   * @param decision - Allow once, for the session, for the project, or deny
+   */
+  async respondPermissionRequest(requestId: string, decision: PermissionDecision): Promise<void> {
+    try {
+      return await apiCall<void>('respond_permission_request', { requestId, decision });
+    } catch (error) {
+      console.error("Failed to respond to permission request:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets the retention policy for the stored output of agent runs
+   * @returns Promise resolving to the retention policy
+   */
+  async getAgentOutputRetention(): Promise<AgentOutputRetention> {
+    try {
+      return await apiCall<AgentOutputRetention>('get_agent_output_retention');
+    } catch (error) {
+      console.error("Failed to get agent output retention:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Saves and applies the retention policy for the stored output of agent runs
+   * @param retention - The retention policy
+   * @returns Promise resolving to the number of runs whose output was pruned
+   */
+  async saveAgentOutputRetention(retention: AgentOutputRetention): Promise<number> {
+    try {
+      return await apiCall<number>('save_agent_output_retention', { retention });
+    } catch (error) {
+      console.error("Failed to save agent output retention:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets the configured agent sources
+   * @returns Promise resolving to the sources, with their tokens redacted
+   */
+  async getAgentSources(): Promise<AgentSource[]> {
+    try {
+      return await apiCall<AgentSource[]>('get_agent_sources');
+    } catch (error) {
+      console.error("Failed to get agent sources:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Saves the agent sources
+   * @param sources - The sources to save
+   */
+  async saveAgentSources(sources: AgentSource[]): Promise<void> {
+    try {
+      return await apiCall<void>('save_agent_sources', { sources });
+    } catch (error) {
+      console.error("Failed to save agent sources:", error);
+      throw error;
+    }
+  },
+
   /**
    * Reads the Claude settings file
    * @returns Promise resolving to the settings object
@@ -700,6 +1341,9 @@ export const api = {
    * @param default_task - Optional default task
    * @param model - Optional model (defaults to 'sonnet')
    * @param hooks - Optional hooks configuration as JSON string
+   * @param limits - Optional per-agent overrides of the global run limits
+   * @param budget - Optional default budget for runs of this agent
+   * @param template - Optional task parameters and template using `{{name}}` placeholders
    * @returns Promise resolving to the created agent
    */
   async createAgent(
@@ -708,7 +1352,10 @@ export const api = {
     system_prompt: string, 
     default_task?: string, 
     model?: string,
-    hooks?: string
+    hooks?: string,
+    limits?: Partial<AgentRunLimits>,
+    budget?: AgentBudget,
+    template?: { parameters?: AgentParameter[]; task_template?: string }
   ): Promise<Agent> {
     try {
       return await apiCall<Agent>('create_agent', { 
@@ -717,7 +1364,14 @@ export const api = {
         systemPrompt: system_prompt,
         defaultTask: default_task,
         model,
-        hooks
+        hooks,
+        firstOutputTimeoutSecs: limits?.first_output_timeout_secs,
+        maxRuntimeSecs: limits?.max_runtime_secs,
+        idleTimeoutSecs: limits?.idle_timeout_secs,
+        maxCostUsd: budget?.max_cost_usd,
+        maxTokens: budget?.max_tokens,
+        parameters: template?.parameters ? JSON.stringify(template.parameters) : undefined,
+        taskTemplate: template?.task_template
       });
     } catch (error) {
       console.error("Failed to create agent:", error);
@@ -734,6 +1388,10 @@ export const api = {
    * @param default_task - Optional default task
    * @param model - Optional model
    * @param hooks - Optional hooks configuration as JSON string
+   * @param limits - Optional per-agent limit overrides in seconds; 0 disables a limit for the agent
+   * @param budget - Optional budget; values must be positive
+   * @param template - Optional task parameters and template; an empty template removes it
+   * @param clearOverrides - Limits and budgets to remove, so the global limit applies or the budget is unlimited
    * @returns Promise resolving to the updated agent
    */
   async updateAgent(
@@ -743,7 +1401,11 @@ export const api = {
     system_prompt: string, 
     default_task?: string, 
     model?: string,
-    hooks?: string
+    hooks?: string,
+    limits?: Partial<AgentRunLimits>,
+    budget?: AgentBudget,
+    template?: { parameters?: AgentParameter[]; task_template?: string },
+    clearOverrides?: (keyof AgentRunLimits | keyof AgentBudget)[]
   ): Promise<Agent> {
     try {
       return await apiCall<Agent>('update_agent', { 
@@ -753,7 +1415,15 @@ export const api = {
         systemPrompt: system_prompt,
         defaultTask: default_task,
         model,
-        hooks
+        hooks,
+        firstOutputTimeoutSecs: limits?.first_output_timeout_secs,
+        maxRuntimeSecs: limits?.max_runtime_secs,
+        idleTimeoutSecs: limits?.idle_timeout_secs,
+        maxCostUsd: budget?.max_cost_usd,
+        maxTokens: budget?.max_tokens,
+        parameters: template?.parameters ? JSON.stringify(template.parameters) : undefined,
+        taskTemplate: template?.task_template,
+        clearOverrides
       });
     } catch (error) {
       console.error("Failed to update agent:", error);
@@ -806,11 +1476,12 @@ export const api = {
   /**
    * Imports an agent from JSON data
    * @param jsonData - The JSON string containing the agent export
+   * @param installResources - Whether to install the bundled slash commands and MCP servers
    * @returns Promise resolving to the imported agent
    */
-  async importAgent(jsonData: string): Promise<Agent> {
+  async importAgent(jsonData: string, installResources?: boolean): Promise<AgentImportResult> {
     try {
-      return await apiCall<Agent>('import_agent', { jsonData });
+      return await apiCall<AgentImportResult>('import_agent', { jsonData, installResources });
     } catch (error) {
       console.error("Failed to import agent:", error);
       throw error;
@@ -820,28 +1491,99 @@ export const api = {
   /**
    * Imports an agent from a file
    * @param filePath - The path to the JSON file
+   * @param expectedSha256 - The SHA-256 of the previewed file, to import exactly that file
+   * @param installResources - Whether to install the bundled slash commands and MCP servers
    * @returns Promise resolving to the imported agent
    */
-  async importAgentFromFile(filePath: string): Promise<Agent> {
+  async importAgentFromFile(
+    filePath: string,
+    expectedSha256?: string,
+    installResources?: boolean
+  ): Promise<AgentImportResult> {
     try {
-      return await apiCall<Agent>('import_agent_from_file', { filePath });
+      return await apiCall<AgentImportResult>('import_agent_from_file', { filePath, expectedSha256, installResources });
     } catch (error) {
       console.error("Failed to import agent from file:", error);
       throw error;
     }
   },
 
+  /**
+   * Lists the versions of an agent, newest first
+   * @param agentId - The agent ID
+   * @returns Promise resolving to the agent's versions
+   */
+  async listAgentVersions(agentId: number): Promise<AgentVersion[]> {
+    try {
+      return await apiCall<AgentVersion[]>('list_agent_versions', { agentId });
+    } catch (error) {
+      console.error("Failed to list agent versions:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Lists the fields that changed between two versions of an agent
+   * @param agentId - The agent ID
+   * @param fromVersion - The older version
+   * @param toVersion - The newer version
+   * @returns Promise resolving to the changed fields
+   */
+  async diffAgentVersions(agentId: number, fromVersion: number, toVersion: number): Promise<AgentVersionChange[]> {
+    try {
+      return await apiCall<AgentVersionChange[]>('diff_agent_versions', { agentId, fromVersion, toVersion });
+    } catch (error) {
+      console.error("Failed to diff agent versions:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Restores an agent's prompt, model, hooks and permissions from an earlier version
+   * @param agentId - The agent ID
+   * @param version - The version to restore
+   * @returns Promise resolving to the updated agent
+   */
+  async rollbackAgentVersion(agentId: number, version: number): Promise<Agent> {
+    try {
+      return await apiCall<Agent>('rollback_agent_version', { agentId, version });
+    } catch (error) {
+      console.error("Failed to roll back agent version:", error);
+      throw error;
+    }
+  },
+
   /**
    * Executes an agent
    * @param agentId - The agent ID to execute
    * @param projectPath - The project path to run the agent in
    * @param task - The task description
    * @param model - Optional model override
+   * @param budget - Optional budget overriding the agent's default for this run
+   * @param isolate - Run in a fresh git worktree on its own branch instead of the project
+   * @param parameters - Values for the agent's task template; when given, the task is rendered from it
    * @returns Promise resolving to the run ID when execution starts
    */
-  async executeAgent(agentId: number, projectPath: string, task: string, model?: string): Promise<number> {
+  async executeAgent(
+    agentId: number,
+    projectPath: string,
+    task: string,
+    model?: string,
+    budget?: AgentBudget,
+    isolate?: boolean,
+    parameters?: Record<string, string | number>
+  ): Promise<number> {
     try {
-      return await apiCall<number>('execute_agent', { agentId, projectPath, task, model });
+      return await apiCall<number>('execute_agent', {
+        agentId,
+        projectPath,
+        task,
+        model,
+        maxCostUsd: budget?.max_cost_usd,
+        maxTokens: budget?.max_tokens,
+        isolate,
+        parameters
+      });
     } catch (error) {
       console.error("Failed to execute agent:", error);
       // Return a sentinel value to indicate error
@@ -961,6 +1703,45 @@ export const api = {
     }
   },
 
+  /**
+   * Gets what startup recovery did with the runs left running when the app last quit
+   * @returns Promise resolving to the report, or null while recovery is still running
+   */
+  async getRecoveryReport(): Promise<RecoveryReport | null> {
+    try {
+      return await apiCall<RecoveryReport | null>('get_recovery_report');
+    } catch (error) {
+      console.error("Failed to get recovery report:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets whether agent runs still alive at startup are re-adopted
+   * @returns Promise resolving to the recovery settings
+   */
+  async getRecoverySettings(): Promise<RecoverySettings> {
+    try {
+      return await apiCall<RecoverySettings>('get_recovery_settings');
+    } catch (error) {
+      console.error("Failed to get recovery settings:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Saves whether agent runs still alive at startup are re-adopted
+   * @param settings - The recovery settings
+   */
+  async saveRecoverySettings(settings: RecoverySettings): Promise<void> {
+    try {
+      return await apiCall<void>('save_recovery_settings', { settings });
+    } catch (error) {
+      console.error("Failed to save recovery settings:", error);
+      throw error;
+    }
+  },
+
   /**
    * Get real-time output for a running session (with live output fallback)
    * @param runId - The run ID to get output for
@@ -1027,31 +1808,176 @@ export const api = {
 
   /**
    * Executes a new interactive Claude Code session with streaming output
+   * @returns Promise resolving to the run ID; until Claude reports the session ID,
+   * events are scoped to it (`claude-run-output:<run>`, `claude-run-complete:<run>`, ...)
    */
-  async executeClaudeCode(projectPath: string, prompt: string, model: string): Promise<void> {
-    return apiCall("execute_claude_code", { projectPath, prompt, model });
+  async executeClaudeCode(
+    projectPath: string,
+    prompt: string,
+    model: string,
+    options?: ClaudeSessionOptions
+  ): Promise<number> {
+    return apiCall("execute_claude_code", { projectPath, prompt, model, options });
   },
 
   /**
    * Continues an existing Claude Code conversation with streaming output
+   * @returns Promise resolving to the run ID, see executeClaudeCode
    */
-  async continueClaudeCode(projectPath: string, prompt: string, model: string): Promise<void> {
-    return apiCall("continue_claude_code", { projectPath, prompt, model });
+  async continueClaudeCode(
+    projectPath: string,
+    prompt: string,
+    model: string,
+    options?: ClaudeSessionOptions
+  ): Promise<number> {
+    return apiCall("continue_claude_code", { projectPath, prompt, model, options });
   },
 
   /**
    * Resumes an existing Claude Code session by ID with streaming output
+   * @returns Promise resolving to the run ID, see executeClaudeCode
+   */
+  async resumeClaudeCode(
+    projectPath: string,
+    sessionId: string,
+    prompt: string,
+    model: string,
+    options?: ClaudeSessionOptions
+  ): Promise<number> {
+    return apiCall("resume_claude_code", { projectPath, sessionId, prompt, model, options });
+  },
+
+  /**
+   * Replays the output of a running session from a sequence number, then follows it
+   * until the session exits, ending with a line that carries the exit status in
+   * `success`. After a reload, pass the last sequence number seen
+   * plus one to pick up where the stream left off. Web clients attach to
+   * `/ws/claude/{stream_id}/attach?from_seq=N` instead.
+   * @param sessionId - The Claude session ID
+   * @param onLine - Called with each line of output
+   * @param fromSeq - The first sequence number to replay, 0 by default
+   * @returns Promise resolving to the oldest sequence number still buffered
    */
-  async resumeClaudeCode(projectPath: string, sessionId: string, prompt: string, model: string): Promise<void> {
-    return apiCall("resume_claude_code", { projectPath, sessionId, prompt, model });
+  async subscribeClaudeSessionOutput(
+    sessionId: string,
+    onLine: (line: OutputLine) => void,
+    fromSeq?: number
+  ): Promise<number> {
+    try {
+      const onOutput = new Channel<OutputLine>();
+      onOutput.onmessage = onLine;
+      return await apiCall<number>("subscribe_claude_session_output", { sessionId, fromSeq, onOutput });
+    } catch (error) {
+      console.error("Failed to subscribe to session output:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Like subscribeClaudeSessionOutput, for the run ID returned by executeClaudeCode
+   * and friends. Replaying from 0 includes the lines written before the run ID
+   * was known, so no output is missed. Runs that already exited can still be
+   * subscribed to for a minute, so their exit status is not missed either.
+   * @param runId - The run ID
+   * @param onLine - Called with each line of output
+   * @param fromSeq - The first sequence number to replay, 0 by default
+   * @returns Promise resolving to the oldest sequence number still buffered
+   */
+  async subscribeClaudeRunOutput(
+    runId: number,
+    onLine: (line: OutputLine) => void,
+    fromSeq?: number
+  ): Promise<number> {
+    try {
+      const onOutput = new Channel<OutputLine>();
+      onOutput.onmessage = onLine;
+      return await apiCall<number>("subscribe_claude_run_output", { runId, fromSeq, onOutput });
+    } catch (error) {
+      console.error("Failed to subscribe to run output:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Starts a long-lived Claude Code session that keeps running between turns.
+   * Output streams on `claude-output:<session>`; each turn ends with
+   * `claude-turn-complete:<session>`.
+   * @param projectPath - The project to run in
+   * @param prompt - The first prompt
+   * @param model - The model to use
+   * @param resumeSessionId - Optional session to resume
+   * @param options - Optional Claude CLI options
+   * @returns Promise resolving to the run ID of the session's process
+   */
+  async startClaudeSession(
+    projectPath: string,
+    prompt: string,
+    model: string,
+    resumeSessionId?: string,
+    options?: ClaudeSessionOptions
+  ): Promise<number> {
+    return apiCall("start_claude_session", { projectPath, prompt, model, resumeSessionId, options });
+  },
+
+  /**
+   * Sends a follow-up prompt to a session started with startClaudeSession
+   * @param sessionId - The session ID
+   * @param prompt - The prompt
+   */
+  async sendSessionMessage(sessionId: string, prompt: string): Promise<void> {
+    return apiCall("send_session_message", { sessionId, prompt });
+  },
+
+  /**
+   * Interrupts the current turn of a session started with startClaudeSession
+   * @param sessionId - The session ID
+   */
+  async interruptSession(sessionId: string): Promise<void> {
+    return apiCall("interrupt_session", { sessionId });
+  },
+
+  /**
+   * Closes a session started with startClaudeSession once its current turn ends
+   * @param sessionId - The session ID
+   */
+  async closeClaudeSession(sessionId: string): Promise<void> {
+    return apiCall("close_claude_session", { sessionId });
   },
 
   /**
    * Cancels the currently running Claude Code execution
    * @param sessionId - Optional session ID to cancel a specific session
+   * @param runId - Optional run ID to cancel a session whose ID is not known yet
+   * @returns Promise resolving to the stopped processes and the stage that ended each
    */
-  async cancelClaudeExecution(sessionId?: string): Promise<void> {
-    return apiCall("cancel_claude_execution", { sessionId });
+  async cancelClaudeExecution(sessionId?: string, runId?: number): Promise<CancelledProcess[]> {
+    return apiCall("cancel_claude_execution", { sessionId, runId });
+  },
+
+  /**
+   * Gets the grace periods used when cancelling sessions and agent runs
+   * @returns Promise resolving to the cancellation settings
+   */
+  async getCancellationSettings(): Promise<CancellationSettings> {
+    try {
+      return await apiCall<CancellationSettings>('get_cancellation_settings');
+    } catch (error) {
+      console.error("Failed to get cancellation settings:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Saves the grace periods used when cancelling sessions and agent runs
+   * @param settings - The cancellation settings
+   */
+  async saveCancellationSettings(settings: CancellationSettings): Promise<void> {
+    try {
+      return await apiCall<void>('save_cancellation_settings', { settings });
+    } catch (error) {
+      console.error("Failed to save cancellation settings:", error);
+      throw error;
+    }
   },
 
   /**
@@ -1583,6 +2509,145 @@ export const api = {
     }
   },
 
+  /**
+   * Gets the changes an isolated agent run made, as a unified diff
+   * @param runId - The run ID
+   * @returns Promise resolving to the diff against the run's base commit
+   */
+  async getAgentRunDiff(runId: number): Promise<string> {
+    try {
+      return await apiCall<string>("get_agent_run_diff", { runId });
+    } catch (error) {
+      console.error("Failed to get agent run diff:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Continues a finished agent run with a follow-up prompt, resuming its session
+   * @param runId - The run to continue
+   * @param prompt - The follow-up prompt
+   * @param model - Optional model override
+   * @returns Promise resolving to the ID of the follow-up run
+   */
+  async continueAgentRun(runId: number, prompt: string, model?: string): Promise<number> {
+    try {
+      return await apiCall<number>("continue_agent_run", { runId, prompt, model });
+    } catch (error) {
+      console.error("Failed to continue agent run:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Compares two agent runs side by side
+   * @param leftRunId - The first run ID
+   * @param rightRunId - The second run ID
+   * @returns Promise resolving to the aligned metrics, files and diffs of both runs
+   */
+  async compareAgentRuns(leftRunId: number, rightRunId: number): Promise<AgentRunComparison> {
+    try {
+      return await apiCall<AgentRunComparison>("compare_agent_runs", { leftRunId, rightRunId });
+    } catch (error) {
+      console.error("Failed to compare agent runs:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Runs an agent over many projects, a few at a time
+   * @param agentId - The agent ID
+   * @param projectPaths - The projects to run in, or null for every project
+   * @param task - The task for each run
+   * @param model - Optional model override
+   * @param budget - Optional budget applied to each run
+   * @param isolate - Run each project in its own git worktree
+   * @param parameters - Task parameter values
+   * @param concurrency - How many runs execute at the same time (default 3)
+   * @returns Promise resolving to the batch ID
+   */
+  async executeAgentBatch(
+    agentId: number,
+    projectPaths: string[] | null,
+    task: string,
+    model?: string,
+    budget?: AgentBudget,
+    isolate?: boolean,
+    parameters?: Record<string, string | number>,
+    concurrency?: number
+  ): Promise<number> {
+    try {
+      return await apiCall<number>("execute_agent_batch", {
+        agentId,
+        projectPaths,
+        task,
+        model,
+        maxCostUsd: budget?.max_cost_usd,
+        maxTokens: budget?.max_tokens,
+        isolate,
+        parameters,
+        concurrency,
+      });
+    } catch (error) {
+      console.error("Failed to execute agent batch:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Gets a batch with the status, cost and result of each of its runs
+   * @param batchId - The batch ID
+   * @returns Promise resolving to the batch summary
+   */
+  async getAgentBatch(batchId: number): Promise<AgentBatchSummary> {
+    try {
+      return await apiCall<AgentBatchSummary>("get_agent_batch", { batchId });
+    } catch (error) {
+      console.error("Failed to get agent batch:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Lists agent batches, newest first
+   * @returns Promise resolving to the batches
+   */
+  async listAgentBatches(): Promise<AgentBatch[]> {
+    try {
+      return await apiCall<AgentBatch[]>("list_agent_batches");
+    } catch (error) {
+      console.error("Failed to list agent batches:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Get the global time limits applied to agent runs
+   * @returns Promise resolving to the current limits
+   */
+  async getAgentRunLimits(): Promise<AgentRunLimits> {
+    try {
+      return await apiCall<AgentRunLimits>("get_agent_run_limits");
+    } catch (error) {
+      console.error("Failed to get agent run limits:", error);
+      throw error;
+    }
+  },
+
+  /**
+   * Save the global time limits applied to agent runs
+   * @param limits - The limits to save (0 disables a limit)
+   * @returns Promise resolving when the limits are saved
+   */
+  async saveAgentRunLimits(limits: AgentRunLimits): Promise<void> {
+    try {
+      return await apiCall<void>("save_agent_run_limits", { limits });
+    } catch (error) {
+      console.error("Failed to save agent run limits:", error);
+      throw error;
+    }
+  },
+
   /**
    * List all available Claude installations on the system
    * @returns Promise resolving to an array of Claude installations
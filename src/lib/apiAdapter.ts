@@ -304,6 +304,7 @@ async function handleStreamingCommand<T>(command: string, params?: any): Promise
         prompt: params?.prompt || '',
         model: params?.model || 'claude-3-5-sonnet-20241022',
         session_id: params?.sessionId,
+        options: params?.options,
       };
       
       console.log(`[TRACE] Sending WebSocket request:`, request);